@@ -0,0 +1,370 @@
+//! Mixer buses, which group sounds together so that their volume
+//! and playback can be controlled all at once.
+//!
+//! Every `AudioContext` has a master bus which everything else is
+//! eventually mixed into, plus a `music` and an `effects` bus routed
+//! into the master.  Sound sources play on the master bus unless they
+//! are assigned to another one with
+//! [`SoundSource::set_bus()`](../trait.SoundSource.html#tymethod.set_bus).
+
+use std::fmt;
+use std::mem;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time;
+
+use super::effect::{ChainProcessor, Effect, EffectChain, EffectHandle};
+use super::meter::{Meter, MeterBuffer};
+use super::output::AudioClock;
+use super::sink::Sink;
+
+/// A type-erased `rodio` source producing `f32` samples, as fed into a bus.
+pub(crate) type BoxedSource = Box<dyn rodio::Source<Item = f32> + Send>;
+
+/// State shared between a `Bus` handle and the `BusOutput` mixing for it.
+struct BusState {
+    name: String,
+    // `f32` stored as its bit pattern, since there's no `AtomicF32`.
+    volume: AtomicU32,
+    muted: AtomicBool,
+    paused: AtomicBool,
+    channels: u16,
+    sample_rate: u32,
+    has_pending: AtomicBool,
+    pending: Mutex<Vec<BoxedSource>>,
+    effects: EffectChain,
+    // Shared by every bus in the same tree.
+    clock: Arc<AudioClock>,
+    // Only set up once something asks for a meter, so that buses nobody
+    // measures don't pay for it.
+    meter: OnceLock<Arc<MeterBuffer>>,
+}
+
+/// A named mixer bus.  All sounds routed to a bus are mixed together,
+/// then scaled by the bus volume and fed into its parent bus.
+///
+/// This is a cheap handle to shared state, so it can be cloned freely;
+/// changing the volume, mute or pause state through any clone affects
+/// every sound currently playing on the bus, as well as those started later.
+#[derive(Clone)]
+pub struct Bus {
+    state: Arc<BusState>,
+}
+
+impl Bus {
+    /// Creates a new bus mixing into the returned `BusOutput`, which
+    /// must be fed into something that actually plays it.
+    pub(crate) fn new(name: &str, channels: u16, sample_rate: u32) -> (Bus, BusOutput) {
+        let clock = Arc::new(AudioClock::new(sample_rate));
+        Bus::with_clock(name, channels, sample_rate, clock)
+    }
+
+    fn with_clock(
+        name: &str,
+        channels: u16,
+        sample_rate: u32,
+        clock: Arc<AudioClock>,
+    ) -> (Bus, BusOutput) {
+        let state = Arc::new(BusState {
+            name: name.to_owned(),
+            volume: AtomicU32::new(1.0f32.to_bits()),
+            muted: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            channels,
+            sample_rate,
+            has_pending: AtomicBool::new(false),
+            pending: Mutex::new(Vec::new()),
+            effects: EffectChain::new(),
+            clock,
+            meter: OnceLock::new(),
+        });
+        let output = BusOutput {
+            effects: state.effects.processor(channels, sample_rate),
+            state: state.clone(),
+            current_sources: Vec::with_capacity(16),
+            still_current: Vec::with_capacity(16),
+            channel: 0,
+            holding: false,
+        };
+        (Bus { state }, output)
+    }
+
+    /// Creates a new bus that is mixed into this one.
+    pub(crate) fn add_child(&self, name: &str) -> Bus {
+        let (bus, output) = Bus::with_clock(
+            name,
+            self.state.channels,
+            self.state.sample_rate,
+            self.state.clock.clone(),
+        );
+        self.play_raw(output);
+        bus
+    }
+
+    /// Mixes the given source into this bus until it ends.
+    pub(crate) fn play_raw<S>(&self, source: S)
+    where
+        S: rodio::Source<Item = f32> + Send + 'static,
+    {
+        let source = rodio::source::UniformSourceIterator::new(
+            source,
+            self.state.channels,
+            self.state.sample_rate,
+        );
+        self.state
+            .pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Box::new(source));
+        self.state.has_pending.store(true, Ordering::Release);
+    }
+
+    /// Returns how many channels the bus mixes.
+    pub(crate) fn channels(&self) -> u16 {
+        self.state.channels
+    }
+
+    /// Creates a new `Sink` which plays on this bus.
+    pub(crate) fn new_sink(&self) -> Sink {
+        let (sink, output) = Sink::new(
+            self.state.channels,
+            self.state.sample_rate,
+            self.state.clock.clone(),
+        );
+        self.play_raw(output);
+        sink
+    }
+
+    /// Returns the audio clock, which counts the frames mixed by the
+    /// master bus this bus feeds into.
+    pub(crate) fn clock(&self) -> &Arc<AudioClock> {
+        &self.state.clock
+    }
+
+    /// Returns the name of the bus.
+    pub fn name(&self) -> &str {
+        &self.state.name
+    }
+
+    /// Gets the volume of the bus.
+    pub fn volume(&self) -> f32 {
+        f32::from_bits(self.state.volume.load(Ordering::Relaxed))
+    }
+
+    /// Sets the volume of the bus.  `1.0` leaves the mixed sounds
+    /// untouched, any other value scales them.
+    pub fn set_volume(&self, value: f32) {
+        self.state.volume.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Gets whether or not the bus is muted.
+    pub fn muted(&self) -> bool {
+        self.state.muted.load(Ordering::Relaxed)
+    }
+
+    /// Mutes or unmutes the bus.  Muted sounds keep playing, they just
+    /// can't be heard.
+    pub fn set_muted(&self, muted: bool) {
+        self.state.muted.store(muted, Ordering::Relaxed);
+    }
+
+    /// Gets whether or not the bus is paused.
+    pub fn paused(&self) -> bool {
+        self.state.paused.load(Ordering::Relaxed)
+    }
+
+    /// Pauses every sound playing on the bus, and on any bus mixed into it.
+    pub fn pause(&self) {
+        self.state.paused.store(true, Ordering::Relaxed);
+    }
+
+    /// Resumes playback after [`pause()`](#method.pause).
+    pub fn resume(&self) {
+        self.state.paused.store(false, Ordering::Relaxed);
+    }
+
+    /// Adds an effect to the bus, applied to everything mixed into it
+    /// after any effects added before.
+    pub fn add_effect(&self, effect: Effect) -> EffectHandle {
+        self.state.effects.add(effect)
+    }
+
+    /// Removes every effect from the bus.
+    pub fn clear_effects(&self) {
+        self.state.effects.clear()
+    }
+
+    /// Returns a [`Meter`](struct.Meter.html) measuring what the bus plays.
+    ///
+    /// Metering starts the first time this is called, so the meter reads
+    /// silence until the bus has played a little more.
+    pub fn meter(&self) -> Meter {
+        let buffer = self.state.meter.get_or_init(|| {
+            Arc::new(MeterBuffer::new(
+                self.state.channels,
+                self.state.sample_rate,
+            ))
+        });
+        Meter::new(buffer.clone())
+    }
+}
+
+impl fmt::Debug for Bus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<Audio bus '{}': {self:p}>", self.state.name)
+    }
+}
+
+/// The mixed output of a `Bus`, as a never-ending `rodio` source.
+pub(crate) struct BusOutput {
+    state: Arc<BusState>,
+    current_sources: Vec<BoxedSource>,
+    // Scratch space, kept around to avoid allocating while mixing.
+    still_current: Vec<BoxedSource>,
+    // Which channel of the current frame the next sample is for.
+    channel: u16,
+    // Whether the bus is paused, as of the start of the current frame.
+    holding: bool,
+    effects: ChainProcessor,
+}
+
+impl BusOutput {
+    fn start_pending_sources(&mut self) {
+        let mut pending = self
+            .state
+            .pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.current_sources.append(&mut pending);
+        self.state.has_pending.store(false, Ordering::Release);
+    }
+
+    fn mix(&mut self) -> f32 {
+        let channel = self.channel;
+        self.channel += 1;
+        if self.channel == self.state.channels {
+            self.channel = 0;
+        }
+        // Samples are interleaved, so sources may only start, pause or resume
+        // on a frame boundary or their channels would come out swapped.
+        if channel == 0 {
+            self.holding = self.state.paused.load(Ordering::Relaxed);
+            if !self.holding && self.state.has_pending.load(Ordering::Acquire) {
+                self.start_pending_sources();
+            }
+        }
+        // A paused bus stops pulling from its sources, so they resume where they were.
+        if self.holding {
+            return 0.0;
+        }
+
+        let mut sum = 0.0;
+        for mut source in self.current_sources.drain(..) {
+            if let Some(sample) = source.next() {
+                sum += sample;
+                self.still_current.push(source);
+            }
+        }
+        mem::swap(&mut self.still_current, &mut self.current_sources);
+        let sum = self.effects.process(sum, channel);
+
+        if self.state.muted.load(Ordering::Relaxed) {
+            0.0
+        } else {
+            sum * f32::from_bits(self.state.volume.load(Ordering::Relaxed))
+        }
+    }
+}
+
+impl Iterator for BusOutput {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.mix();
+        if let Some(meter) = self.state.meter.get() {
+            meter.push(sample);
+        }
+        Some(sample)
+    }
+}
+
+impl rodio::Source for BusOutput {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.state.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.state.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<time::Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    #[test]
+    fn mixes_and_scales() {
+        let (bus, mut output) = Bus::new("test", 1, 44100);
+        bus.play_raw(SamplesBuffer::new(1, 44100, vec![0.25f32, 0.25]));
+        bus.play_raw(SamplesBuffer::new(1, 44100, vec![0.5f32]));
+        bus.set_volume(2.0);
+        assert_eq!(output.next(), Some(1.5));
+        assert_eq!(output.next(), Some(0.5));
+        // Buses keep going even once they have nothing left to play.
+        assert_eq!(output.next(), Some(0.0));
+    }
+
+    #[test]
+    fn child_bus_pause_and_mute() {
+        let (master, mut output) = Bus::new("master", 1, 44100);
+        let music = master.add_child("music");
+        music.play_raw(SamplesBuffer::new(1, 44100, vec![0.5f32, 0.25]));
+
+        music.pause();
+        assert_eq!(output.next(), Some(0.0));
+        music.resume();
+        assert_eq!(output.next(), Some(0.5));
+        master.set_muted(true);
+        assert_eq!(output.next(), Some(0.0));
+        master.set_muted(false);
+        assert_eq!(output.next(), Some(0.0));
+    }
+
+    #[test]
+    fn pauses_on_frame_boundaries() {
+        let (bus, mut output) = Bus::new("test", 2, 44100);
+        bus.play_raw(SamplesBuffer::new(2, 44100, vec![0.1f32, 0.2, 0.3, 0.4]));
+        assert_eq!(output.next(), Some(0.1));
+        // Pausing halfway through a frame lets the frame finish first.
+        bus.pause();
+        assert_eq!(output.next(), Some(0.2));
+        assert_eq!(output.next(), Some(0.0));
+        bus.resume();
+        assert_eq!(output.next(), Some(0.0));
+        assert_eq!(output.next(), Some(0.3));
+        assert_eq!(output.next(), Some(0.4));
+    }
+
+    #[test]
+    fn effects_apply_to_the_mix() {
+        let (bus, mut output) = Bus::new("test", 1, 44100);
+        let distortion = bus.add_effect(Effect::Distortion {
+            drive: 2.0,
+            mix: 1.0,
+        });
+        bus.play_raw(SamplesBuffer::new(1, 44100, vec![0.25f32, 0.25]));
+        bus.play_raw(SamplesBuffer::new(1, 44100, vec![0.25f32, 0.25]));
+        assert_eq!(output.next(), Some(1.0f32.tanh()));
+        distortion.remove();
+        assert_eq!(output.next(), Some(0.5));
+    }
+}
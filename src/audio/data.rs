@@ -0,0 +1,875 @@
+//! The different places a sound source can get its samples from.
+
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::thread;
+use std::time;
+
+use super::bus::BoxedSource;
+use super::looping::LoopPoints;
+use super::metadata::{self, SoundMetadata};
+#[cfg(feature = "tracker")]
+use super::tracker::ModuleData;
+use super::SoundData;
+use crate::error::{GameError, GameResult};
+use crate::filesystem::File;
+
+/// How many frames a generator is asked to fill at a time.  Small enough
+/// that changes to whatever drives the generator are heard quickly, big
+/// enough that it isn't locked for every single sample.
+const GENERATOR_BUFFER_FRAMES: usize = 512;
+
+/// The data played by a `Source` or `SpatialSource`.
+#[derive(Clone, Debug)]
+pub(crate) enum SourceData {
+    /// Sound data in some encoded format, such as OGG or WAV.
+    Encoded(EncodedData),
+    /// Raw PCM samples held in memory.
+    Samples(SamplesData),
+    /// Samples produced on the fly by a callback.
+    Generator(GeneratorData),
+    /// A tracker module, played note by note.
+    #[cfg(feature = "tracker")]
+    Module(ModuleData),
+}
+
+impl SourceData {
+    /// Indicates if the data can be played as a sound.
+    pub fn can_play(&self) -> bool {
+        match self {
+            SourceData::Encoded(data) => data.decoder().is_ok(),
+            _ => true,
+        }
+    }
+
+    /// Reads the loop points the data is tagged with, if any.
+    pub fn loop_points(&self) -> Option<LoopPoints> {
+        match self {
+            SourceData::Encoded(data) => data.loop_points(),
+            SourceData::Samples(data) => data.loop_points,
+            _ => None,
+        }
+    }
+
+    /// Starts playing the data from the given frame.  Anything but raw
+    /// samples has to be decoded up to there, so this can take a while.
+    fn open_at(&self, frame: u64) -> GameResult<BoxedSource> {
+        if let SourceData::Samples(data) = self {
+            let pos = frame.saturating_mul(u64::from(data.channels));
+            return Ok(Box::new(SamplesPlayback {
+                data: data.clone(),
+                pos: pos.min(data.samples.len() as u64) as usize,
+            }));
+        }
+        let mut input = self.open()?;
+        let skip = frame * u64::from(input.channels());
+        for _ in 0..skip {
+            if input.next().is_none() {
+                break;
+            }
+        }
+        Ok(input)
+    }
+
+    /// Starts playing the data from the very beginning.
+    fn open(&self) -> GameResult<BoxedSource> {
+        use rodio::Source;
+
+        Ok(match self {
+            SourceData::Encoded(data) => Box::new(data.decoder()?.convert_samples()),
+            SourceData::Samples(data) => Box::new(SamplesPlayback {
+                data: data.clone(),
+                pos: 0,
+            }),
+            SourceData::Generator(data) => Box::new(GeneratorPlayback {
+                data: data.clone(),
+                buffer: vec![0.0; GENERATOR_BUFFER_FRAMES * usize::from(data.channels)],
+                pos: 0,
+                len: 0,
+                finished: false,
+            }),
+            #[cfg(feature = "tracker")]
+            SourceData::Module(data) => data.open(),
+        })
+    }
+}
+
+/// Encoded sound data, and where it is read from.
+#[derive(Clone, Debug)]
+pub(crate) enum EncodedData {
+    /// Held entirely in memory.
+    Memory(SoundData),
+    /// Read from a file bit by bit as it plays.
+    Stream(StreamReader),
+}
+
+impl EncodedData {
+    fn reader(&self) -> SourceReader {
+        match self {
+            EncodedData::Memory(data) => SourceReader::Memory(io::Cursor::new(data.clone())),
+            EncodedData::Stream(reader) => {
+                SourceReader::Stream(io::BufReader::new(reader.rewound()))
+            }
+        }
+    }
+
+    /// Creates a decoder reading from the very start of the data.
+    pub fn decoder(&self) -> GameResult<rodio::Decoder<SourceReader>> {
+        Ok(rodio::Decoder::new(self.reader())?)
+    }
+
+    /// Reads the loop points from the tags of the data, if it has any.
+    pub fn loop_points(&self) -> Option<LoopPoints> {
+        let (_, tags) = metadata::read_tags(self.reader());
+        LoopPoints::from_tags(&tags)
+    }
+
+    /// Reads everything there is to know about the data without playing it.
+    pub fn metadata(&self) -> GameResult<SoundMetadata> {
+        use rodio::Source;
+
+        let decoder = self.decoder()?;
+        let (format, tags) = metadata::read_tags(self.reader());
+        Ok(SoundMetadata {
+            format,
+            channels: decoder.channels(),
+            sample_rate: decoder.sample_rate(),
+            duration: self.duration(),
+            tags,
+        })
+    }
+
+    /// Works out how long the sound is, by decoding all of it if the format
+    /// doesn't say.  Returns `None` if it can't be decoded at all.
+    pub fn duration(&self) -> Option<time::Duration> {
+        use rodio::Source;
+
+        let decoder = self.decoder().ok()?;
+        if let Some(duration) = decoder.total_duration() {
+            return Some(duration);
+        }
+        let channels = decoder.channels().max(1);
+        let sample_rate = decoder.sample_rate().max(1);
+        let frames = decoder.count() / usize::from(channels);
+        Some(time::Duration::from_secs_f64(
+            frames as f64 / f64::from(sample_rate),
+        ))
+    }
+}
+
+fn check_format(channels: u16, sample_rate: u32) -> GameResult {
+    if channels == 0 || sample_rate == 0 {
+        return Err(GameError::AudioError(format!(
+            "Invalid sample format: {channels} channels at {sample_rate} Hz"
+        )));
+    }
+    Ok(())
+}
+
+/// Interleaved `f32` samples, shared between every playback.
+#[derive(Clone, Debug)]
+pub(crate) struct SamplesData {
+    channels: u16,
+    sample_rate: u32,
+    samples: Arc<[f32]>,
+    // Kept from the tags of the data the samples were decoded from, if any.
+    loop_points: Option<LoopPoints>,
+}
+
+impl SamplesData {
+    pub fn new(channels: u16, sample_rate: u32, samples: Arc<[f32]>) -> GameResult<Self> {
+        check_format(channels, sample_rate)?;
+        Ok(SamplesData {
+            channels,
+            sample_rate,
+            samples,
+            loop_points: None,
+        })
+    }
+
+    /// Decodes the whole of the given data into samples.
+    pub fn decode(data: &EncodedData) -> GameResult<Self> {
+        use rodio::Source;
+
+        let decoder = data.decoder()?;
+        let channels = decoder.channels();
+        let sample_rate = decoder.sample_rate();
+        let samples: Vec<f32> = decoder.convert_samples().collect();
+        let mut decoded = SamplesData::new(channels, sample_rate, samples.into())?;
+        decoded.loop_points = data.loop_points();
+        Ok(decoded)
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub fn loop_points(&self) -> Option<LoopPoints> {
+        self.loop_points
+    }
+
+    pub fn duration(&self) -> time::Duration {
+        let frames = self.samples.len() / usize::from(self.channels);
+        time::Duration::from_secs_f64(frames as f64 / f64::from(self.sample_rate))
+    }
+}
+
+/// The callback behind a generated sound.
+pub(crate) type GeneratorFn = dyn FnMut(&mut [f32]) -> usize + Send;
+
+/// A callback producing interleaved `f32` samples, shared between every playback.
+#[derive(Clone)]
+pub(crate) struct GeneratorData {
+    channels: u16,
+    sample_rate: u32,
+    callback: Arc<Mutex<GeneratorFn>>,
+}
+
+impl GeneratorData {
+    pub fn new<F>(channels: u16, sample_rate: u32, callback: F) -> GameResult<Self>
+    where
+        F: FnMut(&mut [f32]) -> usize + Send + 'static,
+    {
+        check_format(channels, sample_rate)?;
+        Ok(GeneratorData {
+            channels,
+            sample_rate,
+            callback: Arc::new(Mutex::new(callback)),
+        })
+    }
+}
+
+impl fmt::Debug for GeneratorData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "<Sound generator: {} channels at {} Hz>",
+            self.channels, self.sample_rate
+        )
+    }
+}
+
+/// A reader over a file that is shared with other readers, each with its
+/// own position.  This lets every playback of a streaming source decode
+/// independently, without needing the `Filesystem` to reopen the file.
+#[derive(Clone, Debug)]
+pub(crate) struct StreamReader {
+    file: Arc<Mutex<File>>,
+    pos: u64,
+}
+
+impl StreamReader {
+    pub fn new(file: File) -> Self {
+        StreamReader {
+            file: Arc::new(Mutex::new(file)),
+            pos: 0,
+        }
+    }
+
+    /// Returns a new reader over the same file, starting at the beginning.
+    fn rewound(&self) -> Self {
+        StreamReader {
+            file: self.file.clone(),
+            pos: 0,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, File> {
+        // Readers always seek before touching the file, so a reader that
+        // panicked halfway through can't leave it in a bad state.
+        self.file.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Read for StreamReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let mut file = self.lock();
+            let _ = file.seek(SeekFrom::Start(self.pos))?;
+            file.read(buf)?
+        };
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for StreamReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = match pos {
+            SeekFrom::Start(offset) => offset,
+            SeekFrom::End(_) => self.lock().seek(pos)?,
+            SeekFrom::Current(offset) => self
+                .pos
+                .checked_add_signed(offset)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid seek"))?,
+        };
+        Ok(self.pos)
+    }
+}
+
+/// What a `rodio::Decoder` reads `EncodedData` through.
+pub(crate) enum SourceReader {
+    Memory(io::Cursor<SoundData>),
+    Stream(io::BufReader<StreamReader>),
+}
+
+impl Read for SourceReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            SourceReader::Memory(r) => r.read(buf),
+            SourceReader::Stream(r) => r.read(buf),
+        }
+    }
+}
+
+impl Seek for SourceReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            SourceReader::Memory(r) => r.seek(pos),
+            SourceReader::Stream(r) => r.seek(pos),
+        }
+    }
+}
+
+/// Plays back `SamplesData`.
+struct SamplesPlayback {
+    data: SamplesData,
+    pos: usize,
+}
+
+impl Iterator for SamplesPlayback {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = *self.data.samples.get(self.pos)?;
+        self.pos += 1;
+        Some(sample)
+    }
+}
+
+impl rodio::Source for SamplesPlayback {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.data.samples.len() - self.pos)
+    }
+
+    fn channels(&self) -> u16 {
+        self.data.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.data.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<time::Duration> {
+        Some(self.data.duration())
+    }
+}
+
+/// Plays back a `GeneratorData`, asking it for a buffer's worth of samples at a time.
+struct GeneratorPlayback {
+    data: GeneratorData,
+    buffer: Vec<f32>,
+    pos: usize,
+    len: usize,
+    finished: bool,
+}
+
+impl GeneratorPlayback {
+    fn refill(&mut self) {
+        let mut callback = self
+            .data
+            .callback
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let len = callback(&mut self.buffer).min(self.buffer.len());
+        // Only ever play whole frames, so that channels can't get swapped around.
+        self.len = len - len % usize::from(self.data.channels);
+        self.pos = 0;
+        self.finished = len < self.buffer.len();
+    }
+}
+
+impl Iterator for GeneratorPlayback {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.pos == self.len {
+            if self.finished {
+                return None;
+            }
+            self.refill();
+            if self.len == 0 {
+                return None;
+            }
+        }
+        let sample = self.buffer[self.pos];
+        self.pos += 1;
+        Some(sample)
+    }
+}
+
+impl rodio::Source for GeneratorPlayback {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.data.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.data.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<time::Duration> {
+        None
+    }
+}
+
+/// How far into its data the playback of a source is, as published
+/// from the audio thread.
+#[derive(Debug, Default)]
+pub(crate) struct PlaybackPosition {
+    frame: AtomicU64,
+    sample_rate: AtomicU32,
+}
+
+impl PlaybackPosition {
+    pub fn get(&self) -> time::Duration {
+        let frame = self.frame.load(Ordering::Relaxed);
+        let sample_rate = self.sample_rate.load(Ordering::Relaxed);
+        if sample_rate == 0 {
+            return time::Duration::ZERO;
+        }
+        time::Duration::from_secs_f64(frame as f64 / f64::from(sample_rate))
+    }
+
+    pub fn reset(&self) {
+        self.frame.store(0, Ordering::Relaxed);
+    }
+
+    fn set(&self, frame: u64, sample_rate: u32) {
+        self.frame.store(frame, Ordering::Relaxed);
+        self.sample_rate.store(sample_rate, Ordering::Relaxed);
+    }
+}
+
+/// Asks the loop worker to open some data at the given frame, and says
+/// where to send the opened input.
+struct LoopRequest {
+    data: SourceData,
+    frame: u64,
+    reply: mpsc::Sender<GameResult<BoxedSource>>,
+}
+
+/// Returns a handle to the loop worker: a single thread, shared by every
+/// playback, which opens the inputs for their next times around the loop, so
+/// that creating a decoder and decoding up to the loop start doesn't hold up
+/// the audio thread.  It's started the first time it's needed.
+fn loop_worker() -> mpsc::Sender<LoopRequest> {
+    static WORKER: OnceLock<Mutex<mpsc::Sender<LoopRequest>>> = OnceLock::new();
+    WORKER
+        .get_or_init(|| {
+            let (sender, receiver) = mpsc::channel::<LoopRequest>();
+            let _ = thread::spawn(move || {
+                for request in receiver {
+                    let _ = request.reply.send(request.data.open_at(request.frame));
+                }
+            });
+            Mutex::new(sender)
+        })
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// The inputs for a playback's next times around its loop, opened one ahead
+/// of when they're needed by the loop worker.
+struct LoopInputs {
+    worker: mpsc::Sender<LoopRequest>,
+    reply: mpsc::Sender<GameResult<BoxedSource>>,
+    replies: mpsc::Receiver<GameResult<BoxedSource>>,
+}
+
+impl LoopInputs {
+    fn new() -> Self {
+        let (reply, replies) = mpsc::channel();
+        LoopInputs {
+            worker: loop_worker(),
+            reply,
+            replies,
+        }
+    }
+
+    fn request(&self, data: &SourceData, frame: u64) -> GameResult {
+        let request = LoopRequest {
+            data: data.clone(),
+            frame,
+            reply: self.reply.clone(),
+        };
+        self.worker
+            .send(request)
+            .map_err(|_| GameError::AudioError("Loop decoding thread stopped".to_owned()))
+    }
+}
+
+/// The base of everything played by a source: plays its `SourceData`
+/// from a given point, starting over from the loop start when it reaches
+/// the loop end if it is set to repeat, and keeps track of how far along it is.
+pub(crate) struct Playback {
+    data: SourceData,
+    input: BoxedSource,
+    repeat: bool,
+    position: Arc<PlaybackPosition>,
+    frame: u64,
+    channel: u16,
+    loop_start: u64,
+    loop_end: Option<u64>,
+    // The inputs for the next times around the loop, when the data needs
+    // decoding, opened ahead of time.
+    next_loop: Option<LoopInputs>,
+    // The input for the first time around the loop, opened along with the
+    // playback so that it's ready however soon the loop comes around.
+    first_loop: Option<BoxedSource>,
+}
+
+impl Playback {
+    /// Creates a new playback of the data, starting `start` into it.
+    ///
+    /// The samples before `start` are skipped right away, on the calling
+    /// thread, rather than holding up the audio thread once it is played.
+    /// Loop points only apply when repeating, and are ignored for generators,
+    /// which have no beginning to go back to.
+    pub fn new(
+        data: &SourceData,
+        repeat: bool,
+        loop_points: Option<LoopPoints>,
+        start: time::Duration,
+        position: Arc<PlaybackPosition>,
+    ) -> GameResult<Self> {
+        let input = data.open()?;
+        let sample_rate = input.sample_rate();
+        let skip =
+            (start.as_secs_f64() * f64::from(sample_rate)) as u64 * u64::from(input.channels());
+        let (loop_start, loop_end) = match loop_points {
+            Some(points) if repeat && !matches!(data, SourceData::Generator(_)) => {
+                let start = points.start.frame(sample_rate);
+                let end = points.end.map(|end| end.frame(sample_rate));
+                (start, end.filter(|&end| end > start))
+            }
+            _ => (0, None),
+        };
+        let mut playback = Playback {
+            data: data.clone(),
+            input,
+            repeat,
+            position,
+            frame: 0,
+            channel: 0,
+            loop_start,
+            loop_end,
+            next_loop: None,
+            first_loop: None,
+        };
+        playback.prepare_loop()?;
+        for _ in 0..skip {
+            if playback.pull().is_none() {
+                break;
+            }
+        }
+        Ok(playback)
+    }
+
+    /// Opens the inputs for the loops ahead of time, if they need decoding,
+    /// even when the loop starts at the beginning: creating a decoder reads
+    /// and parses the data, which may mean waiting on a file.  Raw samples
+    /// can jump straight to the loop start instead.
+    fn prepare_loop(&mut self) -> GameResult {
+        if self.repeat && matches!(self.data, SourceData::Encoded(_)) {
+            self.first_loop = Some(self.data.open_at(self.loop_start)?);
+            self.next_loop = Some(LoopInputs::new());
+        }
+        Ok(())
+    }
+
+    /// Returns the input for the next time around the loop, or `None` if the
+    /// loop worker is still opening it, and asks for the one after.
+    fn next_loop_input(&mut self) -> GameResult<Option<BoxedSource>> {
+        let Some(next_loop) = &self.next_loop else {
+            return self.data.open_at(self.loop_start).map(Some);
+        };
+        let input = match self.first_loop.take() {
+            Some(input) => input,
+            None => match next_loop.replies.try_recv() {
+                Ok(input) => input?,
+                Err(_) => return Ok(None),
+            },
+        };
+        next_loop.request(&self.data, self.loop_start)?;
+        Ok(Some(input))
+    }
+
+    fn pull(&mut self) -> Option<f32> {
+        let at_loop_end = self.channel == 0 && self.loop_end.is_some_and(|end| self.frame >= end);
+        let next = if at_loop_end { None } else { self.input.next() };
+        let sample = match next {
+            Some(sample) => sample,
+            None if self.repeat => {
+                // Playing again from the loop start, rather than buffering the
+                // samples played the first time around, keeps long tracks out of
+                // memory.  If that gives us nothing at all, stop instead of
+                // spinning forever.  Starting over halfway through a frame
+                // would get the channels mixed up.
+                let input = if self.channel == 0 {
+                    self.next_loop_input().ok()?
+                } else {
+                    None
+                };
+                match input {
+                    Some(input) => {
+                        self.input = input;
+                        self.frame = self.loop_start;
+                        self.channel = 0;
+                        self.input.next()?
+                    }
+                    // The input is almost always ready long before it's needed,
+                    // but if it isn't, play silence rather than wait for it.
+                    None => 0.0,
+                }
+            }
+            None => return None,
+        };
+        self.channel += 1;
+        if self.channel >= self.input.channels() {
+            self.channel = 0;
+            self.frame += 1;
+        }
+        Some(sample)
+    }
+}
+
+impl Iterator for Playback {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.pull();
+        if self.channel == 0 {
+            self.position.set(self.frame, self.input.sample_rate());
+        }
+        sample
+    }
+}
+
+impl rodio::Source for Playback {
+    fn current_frame_len(&self) -> Option<usize> {
+        if self.repeat {
+            None
+        } else {
+            self.input.current_frame_len()
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<time::Duration> {
+        if self.repeat {
+            None
+        } else {
+            self.input.total_duration()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path;
+
+    fn sound_path() -> path::PathBuf {
+        path::Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/pew.ogg")
+    }
+
+    fn play(data: &SourceData, repeat: bool) -> Playback {
+        Playback::new(data, repeat, None, time::Duration::ZERO, Default::default()).unwrap()
+    }
+
+    #[test]
+    fn streaming_matches_memory() {
+        let bytes = std::fs::read(sound_path()).unwrap();
+        let memory = SourceData::Encoded(EncodedData::Memory(SoundData::from(bytes)));
+        let file = std::fs::File::open(sound_path()).unwrap();
+        let stream = SourceData::Encoded(EncodedData::Stream(StreamReader::new(File::VfsFile(
+            Box::new(file),
+        ))));
+
+        let from_memory: Vec<f32> = play(&memory, false).collect();
+        let from_stream: Vec<f32> = play(&stream, false).collect();
+        assert!(!from_memory.is_empty());
+        assert_eq!(from_memory, from_stream);
+
+        // Repeating starts decoding the file over again, on the loop worker
+        // after the first time around.
+        let mut playback = play(&stream, true);
+        assert!(playback.next_loop.is_some());
+        let repeated: Vec<f32> = playback.by_ref().take(from_memory.len() * 2).collect();
+        assert_eq!(repeated[..from_memory.len()], from_memory[..]);
+        assert_eq!(repeated[from_memory.len()..], from_memory[..]);
+        let next = playback.next_loop.as_ref().unwrap().replies.recv().unwrap();
+        assert_eq!(next.unwrap().collect::<Vec<f32>>(), from_memory);
+    }
+
+    #[test]
+    fn encoded_duration() {
+        use rodio::Source;
+
+        let bytes = std::fs::read(sound_path()).unwrap();
+        let data = EncodedData::Memory(SoundData::from(bytes));
+        let decoder = data.decoder().unwrap();
+        let (channels, sample_rate) = (decoder.channels(), decoder.sample_rate());
+        let frames = decoder.count() / usize::from(channels);
+        let duration = data.duration().unwrap();
+        assert_eq!(
+            (duration.as_secs_f64() * f64::from(sample_rate)).round() as usize,
+            frames
+        );
+    }
+
+    #[test]
+    fn samples_repeat() {
+        let data = SamplesData::new(2, 44100, Arc::from(vec![0.1, 0.2, 0.3, 0.4])).unwrap();
+        let data = SourceData::Samples(data);
+        let once: Vec<f32> = play(&data, false).collect();
+        assert_eq!(once, [0.1, 0.2, 0.3, 0.4]);
+        let repeated: Vec<f32> = play(&data, true).take(6).collect();
+        assert_eq!(repeated, [0.1, 0.2, 0.3, 0.4, 0.1, 0.2]);
+
+        assert!(SamplesData::new(0, 44100, Arc::from(vec![])).is_err());
+    }
+
+    #[test]
+    fn start_and_position() {
+        let samples: Vec<f32> = (0..8).map(|i| i as f32).collect();
+        let data = SourceData::Samples(SamplesData::new(2, 2, Arc::from(samples)).unwrap());
+        let position = Arc::new(PlaybackPosition::default());
+
+        // One second in is two frames in, or four samples.
+        let mut playback = Playback::new(
+            &data,
+            true,
+            None,
+            time::Duration::from_secs(1),
+            position.clone(),
+        )
+        .unwrap();
+        assert_eq!(playback.next(), Some(4.0));
+        assert_eq!(playback.next(), Some(5.0));
+        assert_eq!(position.get(), time::Duration::from_millis(1500));
+        assert_eq!(playback.nth(3), Some(1.0));
+        // Back at the start after repeating, one frame in.
+        assert_eq!(position.get(), time::Duration::from_millis(500));
+    }
+
+    #[test]
+    fn loop_points() {
+        use super::super::looping::LoopPoint;
+
+        let samples: Vec<f32> = (0..6).map(|i| i as f32).collect();
+        let data = SourceData::Samples(SamplesData::new(1, 10, Arc::from(samples)).unwrap());
+        let points = LoopPoints::new(LoopPoint::Frame(1), Some(LoopPoint::Frame(4)));
+        let looped: Vec<f32> = Playback::new(
+            &data,
+            true,
+            Some(points),
+            time::Duration::ZERO,
+            Default::default(),
+        )
+        .unwrap()
+        .take(10)
+        .collect();
+        assert_eq!(looped, [0.0, 1.0, 2.0, 3.0, 1.0, 2.0, 3.0, 1.0, 2.0, 3.0]);
+
+        // Encoded data gets decoded up to the loop start in the background.
+        let bytes = std::fs::read(sound_path()).unwrap();
+        let data = SourceData::Encoded(EncodedData::Memory(SoundData::from(bytes)));
+        let once: Vec<f32> = play(&data, false).collect();
+        let channels = usize::from(data.open().unwrap().channels());
+        let points = LoopPoints::new(LoopPoint::Frame(100), None);
+        let looped: Vec<f32> = Playback::new(
+            &data,
+            true,
+            Some(points),
+            time::Duration::ZERO,
+            Default::default(),
+        )
+        .unwrap()
+        .take(once.len() * 2)
+        .collect();
+        let (first, second) = looped.split_at(once.len());
+        assert_eq!(first, &once[..]);
+        assert_eq!(
+            second[..once.len() - 100 * channels],
+            once[100 * channels..]
+        );
+    }
+
+    #[test]
+    fn waits_for_loops_in_silence() {
+        use super::super::looping::LoopPoint;
+
+        let data = SamplesData::new(2, 44100, Arc::from(vec![0.1, 0.2, 0.3, 0.4])).unwrap();
+        let data = SourceData::Samples(data);
+        let points = LoopPoints::new(LoopPoint::Frame(1), None);
+        let mut playback = Playback::new(
+            &data,
+            true,
+            Some(points),
+            time::Duration::ZERO,
+            Default::default(),
+        )
+        .unwrap();
+        // Stand in for a loop worker which hasn't opened the next loop yet.
+        let (worker, _requests) = mpsc::channel();
+        let (reply, replies) = mpsc::channel();
+        playback.next_loop = Some(LoopInputs {
+            worker,
+            reply: reply.clone(),
+            replies,
+        });
+        let first: Vec<f32> = playback.by_ref().take(6).collect();
+        assert_eq!(first, [0.1, 0.2, 0.3, 0.4, 0.0, 0.0]);
+        reply.send(data.open_at(1)).unwrap();
+        let next: Vec<f32> = playback.take(2).collect();
+        assert_eq!(next, [0.3, 0.4]);
+    }
+
+    #[test]
+    fn generator_ends_on_short_buffer() {
+        let mut remaining = GENERATOR_BUFFER_FRAMES + 3;
+        let data = GeneratorData::new(1, 44100, move |buf: &mut [f32]| {
+            let n = remaining.min(buf.len());
+            buf[..n].fill(0.5);
+            remaining -= n;
+            n
+        })
+        .unwrap();
+        let samples: Vec<f32> = play(&SourceData::Generator(data), false).collect();
+        assert_eq!(samples.len(), GENERATOR_BUFFER_FRAMES + 3);
+        assert!(samples.iter().all(|&s| s == 0.5));
+    }
+}
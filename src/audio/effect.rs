@@ -0,0 +1,738 @@
+//! Effects which alter the sound of a source or a whole mixer bus,
+//! such as filters, echo and reverb.
+
+use std::f32::consts::PI;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+use std::time;
+
+/// The longest delay an echo can have, which bounds how much memory it needs.
+const MAX_ECHO_DELAY: time::Duration = time::Duration::from_secs(5);
+
+/// An audio effect, along with its parameters.
+///
+/// Effects are added to a [`Bus`](struct.Bus.html#method.add_effect) or to a
+/// [`SoundSource`](trait.SoundSource.html#tymethod.add_effect), and can be
+/// changed or removed while sounds are playing through the returned
+/// [`EffectHandle`](struct.EffectHandle.html).  For instance, muffling
+/// everything while the game is paused is as simple as:
+///
+/// ```rust,no_run
+/// # use ggez::audio::Effect;
+/// # fn t(ctx: &ggez::Context) {
+/// let muffle = ctx.audio.master().add_effect(Effect::LowPass { cutoff: 800.0 });
+/// // ...and once the game is resumed:
+/// muffle.remove();
+/// # }
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Effect {
+    /// Lets through frequencies below `cutoff` (in Hz), dampening higher ones.
+    LowPass {
+        /// The cutoff frequency, in Hz.
+        cutoff: f32,
+    },
+    /// Lets through frequencies above `cutoff` (in Hz), dampening lower ones.
+    HighPass {
+        /// The cutoff frequency, in Hz.
+        cutoff: f32,
+    },
+    /// Repeats the sound after `delay`, each repeat `feedback` times as loud
+    /// as the one before.
+    Echo {
+        /// The time between repeats, up to five seconds.  Longer delays are
+        /// treated as five seconds.
+        delay: time::Duration,
+        /// How loud each repeat is compared to the previous one, from `0.0` to `1.0`.
+        feedback: f32,
+        /// How loud the repeats are compared to the original sound, from `0.0` to `1.0`.
+        mix: f32,
+    },
+    /// Makes the sound reverberate as if played in a room.
+    Reverb {
+        /// How large the room sounds, from `0.0` to `1.0`.
+        room_size: f32,
+        /// How quickly high frequencies die out, from `0.0` to `1.0`.
+        damping: f32,
+        /// How much of the output is reverberation rather than the
+        /// original sound, from `0.0` to `1.0`.
+        mix: f32,
+    },
+    /// Distorts the sound by amplifying it by `drive` and clipping it.
+    Distortion {
+        /// How much to amplify the sound before clipping; `1.0` or more.
+        drive: f32,
+        /// How much of the output is distorted rather than the original
+        /// sound, from `0.0` to `1.0`.
+        mix: f32,
+    },
+}
+
+/// A handle to an effect added to a source or bus, which can change
+/// or remove it while sounds are playing.
+#[derive(Clone)]
+pub struct EffectHandle {
+    slot: Arc<EffectSlot>,
+    chain: Weak<ChainShared>,
+}
+
+impl EffectHandle {
+    /// Gets the effect and its current parameters.
+    pub fn effect(&self) -> Effect {
+        *lock(&self.slot.effect)
+    }
+
+    /// Replaces the effect, which takes effect right away for every sound
+    /// playing through it.
+    pub fn set_effect(&self, effect: Effect) {
+        if let Some(chain) = self.chain.upgrade() {
+            chain.prepare(&self.slot, &effect);
+        }
+        *lock(&self.slot.effect) = effect;
+        let _ = self.slot.generation.fetch_add(1, Ordering::Release);
+    }
+
+    /// Removes the effect from whatever it was added to.
+    pub fn remove(&self) {
+        if let Some(chain) = self.chain.upgrade() {
+            lock(&chain.effects).retain(|slot| !Arc::ptr_eq(slot, &self.slot));
+            let _ = chain.generation.fetch_add(1, Ordering::Release);
+        }
+    }
+}
+
+impl fmt::Debug for EffectHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<Audio effect {:?}: {self:p}>", self.effect())
+    }
+}
+
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // Nothing in here can be left half-updated by a panic.
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// One effect in a chain.  The generation is bumped whenever the effect
+/// changes, so that every processor using it knows to catch up.
+struct EffectSlot {
+    effect: Mutex<Effect>,
+    generation: AtomicUsize,
+}
+
+struct ChainShared {
+    effects: Mutex<Vec<Arc<EffectSlot>>>,
+    generation: AtomicUsize,
+    // One for every `ChainProcessor` still applying the chain.
+    prepared: Mutex<Vec<Weak<Prepared>>>,
+}
+
+impl ChainShared {
+    /// Builds what every processor applying the chain needs for the given
+    /// effect, whatever buffers it takes included, so that they don't have to
+    /// be allocated on the audio thread once it catches up.
+    fn prepare(&self, slot: &Arc<EffectSlot>, effect: &Effect) {
+        lock(&self.prepared).retain(|prepared| match prepared.upgrade() {
+            Some(prepared) => {
+                let processor = Processor::new(effect, prepared.channels, prepared.sample_rate);
+                let mut processors = lock(&prepared.processors);
+                processors.retain(|(s, _)| !Arc::ptr_eq(s, slot));
+                processors.push((slot.clone(), processor));
+                true
+            }
+            None => false,
+        });
+    }
+}
+
+/// Processors built on the game thread for one `ChainProcessor`, for the
+/// effects added to or changed in its chain since it was created.
+struct Prepared {
+    channels: u16,
+    sample_rate: u32,
+    processors: Mutex<Vec<(Arc<EffectSlot>, Processor)>>,
+}
+
+impl Prepared {
+    fn take(&self, slot: &Arc<EffectSlot>) -> Option<Processor> {
+        let mut processors = lock(&self.processors);
+        let i = processors.iter().position(|(s, _)| Arc::ptr_eq(s, slot))?;
+        Some(processors.swap_remove(i).1)
+    }
+}
+
+/// An ordered list of effects, shared between the owner of a source or bus
+/// and the `ChainProcessor`s applying it on the audio thread.
+#[derive(Clone)]
+pub(crate) struct EffectChain {
+    shared: Arc<ChainShared>,
+}
+
+impl EffectChain {
+    pub fn new() -> Self {
+        EffectChain {
+            shared: Arc::new(ChainShared {
+                effects: Mutex::new(Vec::new()),
+                generation: AtomicUsize::new(0),
+                prepared: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Adds an effect to the end of the chain.
+    pub fn add(&self, effect: Effect) -> EffectHandle {
+        let slot = Arc::new(EffectSlot {
+            effect: Mutex::new(effect),
+            generation: AtomicUsize::new(0),
+        });
+        self.shared.prepare(&slot, &effect);
+        lock(&self.shared.effects).push(slot.clone());
+        let _ = self.shared.generation.fetch_add(1, Ordering::Release);
+        EffectHandle {
+            slot,
+            chain: Arc::downgrade(&self.shared),
+        }
+    }
+
+    /// Removes every effect from the chain.
+    pub fn clear(&self) {
+        lock(&self.shared.effects).clear();
+        let _ = self.shared.generation.fetch_add(1, Ordering::Release);
+    }
+
+    /// Creates something to apply the chain to samples in the given format.
+    pub fn processor(&self, channels: u16, sample_rate: u32) -> ChainProcessor {
+        let channels = channels.max(1);
+        let sample_rate = sample_rate.max(1);
+        let prepared = Arc::new(Prepared {
+            channels,
+            sample_rate,
+            processors: Mutex::new(Vec::new()),
+        });
+        lock(&self.shared.prepared).push(Arc::downgrade(&prepared));
+        let mut processor = ChainProcessor {
+            shared: self.shared.clone(),
+            // Anything but the current generation, so the first sample syncs up.
+            generation: self
+                .shared
+                .generation
+                .load(Ordering::Acquire)
+                .wrapping_sub(1),
+            effects: Vec::new(),
+            prepared,
+            channels,
+            sample_rate,
+        };
+        processor.sync();
+        processor
+    }
+}
+
+impl fmt::Debug for EffectChain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<Audio effect chain: {self:p}>")
+    }
+}
+
+/// Applies an `EffectChain` to a stream of interleaved samples, keeping
+/// up with changes made to it along the way.
+pub(crate) struct ChainProcessor {
+    shared: Arc<ChainShared>,
+    generation: usize,
+    effects: Vec<ActiveEffect>,
+    prepared: Arc<Prepared>,
+    channels: u16,
+    sample_rate: u32,
+}
+
+struct ActiveEffect {
+    slot: Arc<EffectSlot>,
+    generation: usize,
+    processor: Processor,
+}
+
+impl ChainProcessor {
+    fn sync(&mut self) {
+        let generation = self.shared.generation.load(Ordering::Acquire);
+        if generation == self.generation {
+            return;
+        }
+        self.generation = generation;
+
+        // Effects that were already in the chain keep their state, so that
+        // adding or removing one doesn't cut off the echo of another.
+        let mut old = std::mem::take(&mut self.effects);
+        for slot in lock(&self.shared.effects).iter() {
+            let effect = match old.iter().position(|e| Arc::ptr_eq(&e.slot, slot)) {
+                Some(i) => old.swap_remove(i),
+                None => ActiveEffect {
+                    slot: slot.clone(),
+                    generation: slot.generation.load(Ordering::Acquire),
+                    processor: self.prepared.take(slot).unwrap_or_else(|| {
+                        Processor::new(&lock(&slot.effect), self.channels, self.sample_rate)
+                    }),
+                },
+            };
+            self.effects.push(effect);
+        }
+    }
+
+    /// Runs one sample for the given channel through every effect in the chain.
+    pub fn process(&mut self, mut sample: f32, channel: u16) -> f32 {
+        self.sync();
+        let channel = usize::from(channel % self.channels);
+        for effect in &mut self.effects {
+            let generation = effect.slot.generation.load(Ordering::Acquire);
+            if generation != effect.generation {
+                effect.generation = generation;
+                effect.processor.update(
+                    &lock(&effect.slot.effect),
+                    self.channels,
+                    self.sample_rate,
+                    self.prepared.take(&effect.slot),
+                );
+            }
+            sample = effect.processor.process(sample, channel);
+        }
+        sample
+    }
+}
+
+/// Applies an `EffectChain` to a `rodio` source.
+pub(crate) struct Effected<S> {
+    input: S,
+    processor: ChainProcessor,
+    channel: u16,
+}
+
+impl<S> Effected<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    pub fn new(input: S, chain: &EffectChain) -> Self {
+        let processor = chain.processor(input.channels(), input.sample_rate());
+        Effected {
+            input,
+            processor,
+            channel: 0,
+        }
+    }
+}
+
+impl<S> Iterator for Effected<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        let channel = self.channel;
+        self.channel += 1;
+        if self.channel == self.processor.channels {
+            self.channel = 0;
+        }
+        Some(self.processor.process(sample, channel))
+    }
+}
+
+impl<S> rodio::Source for Effected<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<time::Duration> {
+        // Echo and reverb tails get cut off at the end of the input,
+        // so the duration stays the same.
+        self.input.total_duration()
+    }
+}
+
+/// The state of an effect being applied, with one set of state per channel.
+enum Processor {
+    Biquad(Vec<Biquad>),
+    Echo(Vec<Echo>),
+    Reverb(Vec<Reverb>),
+    Distortion { drive: f32, mix: f32 },
+}
+
+impl Processor {
+    fn new(effect: &Effect, channels: u16, sample_rate: u32) -> Self {
+        let count = usize::from(channels);
+        let mut processor = match effect {
+            Effect::LowPass { .. } | Effect::HighPass { .. } => {
+                Processor::Biquad((0..count).map(|_| Biquad::default()).collect())
+            }
+            Effect::Echo { .. } => Processor::Echo((0..count).map(|_| Echo::default()).collect()),
+            Effect::Reverb { .. } => Processor::Reverb(
+                (0..count)
+                    .map(|channel| Reverb::new(channel, sample_rate))
+                    .collect(),
+            ),
+            Effect::Distortion { .. } => Processor::Distortion {
+                drive: 1.0,
+                mix: 0.0,
+            },
+        };
+        processor.update(effect, channels, sample_rate, None);
+        processor
+    }
+
+    /// Catches up with changed parameters, keeping as much state as
+    /// possible.  If the kind of effect changed, starts over.  Anything that
+    /// needs allocating is taken from `prepared` when it's there.
+    fn update(
+        &mut self,
+        effect: &Effect,
+        channels: u16,
+        sample_rate: u32,
+        prepared: Option<Processor>,
+    ) {
+        match (&mut *self, *effect) {
+            (Processor::Biquad(filters), Effect::LowPass { cutoff }) => {
+                let coefficients = BiquadCoefficients::low_pass(cutoff, sample_rate);
+                filters
+                    .iter_mut()
+                    .for_each(|f| f.coefficients = coefficients);
+            }
+            (Processor::Biquad(filters), Effect::HighPass { cutoff }) => {
+                let coefficients = BiquadCoefficients::high_pass(cutoff, sample_rate);
+                filters
+                    .iter_mut()
+                    .for_each(|f| f.coefficients = coefficients);
+            }
+            (
+                Processor::Echo(echoes),
+                Effect::Echo {
+                    delay,
+                    feedback,
+                    mix,
+                },
+            ) => {
+                let len = ((delay.min(MAX_ECHO_DELAY).as_secs_f32() * sample_rate as f32) as usize)
+                    .max(1);
+                let mut lines = match prepared {
+                    Some(Processor::Echo(lines)) => lines,
+                    _ => Vec::new(),
+                }
+                .into_iter()
+                .map(|line| line.buffer);
+                for echo in echoes {
+                    if echo.buffer.len() != len {
+                        let line = lines
+                            .next()
+                            .filter(|line| line.len() == len)
+                            .unwrap_or_else(|| vec![0.0; len]);
+                        echo.set_line(line);
+                    }
+                    echo.feedback = feedback.clamp(0.0, 0.99);
+                    echo.mix = mix.clamp(0.0, 1.0);
+                }
+            }
+            (
+                Processor::Reverb(reverbs),
+                Effect::Reverb {
+                    room_size,
+                    damping,
+                    mix,
+                },
+            ) => {
+                for reverb in reverbs {
+                    reverb.set_params(room_size, damping, mix);
+                }
+            }
+            (Processor::Distortion { drive, mix }, Effect::Distortion { drive: d, mix: m }) => {
+                *drive = d.max(1.0);
+                *mix = m.clamp(0.0, 1.0);
+            }
+            _ => *self = prepared.unwrap_or_else(|| Processor::new(effect, channels, sample_rate)),
+        }
+    }
+
+    fn process(&mut self, sample: f32, channel: usize) -> f32 {
+        match self {
+            Processor::Biquad(filters) => filters[channel].process(sample),
+            Processor::Echo(echoes) => echoes[channel].process(sample),
+            Processor::Reverb(reverbs) => reverbs[channel].process(sample),
+            Processor::Distortion { drive, mix } => {
+                let distorted = (sample * *drive).tanh();
+                sample * (1.0 - *mix) + distorted * *mix
+            }
+        }
+    }
+}
+
+/// Coefficients for a second-order filter, straight out of the
+/// well-known "Audio EQ Cookbook" by Robert Bristow-Johnson.
+#[derive(Copy, Clone, Default)]
+struct BiquadCoefficients {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl BiquadCoefficients {
+    fn new(cutoff: f32, sample_rate: u32, high_pass: bool) -> Self {
+        let nyquist = sample_rate as f32 / 2.0;
+        let w0 = 2.0 * PI * cutoff.clamp(10.0, nyquist * 0.99) / sample_rate as f32;
+        let cos_w0 = w0.cos();
+        let alpha = w0.sin() / (2.0 * std::f32::consts::FRAC_1_SQRT_2);
+        let a0 = 1.0 + alpha;
+        let (b0, b1) = if high_pass {
+            ((1.0 + cos_w0) / 2.0, -(1.0 + cos_w0))
+        } else {
+            ((1.0 - cos_w0) / 2.0, 1.0 - cos_w0)
+        };
+        BiquadCoefficients {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b0 / a0,
+            a1: -2.0 * cos_w0 / a0,
+            a2: (1.0 - alpha) / a0,
+        }
+    }
+
+    fn low_pass(cutoff: f32, sample_rate: u32) -> Self {
+        Self::new(cutoff, sample_rate, false)
+    }
+
+    fn high_pass(cutoff: f32, sample_rate: u32) -> Self {
+        Self::new(cutoff, sample_rate, true)
+    }
+}
+
+#[derive(Default)]
+struct Biquad {
+    coefficients: BiquadCoefficients,
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl Biquad {
+    fn process(&mut self, x: f32) -> f32 {
+        let c = &self.coefficients;
+        let y = c.b0 * x + c.b1 * self.x1 + c.b2 * self.x2 - c.a1 * self.y1 - c.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+/// A delay line feeding back into itself.
+#[derive(Default)]
+struct Echo {
+    buffer: Vec<f32>,
+    pos: usize,
+    feedback: f32,
+    mix: f32,
+}
+
+impl Echo {
+    /// Switches to a delay line of a different length, carrying over as much
+    /// of what's still to be repeated as fits so the echo doesn't drop out.
+    fn set_line(&mut self, mut line: Vec<f32>) {
+        let (newer, older) = self.buffer.split_at(self.pos);
+        let pending = older.iter().chain(newer);
+        let len = line.len();
+        let skip = self.buffer.len().saturating_sub(len);
+        let start = len.saturating_sub(self.buffer.len());
+        for (to, &from) in line[start..].iter_mut().zip(pending.skip(skip)) {
+            *to = from;
+        }
+        self.buffer = line;
+        self.pos = 0;
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let delayed = self.buffer[self.pos];
+        self.buffer[self.pos] = x + delayed * self.feedback;
+        self.pos += 1;
+        if self.pos == self.buffer.len() {
+            self.pos = 0;
+        }
+        x + delayed * self.mix
+    }
+}
+
+/// Comb filter lengths from Freeverb, in samples at 44.1 kHz.
+const COMB_LENGTHS: [usize; 4] = [1116, 1188, 1277, 1356];
+/// All-pass filter lengths from Freeverb, in samples at 44.1 kHz.
+const ALLPASS_LENGTHS: [usize; 2] = [556, 441];
+/// How much longer the delays are on every other channel, so that
+/// stereo reverb sounds wide rather than coming from the middle.
+const STEREO_SPREAD: usize = 23;
+
+/// A pared-down version of the classic Freeverb: parallel comb filters
+/// followed by all-pass filters.
+struct Reverb {
+    combs: Vec<Comb>,
+    allpasses: Vec<(Vec<f32>, usize)>,
+    mix: f32,
+}
+
+struct Comb {
+    buffer: Vec<f32>,
+    pos: usize,
+    filtered: f32,
+    feedback: f32,
+    damping: f32,
+}
+
+impl Reverb {
+    fn new(channel: usize, sample_rate: u32) -> Self {
+        let spread = STEREO_SPREAD * (channel % 2);
+        let scale = |len: usize| ((len + spread) * sample_rate as usize / 44100).max(1);
+        Reverb {
+            combs: COMB_LENGTHS
+                .iter()
+                .map(|&len| Comb {
+                    buffer: vec![0.0; scale(len)],
+                    pos: 0,
+                    filtered: 0.0,
+                    feedback: 0.0,
+                    damping: 0.0,
+                })
+                .collect(),
+            allpasses: ALLPASS_LENGTHS
+                .iter()
+                .map(|&len| (vec![0.0; scale(len)], 0))
+                .collect(),
+            mix: 0.0,
+        }
+    }
+
+    fn set_params(&mut self, room_size: f32, damping: f32, mix: f32) {
+        for comb in &mut self.combs {
+            comb.feedback = 0.7 + 0.28 * room_size.clamp(0.0, 1.0);
+            comb.damping = damping.clamp(0.0, 1.0) * 0.4;
+        }
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let input = x * 0.03;
+        let mut wet = 0.0;
+        for comb in &mut self.combs {
+            let out = comb.buffer[comb.pos];
+            comb.filtered = out * (1.0 - comb.damping) + comb.filtered * comb.damping;
+            comb.buffer[comb.pos] = input + comb.filtered * comb.feedback;
+            comb.pos += 1;
+            if comb.pos == comb.buffer.len() {
+                comb.pos = 0;
+            }
+            wet += out;
+        }
+        for (buffer, pos) in &mut self.allpasses {
+            let delayed = buffer[*pos];
+            buffer[*pos] = wet + delayed * 0.5;
+            wet = delayed - wet;
+            *pos += 1;
+            if *pos == buffer.len() {
+                *pos = 0;
+            }
+        }
+        x * (1.0 - self.mix) + wet * 3.0 * self.mix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn low_pass_blocks_high_frequencies() {
+        let chain = EffectChain::new();
+        let _ = chain.add(Effect::LowPass { cutoff: 200.0 });
+        let mut processor = chain.processor(1, 44100);
+
+        // The highest frequency there is: a sample alternating between 1 and -1.
+        let high: f32 = (0..1000)
+            .map(|i| processor.process([1.0, -1.0][i % 2], 0))
+            .skip(900)
+            .map(f32::abs)
+            .fold(0.0, f32::max);
+        assert!(high < 0.01);
+
+        // A constant signal is let through untouched.
+        let low = (0..1000).map(|_| processor.process(0.5, 0)).last().unwrap();
+        assert!((low - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn live_changes_and_removal() {
+        let chain = EffectChain::new();
+        let echo = chain.add(Effect::Echo {
+            delay: time::Duration::from_secs(1),
+            feedback: 0.0,
+            mix: 0.5,
+        });
+        let mut processor = chain.processor(2, 2);
+
+        // Two frames of delay at two frames a second, which is four samples in stereo.
+        let out: Vec<f32> = [1.0, 0.25, 0.0, 0.0, 0.0, 0.0]
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| processor.process(x, i as u16 % 2))
+            .collect();
+        assert_eq!(out, [1.0, 0.25, 0.0, 0.0, 0.5, 0.125]);
+
+        echo.set_effect(Effect::Distortion {
+            drive: 10.0,
+            mix: 1.0,
+        });
+        assert!((processor.process(0.5, 0) - 5.0f32.tanh()).abs() < 1e-6);
+
+        echo.remove();
+        assert_eq!(processor.process(0.5, 0), 0.5);
+    }
+
+    #[test]
+    fn echo_delay_changes() {
+        let chain = EffectChain::new();
+        let echo = chain.add(Effect::Echo {
+            delay: time::Duration::from_secs(2),
+            feedback: 0.0,
+            mix: 1.0,
+        });
+        let mut processor = chain.processor(1, 1);
+        let mut out = vec![processor.process(1.0, 0), processor.process(0.0, 0)];
+
+        // What's already in the line gets repeated after the new delay.
+        echo.set_effect(Effect::Echo {
+            delay: time::Duration::from_secs(3),
+            feedback: 0.0,
+            mix: 1.0,
+        });
+        out.extend((0..4).map(|_| processor.process(0.0, 0)));
+        assert_eq!(out, [1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+        // Absurdly long delays are cut down to size.
+        echo.set_effect(Effect::Echo {
+            delay: time::Duration::MAX,
+            feedback: 0.0,
+            mix: 1.0,
+        });
+        let _ = processor.process(0.0, 0);
+        match &processor.effects[0].processor {
+            Processor::Echo(echoes) => assert_eq!(echoes[0].buffer.len(), 5),
+            _ => panic!("Echo turned into another effect"),
+        }
+    }
+}
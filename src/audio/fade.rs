@@ -0,0 +1,203 @@
+//! Volume control for sources, which can fade smoothly from one
+//! volume to another.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time;
+
+/// Where the volume is headed, and how quickly.
+#[derive(Copy, Clone, Debug)]
+struct FadeTarget {
+    volume: f32,
+    duration: time::Duration,
+    stop: bool,
+}
+
+/// The volume of a source, shared with the `Faded` stages applying it on
+/// the audio thread.  The generation is bumped on every change, so that
+/// each of them knows to catch up.
+#[derive(Debug)]
+pub(crate) struct Fader {
+    target: Mutex<FadeTarget>,
+    generation: AtomicUsize,
+}
+
+impl Fader {
+    pub fn new(volume: f32) -> Self {
+        Fader {
+            target: Mutex::new(FadeTarget {
+                volume,
+                duration: time::Duration::ZERO,
+                stop: false,
+            }),
+            generation: AtomicUsize::new(0),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, FadeTarget> {
+        self.target.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn set_target(&self, target: FadeTarget) {
+        *self.lock() = target;
+        let _ = self.generation.fetch_add(1, Ordering::Release);
+    }
+
+    /// Gets the volume, or the volume being faded to.
+    pub fn volume(&self) -> f32 {
+        self.lock().volume
+    }
+
+    /// Changes the volume right away.
+    pub fn set_volume(&self, volume: f32) {
+        self.fade_to(volume, time::Duration::ZERO);
+    }
+
+    /// Fades from the current volume to the given one over `duration`.
+    pub fn fade_to(&self, volume: f32, duration: time::Duration) {
+        self.set_target(FadeTarget {
+            volume,
+            duration,
+            stop: false,
+        });
+    }
+
+    /// Fades to silence over `duration`, then ends the sound.
+    pub fn fade_out(&self, duration: time::Duration) {
+        self.set_target(FadeTarget {
+            volume: 0.0,
+            duration,
+            stop: true,
+        });
+    }
+}
+
+/// Applies a `Fader` to a `rodio` source, ramping the volume linearly
+/// frame by frame.
+pub(crate) struct Faded<S> {
+    input: S,
+    fader: Arc<Fader>,
+    generation: usize,
+    gain: f32,
+    target: f32,
+    step: f32,
+    remaining: u64,
+    stop: bool,
+    channel: u16,
+}
+
+impl<S> Faded<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    /// Starts out at the fader's volume, without fading up to it.
+    pub fn new(input: S, fader: Arc<Fader>) -> Self {
+        let generation = fader.generation.load(Ordering::Acquire);
+        let target = *fader.lock();
+        Faded {
+            input,
+            fader,
+            generation,
+            gain: target.volume,
+            target: target.volume,
+            step: 0.0,
+            remaining: 0,
+            stop: target.stop,
+            channel: 0,
+        }
+    }
+
+    fn sync(&mut self) {
+        let generation = self.fader.generation.load(Ordering::Acquire);
+        if generation == self.generation {
+            return;
+        }
+        self.generation = generation;
+        let target = *self.fader.lock();
+        self.target = target.volume;
+        self.stop = target.stop;
+        self.remaining =
+            (target.duration.as_secs_f64() * f64::from(self.input.sample_rate())) as u64;
+        if self.remaining == 0 {
+            self.gain = self.target;
+        } else {
+            self.step = (self.target - self.gain) / self.remaining as f32;
+        }
+    }
+}
+
+impl<S> Iterator for Faded<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        // Only ever change the volume between frames, so that
+        // every channel of a frame gets the same treatment.
+        if self.channel == 0 {
+            self.sync();
+            if self.remaining > 0 {
+                self.remaining -= 1;
+                self.gain = if self.remaining == 0 {
+                    self.target
+                } else {
+                    self.gain + self.step
+                };
+            } else if self.stop {
+                return None;
+            }
+        }
+        let sample = self.input.next()?;
+        self.channel += 1;
+        if self.channel >= self.input.channels() {
+            self.channel = 0;
+        }
+        Some(sample * self.gain)
+    }
+}
+
+impl<S> rodio::Source for Faded<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<time::Duration> {
+        self.input.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    #[test]
+    fn fades_then_stops() {
+        let fader = Arc::new(Fader::new(1.0));
+        let input = SamplesBuffer::new(2, 4, vec![1.0f32; 32]);
+        let mut faded = Faded::new(input, fader.clone());
+        assert_eq!(faded.next(), Some(1.0));
+        assert_eq!(faded.next(), Some(1.0));
+
+        // Half a second at four frames a second is two frames.
+        fader.fade_to(0.5, time::Duration::from_millis(500));
+        let out: Vec<f32> = faded.by_ref().take(6).collect();
+        assert_eq!(out, [0.75, 0.75, 0.5, 0.5, 0.5, 0.5]);
+
+        fader.fade_out(time::Duration::from_millis(500));
+        let out: Vec<f32> = faded.collect();
+        assert_eq!(out, [0.25, 0.25, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,120 @@
+//! Telling the game when sounds finish playing.
+
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time;
+
+/// Identifies a `Source`, `SpatialSource` or `SoundPool` voice, so that
+/// you can tell which one a finished sound belongs to.
+///
+/// Every source and voice gets an id of its own, which stays the same
+/// for as long as it exists.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SoundId(u64);
+
+impl SoundId {
+    /// Returns an id which hasn't been handed out before.
+    pub(crate) fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        SoundId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// The ids of sounds which have finished playing, waiting for the game to
+/// pick them up with `AudioContext::take_finished()`.
+#[derive(Debug, Default)]
+pub(crate) struct FinishedQueue {
+    ids: Mutex<Vec<SoundId>>,
+}
+
+impl FinishedQueue {
+    fn lock(&self) -> MutexGuard<'_, Vec<SoundId>> {
+        self.ids.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn push(&self, id: SoundId) {
+        self.lock().push(id);
+    }
+
+    pub fn take(&self) -> Vec<SoundId> {
+        mem::take(&mut *self.lock())
+    }
+}
+
+/// Reports the given id to a `FinishedQueue` once its input plays to the
+/// end.  Sounds which are stopped, or faded out, before then aren't reported.
+pub(crate) struct Notify<S> {
+    input: S,
+    id: SoundId,
+    queue: Option<Arc<FinishedQueue>>,
+}
+
+impl<S> Notify<S> {
+    pub fn new(input: S, id: SoundId, queue: Arc<FinishedQueue>) -> Self {
+        Notify {
+            input,
+            id,
+            queue: Some(queue),
+        }
+    }
+}
+
+impl<S> Iterator for Notify<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next();
+        // Whatever plays us may stop asking for samples as soon as we say
+        // there are none left, so that counts as the end too.
+        if sample.is_none() || self.input.current_frame_len() == Some(0) {
+            if let Some(queue) = self.queue.take() {
+                queue.push(self.id);
+            }
+        }
+        sample
+    }
+}
+
+impl<S> rodio::Source for Notify<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<time::Duration> {
+        self.input.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    #[test]
+    fn reports_once_at_the_end() {
+        let queue = Arc::new(FinishedQueue::default());
+        let id = SoundId::next();
+        assert_ne!(id, SoundId::next());
+        let mut sound = Notify::new(SamplesBuffer::new(1, 100, vec![0.5f32]), id, queue.clone());
+        assert!(queue.take().is_empty());
+        assert_eq!(sound.next(), Some(0.5));
+        assert_eq!(sound.next(), None);
+        assert_eq!(queue.take(), vec![id]);
+        assert!(queue.take().is_empty());
+    }
+}
@@ -0,0 +1,201 @@
+//! The listener that `SpatialSource`s are heard by, and how sounds
+//! get quieter with distance.
+
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use glam::Vec3;
+
+/// How the volume of a `SpatialSource` falls off with its distance from the listener.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, smart_default::SmartDefault)]
+pub enum DistanceModel {
+    /// The volume doesn't depend on distance at all.
+    None,
+    /// The volume falls off in a straight line, reaching silence at the
+    /// maximum distance when the rolloff is `1.0`.
+    Linear,
+    /// The volume is inversely proportional to the distance, which is
+    /// how sound behaves in the real world.
+    #[default]
+    Inverse,
+    /// The volume falls off exponentially, with the rolloff as the exponent.
+    Exponential,
+}
+
+/// Describes how the volume of a `SpatialSource` depends on its distance
+/// from the listener.
+///
+/// Within `min_distance` the sound plays at full volume, and beyond
+/// `max_distance` it gets no quieter.
+#[derive(Debug, Copy, Clone, PartialEq, smart_default::SmartDefault)]
+pub struct Attenuation {
+    /// How the volume falls off between the minimum and maximum distance.
+    pub model: DistanceModel,
+    /// The distance up to which the sound plays at full volume.
+    #[default = 1.0]
+    pub min_distance: f32,
+    /// The distance beyond which the sound gets no quieter.
+    #[default(f32::MAX)]
+    pub max_distance: f32,
+    /// How quickly the volume falls off; `1.0` is natural, higher values
+    /// fall off faster and lower ones slower.
+    #[default = 1.0]
+    pub rolloff: f32,
+}
+
+impl Attenuation {
+    /// Returns the volume a sound at the given distance plays at.
+    pub fn gain(&self, distance: f32) -> f32 {
+        let min = self.min_distance.max(f32::EPSILON);
+        let max = self.max_distance.max(min);
+        let distance = distance.clamp(min, max);
+        let gain = match self.model {
+            DistanceModel::None => 1.0,
+            DistanceModel::Linear if max > min => {
+                1.0 - self.rolloff * (distance - min) / (max - min)
+            }
+            DistanceModel::Linear => 1.0,
+            DistanceModel::Inverse => min / (min + self.rolloff * (distance - min)),
+            DistanceModel::Exponential => (distance / min).powf(-self.rolloff),
+        };
+        gain.clamp(0.0, 1.0)
+    }
+}
+
+/// Where the listener is and which way they're facing.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ListenerState {
+    pub position: Vec3,
+    pub forward: Vec3,
+    pub up: Vec3,
+    pub velocity: Vec3,
+    pub speed_of_sound: f32,
+}
+
+/// The listener which `SpatialSource`s are heard by, usually following
+/// the player or the camera.
+///
+/// By default it stands at the origin with `+x` to its right, which
+/// suits 2D games that give their sources `(x, y, 0.0)` positions.
+///
+/// This is a cheap handle to shared state, so it can be cloned freely, and
+/// changes are picked up right away by every `SpatialSource` playing.
+#[derive(Clone)]
+pub struct Listener {
+    state: Arc<Mutex<ListenerState>>,
+}
+
+impl Listener {
+    pub(crate) fn new() -> Self {
+        Listener {
+            state: Arc::new(Mutex::new(ListenerState {
+                position: Vec3::ZERO,
+                forward: Vec3::NEG_Z,
+                up: Vec3::Y,
+                velocity: Vec3::ZERO,
+                speed_of_sound: 343.0,
+            })),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ListenerState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns a snapshot of the listener.
+    pub(crate) fn get(&self) -> ListenerState {
+        *self.lock()
+    }
+
+    /// Gets the position of the listener.
+    pub fn position(&self) -> mint::Point3<f32> {
+        self.lock().position.into()
+    }
+
+    /// Sets the position of the listener.
+    pub fn set_position<P>(&self, pos: P)
+    where
+        P: Into<mint::Point3<f32>>,
+    {
+        self.lock().position = Vec3::from(pos.into());
+    }
+
+    /// Gets the direction the listener is facing.
+    pub fn forward(&self) -> mint::Vector3<f32> {
+        self.lock().forward.into()
+    }
+
+    /// Gets the direction the top of the listener's head points in.
+    pub fn up(&self) -> mint::Vector3<f32> {
+        self.lock().up.into()
+    }
+
+    /// Sets the direction the listener is facing, and the direction the
+    /// top of their head points in, which together decide which ear is which.
+    pub fn set_orientation<V>(&self, forward: V, up: V)
+    where
+        V: Into<mint::Vector3<f32>>,
+    {
+        let mut state = self.lock();
+        state.forward = Vec3::from(forward.into());
+        state.up = Vec3::from(up.into());
+    }
+
+    /// Gets the velocity of the listener.
+    pub fn velocity(&self) -> mint::Vector3<f32> {
+        self.lock().velocity.into()
+    }
+
+    /// Sets the velocity of the listener, in units per second, which is
+    /// only used for the doppler effect.
+    pub fn set_velocity<V>(&self, velocity: V)
+    where
+        V: Into<mint::Vector3<f32>>,
+    {
+        self.lock().velocity = Vec3::from(velocity.into());
+    }
+
+    /// Gets the speed of sound used for the doppler effect.
+    pub fn speed_of_sound(&self) -> f32 {
+        self.lock().speed_of_sound
+    }
+
+    /// Sets the speed of sound used for the doppler effect, in units per
+    /// second.  Defaults to `343.0`, which is right for a game measuring
+    /// in meters.
+    pub fn set_speed_of_sound(&self, speed: f32) {
+        self.lock().speed_of_sound = speed;
+    }
+}
+
+impl fmt::Debug for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<Audio listener: {self:p}>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attenuation_models() {
+        let mut attenuation = Attenuation {
+            model: DistanceModel::Linear,
+            min_distance: 10.0,
+            max_distance: 110.0,
+            rolloff: 1.0,
+        };
+        assert_eq!(attenuation.gain(5.0), 1.0);
+        assert_eq!(attenuation.gain(60.0), 0.5);
+        assert_eq!(attenuation.gain(500.0), 0.0);
+
+        attenuation.model = DistanceModel::Inverse;
+        assert_eq!(attenuation.gain(20.0), 0.5);
+        attenuation.model = DistanceModel::Exponential;
+        attenuation.rolloff = 2.0;
+        assert_eq!(attenuation.gain(20.0), 0.25);
+        // Nothing gets any quieter past the maximum distance.
+        assert_eq!(attenuation.gain(1000.0), attenuation.gain(110.0));
+    }
+}
@@ -0,0 +1,88 @@
+//! Loop points, for sounds that loop only part of themselves.
+
+use std::time;
+
+/// A point in a sound, given either as a time or as a frame count, a frame
+/// being one sample for each channel.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoopPoint {
+    /// A time into the sound.
+    Time(time::Duration),
+    /// A number of frames into the sound, at its own sample rate.
+    Frame(u64),
+}
+
+impl LoopPoint {
+    /// Returns the frame this point is at, in a sound with the given sample rate.
+    pub(crate) fn frame(&self, sample_rate: u32) -> u64 {
+        match *self {
+            LoopPoint::Time(time) => (time.as_secs_f64() * f64::from(sample_rate)) as u64,
+            LoopPoint::Frame(frame) => frame,
+        }
+    }
+}
+
+impl From<time::Duration> for LoopPoint {
+    fn from(time: time::Duration) -> Self {
+        LoopPoint::Time(time)
+    }
+}
+
+/// The section of a sound that a repeating source loops, so that it can
+/// play an intro once before looping the rest.
+///
+/// Once playback reaches `end`, or the end of the sound if there is no
+/// `end`, it carries on from `start` rather than from the very beginning.
+/// An `end` at or before `start` is ignored.
+///
+/// ```rust,no_run
+/// # use ggez::audio::{LoopPoints, SoundSource, Source};
+/// # use std::time::Duration;
+/// # fn t(ctx: &ggez::Context) -> ggez::GameResult {
+/// let mut music = Source::new_streaming(ctx, "/theme.ogg")?;
+/// music.set_loop_points(Some(LoopPoints::new(Duration::from_secs(12), None)));
+/// music.set_repeat(true);
+/// music.play(ctx)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LoopPoints {
+    /// Where each loop starts over from.
+    pub start: LoopPoint,
+    /// Where each loop ends, or `None` to loop at the end of the sound.
+    pub end: Option<LoopPoint>,
+}
+
+impl LoopPoints {
+    /// Creates new loop points.
+    pub fn new<P: Into<LoopPoint>>(start: P, end: Option<P>) -> Self {
+        LoopPoints {
+            start: start.into(),
+            end: end.map(Into::into),
+        }
+    }
+
+    /// Reads loop points from `LOOPSTART`, and `LOOPEND` or `LOOPLENGTH`
+    /// tags, as written by many music tools, all counted in frames.
+    /// Returns `None` if there is no `LOOPSTART`, or the tags make no sense.
+    pub(crate) fn from_tags(tags: &[(String, String)]) -> Option<Self> {
+        let tag = |name: &str| {
+            tags.iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .and_then(|(_, value)| value.trim().parse::<u64>().ok())
+        };
+        let start = tag("LOOPSTART")?;
+        let end = match tag("LOOPEND") {
+            Some(end) => Some(end),
+            None => match tag("LOOPLENGTH") {
+                Some(length) => Some(start.checked_add(length)?),
+                None => None,
+            },
+        };
+        Some(LoopPoints {
+            start: LoopPoint::Frame(start),
+            end: end.map(LoopPoint::Frame),
+        })
+    }
+}
@@ -0,0 +1,395 @@
+//! Finding out about encoded sound data without playing it: its format,
+//! and the tags embedded in it.
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::time;
+
+use super::looping::LoopPoints;
+
+/// How many OGG pages to look through for the comment header before
+/// giving up; it normally comes straight after the first page.
+const MAX_HEADER_PAGES: usize = 16;
+
+/// Tags are read into memory whole, so anything claiming to be bigger
+/// than this is taken to be corrupt, or cover art, and skipped.
+const MAX_TAG_BLOCK: u32 = 1 << 20;
+
+/// The file formats sounds can be encoded in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AudioFormat {
+    /// RIFF WAVE, usually holding uncompressed PCM.
+    Wav,
+    /// Vorbis in an OGG container.
+    Ogg,
+    /// Free Lossless Audio Codec.
+    Flac,
+    /// MPEG audio layer III.  Only playable with the `mp3` feature.
+    Mp3,
+}
+
+impl AudioFormat {
+    /// Works out the format from the first few bytes of a file.
+    fn detect(header: &[u8]) -> Option<Self> {
+        match header {
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => {
+                Some(AudioFormat::Wav)
+            }
+            [b'O', b'g', b'g', b'S', ..] => Some(AudioFormat::Ogg),
+            [b'f', b'L', b'a', b'C', ..] => Some(AudioFormat::Flac),
+            [b'I', b'D', b'3', ..] => Some(AudioFormat::Mp3),
+            // An MPEG frame sync, for files without an ID3 tag.
+            [0xff, second, ..] if second & 0xe0 == 0xe0 => Some(AudioFormat::Mp3),
+            _ => None,
+        }
+    }
+}
+
+/// Information about a piece of sound data, as returned by
+/// [`SoundData::metadata()`](struct.SoundData.html#method.metadata).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundMetadata {
+    pub(crate) format: Option<AudioFormat>,
+    pub(crate) channels: u16,
+    pub(crate) sample_rate: u32,
+    pub(crate) duration: Option<time::Duration>,
+    pub(crate) tags: Vec<(String, String)>,
+}
+
+impl SoundMetadata {
+    /// Returns the format the sound is encoded in, or `None` if the
+    /// decoder recognized it but we don't.
+    pub fn format(&self) -> Option<AudioFormat> {
+        self.format
+    }
+
+    /// Returns how many channels the sound has.
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Returns the sample rate of the sound.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Returns how long the sound lasts, if it could be worked out.
+    pub fn duration(&self) -> Option<time::Duration> {
+        self.duration
+    }
+
+    /// Returns every tag embedded in the sound, as pairs of names and values,
+    /// in the order they appear.  Names are upper case, so the OGG comment
+    /// `title=Overture` comes out as `("TITLE", "Overture")`.
+    ///
+    /// Tags are read from the comments of OGG and FLAC files and the `INFO`
+    /// chunk of WAV files, whose fields are given their usual comment names,
+    /// such as `INAM` becoming `TITLE`.  MP3 tags aren't read.
+    pub fn tags(&self) -> &[(String, String)] {
+        &self.tags
+    }
+
+    /// Returns the value of the first tag with the given name, ignoring case.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the `TITLE` tag, if there is one.
+    pub fn title(&self) -> Option<&str> {
+        self.tag("TITLE")
+    }
+
+    /// Returns the `ARTIST` tag, if there is one.
+    pub fn artist(&self) -> Option<&str> {
+        self.tag("ARTIST")
+    }
+
+    /// Returns the loop points given by the `LOOPSTART`, and `LOOPEND` or
+    /// `LOOPLENGTH` tags, if there are any.
+    pub fn loop_points(&self) -> Option<LoopPoints> {
+        LoopPoints::from_tags(&self.tags)
+    }
+}
+
+/// Reads the format of the data, and whatever tags it has.
+pub(crate) fn read_tags<R: Read + Seek>(
+    mut reader: R,
+) -> (Option<AudioFormat>, Vec<(String, String)>) {
+    let mut header = [0u8; 12];
+    if reader.read_exact(&mut header).is_err() || reader.rewind().is_err() {
+        return (None, Vec::new());
+    }
+    let format = AudioFormat::detect(&header);
+    let tags = match format {
+        Some(AudioFormat::Ogg) => {
+            read_ogg_comment_packet(reader).and_then(|packet| parse_comments(packet.get(7..)?))
+        }
+        Some(AudioFormat::Flac) => read_flac_comments(reader),
+        Some(AudioFormat::Wav) => read_wav_info(reader),
+        Some(AudioFormat::Mp3) | None => None,
+    };
+    (format, tags.unwrap_or_default())
+}
+
+fn read_u32_le<R: Read>(reader: &mut R) -> Option<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes).ok()?;
+    Some(u32::from_le_bytes(bytes))
+}
+
+fn read_block<R: Read>(reader: &mut R, len: u32) -> Option<Vec<u8>> {
+    if len > MAX_TAG_BLOCK {
+        return None;
+    }
+    let mut block = vec![0u8; len as usize];
+    reader.read_exact(&mut block).ok()?;
+    Some(block)
+}
+
+/// Pieces the second packet of the first logical stream, which holds the
+/// Vorbis comments, together from the OGG pages it is spread over.
+fn read_ogg_comment_packet<R: Read>(mut reader: R) -> Option<Vec<u8>> {
+    let mut serial = None;
+    let mut packets = 0;
+    let mut packet = Vec::new();
+    for _ in 0..MAX_HEADER_PAGES {
+        let mut header = [0u8; 27];
+        reader.read_exact(&mut header).ok()?;
+        if &header[..4] != b"OggS" {
+            return None;
+        }
+        let page_serial = u32::from_le_bytes([header[14], header[15], header[16], header[17]]);
+        let mut segments = vec![0u8; usize::from(header[26])];
+        reader.read_exact(&mut segments).ok()?;
+        let mut body = vec![0u8; segments.iter().map(|&len| usize::from(len)).sum()];
+        reader.read_exact(&mut body).ok()?;
+        if *serial.get_or_insert(page_serial) != page_serial {
+            continue;
+        }
+
+        let mut offset = 0;
+        for len in segments {
+            let len = usize::from(len);
+            if packets == 1 {
+                packet.extend_from_slice(&body[offset..offset + len]);
+            }
+            offset += len;
+            // A segment shorter than the maximum ends its packet.
+            if len < 255 {
+                if packets == 1 {
+                    return packet.starts_with(b"\x03vorbis").then_some(packet);
+                }
+                packets += 1;
+            }
+        }
+    }
+    None
+}
+
+/// Finds the Vorbis comment block among the metadata blocks of a FLAC file.
+fn read_flac_comments<R: Read + Seek>(mut reader: R) -> Option<Vec<(String, String)>> {
+    let _ = reader.seek(SeekFrom::Start(4)).ok()?;
+    loop {
+        let mut header = [0u8; 4];
+        reader.read_exact(&mut header).ok()?;
+        let last = header[0] & 0x80 != 0;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]);
+        if header[0] & 0x7f == 4 {
+            return parse_comments(&read_block(&mut reader, len)?);
+        }
+        if last {
+            return None;
+        }
+        let _ = reader.seek(SeekFrom::Current(i64::from(len))).ok()?;
+    }
+}
+
+/// Splits Vorbis comments, as found in OGG and FLAC files, into `KEY=value` pairs.
+fn parse_comments(comments: &[u8]) -> Option<Vec<(String, String)>> {
+    let mut reader = io::Cursor::new(comments);
+    // The vendor string, which isn't a tag.
+    let vendor_len = read_u32_le(&mut reader)?;
+    let _ = reader.seek(SeekFrom::Current(i64::from(vendor_len))).ok()?;
+    let count = read_u32_le(&mut reader)?;
+    let mut tags = Vec::new();
+    for _ in 0..count {
+        let len = read_u32_le(&mut reader)?;
+        let comment = read_block(&mut reader, len)?;
+        let comment = String::from_utf8_lossy(&comment);
+        if let Some((key, value)) = comment.split_once('=') {
+            tags.push((key.to_ascii_uppercase(), value.to_owned()));
+        }
+    }
+    Some(tags)
+}
+
+/// Reads the `LIST` `INFO` chunk of a WAV file, if it has one.
+fn read_wav_info<R: Read + Seek>(mut reader: R) -> Option<Vec<(String, String)>> {
+    let _ = reader.seek(SeekFrom::Start(12)).ok()?;
+    loop {
+        let mut id = [0u8; 4];
+        reader.read_exact(&mut id).ok()?;
+        let len = read_u32_le(&mut reader)?;
+        // Chunks are padded to an even length.
+        let padded = i64::from(len) + i64::from(len % 2);
+        if &id != b"LIST" {
+            let _ = reader.seek(SeekFrom::Current(padded)).ok()?;
+            continue;
+        }
+        let list = read_block(&mut reader, len)?;
+        if !list.starts_with(b"INFO") {
+            let _ = reader
+                .seek(SeekFrom::Current(padded - i64::from(len)))
+                .ok()?;
+            continue;
+        }
+
+        let mut tags = Vec::new();
+        let mut rest = &list[4..];
+        while rest.len() >= 8 {
+            let len = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+            let value = rest.get(8..8 + len)?;
+            let key = match &rest[..4] {
+                b"INAM" => "TITLE".to_owned(),
+                b"IART" => "ARTIST".to_owned(),
+                b"IPRD" => "ALBUM".to_owned(),
+                b"ICMT" => "COMMENT".to_owned(),
+                b"ICRD" => "DATE".to_owned(),
+                b"IGNR" => "GENRE".to_owned(),
+                b"ICOP" => "COPYRIGHT".to_owned(),
+                other => String::from_utf8_lossy(other).into_owned(),
+            };
+            let value = String::from_utf8_lossy(value);
+            tags.push((key, value.trim_end_matches('\0').to_owned()));
+            rest = rest.get(8 + len + len % 2..).unwrap_or_default();
+        }
+        return Some(tags);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::looping::LoopPoint;
+    use super::super::SoundData;
+    use super::*;
+
+    /// Wraps packets up into a single OGG page, leaving out the checksum,
+    /// which isn't checked.
+    fn ogg_page(packets: &[&[u8]]) -> Vec<u8> {
+        let mut segments = Vec::new();
+        let mut body = Vec::new();
+        for packet in packets {
+            let mut len = packet.len();
+            while len >= 255 {
+                segments.push(255);
+                len -= 255;
+            }
+            segments.push(len as u8);
+            body.extend_from_slice(packet);
+        }
+        let mut page = b"OggS".to_vec();
+        page.resize(26, 0);
+        page.push(segments.len() as u8);
+        page.extend_from_slice(&segments);
+        page.extend_from_slice(&body);
+        page
+    }
+
+    fn comment_packet(comments: &[&str]) -> Vec<u8> {
+        let mut packet = b"\x03vorbis".to_vec();
+        packet.extend_from_slice(&4u32.to_le_bytes());
+        packet.extend_from_slice(b"ggez");
+        packet.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+        for comment in comments {
+            packet.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            packet.extend_from_slice(comment.as_bytes());
+        }
+        packet
+    }
+
+    #[test]
+    fn ogg_tags() {
+        let long_title = format!("title={}", "a".repeat(300));
+        let comments = comment_packet(&["loopstart=1000", &long_title, "LOOPLENGTH=500"]);
+        let ogg = ogg_page(&[b"\x01vorbis", &comments]);
+        let (format, tags) = read_tags(io::Cursor::new(ogg));
+        assert_eq!(format, Some(AudioFormat::Ogg));
+        assert_eq!(tags[1], ("TITLE".to_owned(), "a".repeat(300)));
+        assert_eq!(
+            LoopPoints::from_tags(&tags),
+            Some(LoopPoints::new(
+                LoopPoint::Frame(1000),
+                Some(LoopPoint::Frame(1500))
+            ))
+        );
+
+        let comments = comment_packet(&["TITLE=No loop"]);
+        let ogg = ogg_page(&[b"\x01vorbis", &comments]);
+        let (_, tags) = read_tags(io::Cursor::new(ogg));
+        assert_eq!(LoopPoints::from_tags(&tags), None);
+
+        let overflowing = [
+            ("LOOPSTART".to_owned(), u64::MAX.to_string()),
+            ("LOOPLENGTH".to_owned(), "2".to_owned()),
+        ];
+        assert_eq!(LoopPoints::from_tags(&overflowing), None);
+    }
+
+    #[test]
+    fn wav_tags() {
+        let mut info = b"INFO".to_vec();
+        for (id, value) in [(b"INAM", &b"Overture\0"[..]), (b"IART", b"ggez")] {
+            info.extend_from_slice(id);
+            info.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            info.extend_from_slice(value);
+            if value.len() % 2 == 1 {
+                info.push(0);
+            }
+        }
+        let mut wav = b"RIFF\0\0\0\0WAVEdata".to_vec();
+        wav.extend_from_slice(&3u32.to_le_bytes());
+        wav.extend_from_slice(&[0, 0, 0, 0]);
+        wav.extend_from_slice(b"LIST");
+        wav.extend_from_slice(&(info.len() as u32).to_le_bytes());
+        wav.extend_from_slice(&info);
+
+        let (format, tags) = read_tags(io::Cursor::new(wav));
+        assert_eq!(format, Some(AudioFormat::Wav));
+        assert_eq!(
+            tags,
+            [
+                ("TITLE".to_owned(), "Overture".to_owned()),
+                ("ARTIST".to_owned(), "ggez".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn resource_files() {
+        let read = |name: &str| {
+            let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("resources")
+                .join(name);
+            SoundData::from(std::fs::read(path).unwrap())
+                .metadata()
+                .unwrap()
+        };
+        let ogg = read("pew.ogg");
+        assert_eq!(ogg.format(), Some(AudioFormat::Ogg));
+        assert_eq!((ogg.channels(), ogg.sample_rate()), (1, 44100));
+        assert_eq!(ogg.tag("comment"), Some("Processed by SoX"));
+        assert_eq!(ogg.title(), None);
+        assert!(ogg.duration().is_some());
+
+        let flac = read("pew.flac");
+        assert_eq!(flac.format(), Some(AudioFormat::Flac));
+        assert!(flac.tags().is_empty());
+        let wav = read("pew.wav");
+        assert_eq!(wav.format(), Some(AudioFormat::Wav));
+        assert_eq!(wav.loop_points(), None);
+
+        assert!(SoundData::from_bytes(b"not a sound").metadata().is_err());
+    }
+}
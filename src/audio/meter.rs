@@ -0,0 +1,222 @@
+//! Measuring the levels and spectrum of what a bus plays, for visualizers
+//! and anything else that reacts to the game's own audio.
+
+use std::f32::consts::PI;
+use std::fmt;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// How many frames the levels and spectrum are worked out over.  Must be
+/// a power of two, for the FFT.
+const WINDOW_FRAMES: usize = 1024;
+
+/// How many frames of audio are kept around for the game to look at.
+/// Bigger than the window, so that the audio thread doesn't overwrite
+/// samples while they are being read unless the game is very slow about it.
+const BUFFER_FRAMES: usize = WINDOW_FRAMES * 4;
+
+/// The most recent samples played by a bus.
+///
+/// Only the audio thread writes and it never waits for anything, which
+/// is why samples are kept as atomics rather than behind a lock.
+pub(crate) struct MeterBuffer {
+    // Interleaved samples, stored as their bit patterns.
+    samples: Box<[AtomicU32]>,
+    // How many samples have been written in all.
+    written: AtomicU64,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl MeterBuffer {
+    pub fn new(channels: u16, sample_rate: u32) -> Self {
+        MeterBuffer {
+            samples: (0..BUFFER_FRAMES * usize::from(channels))
+                .map(|_| AtomicU32::new(0))
+                .collect(),
+            written: AtomicU64::new(0),
+            channels,
+            sample_rate,
+        }
+    }
+
+    /// Records the next sample played.  Called on the audio thread.
+    pub fn push(&self, sample: f32) {
+        let written = self.written.load(Ordering::Relaxed);
+        let index = (written % self.samples.len() as u64) as usize;
+        self.samples[index].store(sample.to_bits(), Ordering::Relaxed);
+        self.written.store(written + 1, Ordering::Release);
+    }
+
+    /// Copies out the most recent window of whole frames, oldest first,
+    /// padded with silence if not that much has been played yet.
+    fn window(&self) -> Vec<f32> {
+        let channels = u64::from(self.channels);
+        let len = (WINDOW_FRAMES * usize::from(self.channels)) as u64;
+        let written = self.written.load(Ordering::Acquire);
+        let end = written - written % channels;
+        let start = end.saturating_sub(len);
+        let mut window = vec![0.0; (len - (end - start)) as usize];
+        window.extend((start..end).map(|i| {
+            let index = (i % self.samples.len() as u64) as usize;
+            f32::from_bits(self.samples[index].load(Ordering::Relaxed))
+        }));
+        window
+    }
+}
+
+/// The peak and RMS level of a [`Meter`](struct.Meter.html)'s bus, as linear
+/// amplitudes where `1.0` is full scale.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Levels {
+    /// The loudest single sample.
+    pub peak: f32,
+    /// The root mean square of the samples, which follows how loud the
+    /// audio sounds more closely than the peak does.
+    pub rms: f32,
+}
+
+/// Measures the levels and spectrum of what a [`Bus`](struct.Bus.html)
+/// has played most recently, after its effects and volume.
+///
+/// Get one with [`Bus::meter()`](struct.Bus.html#method.meter); the master
+/// bus's meter measures everything the game plays, and a single source
+/// can be measured by giving it a bus of its own.  Reading it never holds
+/// up the audio thread, so it's fine to do every frame.  This is a cheap
+/// handle to shared state, so it can be cloned freely.
+///
+/// ```rust,no_run
+/// # fn t(ctx: &ggez::Context) {
+/// let meter = ctx.audio.master().meter();
+/// let bass: f32 = meter.spectrum()[..8].iter().sum();
+/// let loudness = meter.levels().rms;
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Meter {
+    buffer: Arc<MeterBuffer>,
+}
+
+impl Meter {
+    pub(crate) fn new(buffer: Arc<MeterBuffer>) -> Self {
+        Meter { buffer }
+    }
+
+    /// Returns the levels over roughly the last 20 milliseconds played,
+    /// across all channels.
+    pub fn levels(&self) -> Levels {
+        let window = self.buffer.window();
+        let peak = window.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        let squares: f32 = window.iter().map(|s| s * s).sum();
+        Levels {
+            peak,
+            rms: (squares / window.len() as f32).sqrt(),
+        }
+    }
+
+    /// Returns the magnitude spectrum of roughly the last 20 milliseconds
+    /// played, with all channels mixed together.
+    ///
+    /// Each value is the amplitude of the frequencies around
+    /// [`frequency(bin)`](#method.frequency), from 0 Hz up to just under half
+    /// the sample rate, scaled so that a full scale sine wave comes out at
+    /// about `1.0`.
+    pub fn spectrum(&self) -> Vec<f32> {
+        let window = self.buffer.window();
+        let channels = usize::from(self.buffer.channels);
+        let mut re: Vec<f32> = window
+            .chunks_exact(channels)
+            .enumerate()
+            .map(|(i, frame)| {
+                // A Hann window keeps loud frequencies from smearing into the rest.
+                let hann = 0.5 - 0.5 * (2.0 * PI * i as f32 / WINDOW_FRAMES as f32).cos();
+                hann * frame.iter().sum::<f32>() / channels as f32
+            })
+            .collect();
+        let mut im = vec![0.0; WINDOW_FRAMES];
+        fft(&mut re, &mut im);
+        // The Hann window halves the amplitude, and the other half of every
+        // frequency is in the mirrored bins we leave out.
+        let scale = 4.0 / WINDOW_FRAMES as f32;
+        re.iter()
+            .zip(&im)
+            .take(WINDOW_FRAMES / 2)
+            .map(|(re, im)| re.hypot(*im) * scale)
+            .collect()
+    }
+
+    /// Returns the frequency, in Hz, at the center of the given bin of the
+    /// [`spectrum()`](#method.spectrum).
+    pub fn frequency(&self, bin: usize) -> f32 {
+        bin as f32 * self.buffer.sample_rate as f32 / WINDOW_FRAMES as f32
+    }
+}
+
+impl fmt::Debug for Meter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<Audio meter: {self:p}>")
+    }
+}
+
+/// An in-place radix-2 FFT.  The length must be a power of two.
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if j > i {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut size = 2;
+    while size <= n {
+        let angle = -2.0 * PI / size as f32;
+        for start in (0..n).step_by(size) {
+            for k in 0..size / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + size / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        size *= 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_a_sine() {
+        let sample_rate = 44100;
+        let buffer = Arc::new(MeterBuffer::new(2, sample_rate));
+        let meter = Meter::new(buffer.clone());
+        assert_eq!(meter.levels(), Levels::default());
+
+        // Right in the middle of bin 32, and half of full scale.
+        let frequency = meter.frequency(32);
+        for i in 0..WINDOW_FRAMES * 3 {
+            let t = i as f32 / sample_rate as f32;
+            let sample = 0.5 * (2.0 * PI * frequency * t).sin();
+            buffer.push(sample);
+            buffer.push(sample);
+        }
+
+        let levels = meter.levels();
+        assert!((levels.peak - 0.5).abs() < 1e-3);
+        assert!((levels.rms - 0.5 / 2.0f32.sqrt()).abs() < 1e-3);
+        let spectrum = meter.spectrum();
+        assert_eq!(spectrum.len(), WINDOW_FRAMES / 2);
+        assert!((spectrum[32] - 0.5).abs() < 1e-2);
+        assert!(spectrum[..30]
+            .iter()
+            .chain(&spectrum[35..])
+            .all(|&m| m < 1e-2));
+    }
+}
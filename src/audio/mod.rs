@@ -0,0 +1,1639 @@
+//! Provides an interface to output sound to the user's speakers.
+//!
+//! It consists of two main types: [`SoundData`](struct.SoundData.html)
+//! is just an array of raw sound data bytes, and a [`Source`](struct.Source.html) is a
+//! `SoundData` connected to a particular sound channel ready to be played.
+//!
+//! Short sounds played often can be decoded once, up front, into a
+//! [`DecodedSoundData`](struct.DecodedSoundData.html), so that they start
+//! quicker at the cost of more memory.
+//!
+//! Long music tracks don't need to be loaded into a `SoundData` first; sources
+//! created with [`Source::new_streaming()`](struct.Source.html#method.new_streaming)
+//! read and decode their file bit by bit as they play instead.  Sources can
+//! also play raw PCM samples, or samples generated on the fly by a callback,
+//! with [`Source::from_samples()`](struct.Source.html#method.from_samples) and
+//! [`Source::from_generator()`](struct.Source.html#method.from_generator).
+//!
+//! Sources play on a mixer [`Bus`](struct.Bus.html), which allows the
+//! volume of whole groups of sounds, such as all music, to be controlled at once.
+//! Both sources and buses can also have [`Effect`](enum.Effect.html)s, such as
+//! filters and reverb, applied to them.
+//!
+//! Any bus can be [metered](struct.Meter.html), for level meters and
+//! spectrum visualizers driven by whatever the game is playing.
+//!
+//! Sources can fade smoothly between volumes, and a
+//! [`MusicPlayer`](struct.MusicPlayer.html) crossfades from one music track to the next.
+//!
+//! A [`SpatialSource`](struct.SpatialSource.html) is placed in space around the
+//! context's [`Listener`](struct.Listener.html), with distance attenuation and
+//! optional doppler shift.  Plain sources can still be panned left and
+//! right with [`Source::set_pan()`](struct.Source.html#method.set_pan).
+//!
+//! Sound effects which go off again and again, overlapping, are best played
+//! with a [`SoundPool`](struct.SoundPool.html), which caps how many voices
+//! play at once.
+//!
+//! With the `tracker` feature enabled, a
+//! [`TrackerSource`](struct.TrackerSource.html) plays MOD, S3M and XM
+//! tracker music.
+//!
+//! For timing sounds precisely, such as to the beat of the music, the
+//! [`AudioContext::clock()`](struct.AudioContext.html#method.clock) keeps
+//! time by the audio itself, and
+//! [`SoundSource::play_at()`](trait.SoundSource.html#method.play_at)
+//! starts a sound on the exact sample the clock reaches a given time.
+#![cfg(feature = "audio")]
+
+use std::fmt;
+use std::io::Read;
+use std::mem;
+use std::path;
+use std::time;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::conf::AudioBackend;
+use crate::context::Has;
+use crate::error::GameError;
+use crate::error::GameResult;
+use crate::filesystem::File;
+use crate::filesystem::Filesystem;
+use crate::filesystem::InternalClone;
+
+mod bus;
+mod data;
+mod effect;
+mod fade;
+mod finished;
+mod listener;
+mod looping;
+mod metadata;
+mod meter;
+mod music;
+mod output;
+mod pan;
+mod pool;
+mod sink;
+mod spatial;
+mod tracker;
+
+pub use self::bus::Bus;
+use self::data::{
+    EncodedData, GeneratorData, Playback, PlaybackPosition, SamplesData, SourceData, StreamReader,
+};
+pub use self::effect::{Effect, EffectHandle};
+use self::effect::{EffectChain, Effected};
+use self::fade::{Faded, Fader};
+pub use self::finished::SoundId;
+use self::finished::{FinishedQueue, Notify};
+pub use self::listener::{Attenuation, DistanceModel, Listener};
+pub use self::looping::{LoopPoint, LoopPoints};
+pub use self::metadata::{AudioFormat, SoundMetadata};
+pub use self::meter::{Levels, Meter};
+pub use self::music::MusicPlayer;
+pub use self::output::CapturedAudio;
+use self::output::{Capture, NullThread, Tapped};
+pub use self::pan::PanLaw;
+use self::pan::{Panned, Panner};
+pub use self::pool::{SoundPool, VoiceHandle, VoiceStealing};
+use self::sink::Sink;
+use self::spatial::{Emitter, SpatialSink};
+#[cfg(feature = "tracker")]
+pub use self::tracker::{PatternPosition, TrackerSource};
+
+const MASTER_BUS: &str = "master";
+const MUSIC_BUS: &str = "music";
+const EFFECTS_BUS: &str = "effects";
+
+/// A struct that contains all information for tracking sound info.
+///
+/// You generally don't have to create this yourself, it will be part
+/// of your `Context` object.
+pub struct AudioContext {
+    fs: Filesystem,
+    output: Output,
+    capture: Arc<Capture>,
+    // The master bus always comes first, followed by the music and effects buses.
+    buses: Vec<Bus>,
+    listener: Listener,
+    finished: Arc<FinishedQueue>,
+}
+
+/// Whatever plays the output of the master bus, depending on the `AudioBackend`.
+enum Output {
+    Device {
+        _stream: rodio::OutputStream,
+        handle: rodio::OutputStreamHandle,
+    },
+    Null {
+        _thread: NullThread,
+    },
+    Manual(Mutex<Tapped>),
+}
+
+impl AudioContext {
+    /// Create new `AudioContext`, playing on the default output device.
+    pub fn new(fs: &Filesystem) -> GameResult<Self> {
+        AudioContext::with_backend(fs, AudioBackend::Device)
+    }
+
+    /// Create new `AudioContext` using the given backend.
+    pub fn with_backend(fs: &Filesystem, backend: AudioBackend) -> GameResult<Self> {
+        let (channels, sample_rate) = match backend {
+            AudioBackend::Device => default_output_format(),
+            AudioBackend::Null {
+                channels,
+                sample_rate,
+            }
+            | AudioBackend::Manual {
+                channels,
+                sample_rate,
+            } => {
+                if channels == 0 || sample_rate == 0 {
+                    return Err(GameError::AudioError(format!(
+                        "Invalid audio backend format: {channels} channels at {sample_rate} Hz"
+                    )));
+                }
+                (channels, sample_rate)
+            }
+        };
+        let (master, master_output) = Bus::new(MASTER_BUS, channels, sample_rate);
+        let capture = Arc::new(Capture::new(channels, sample_rate));
+        let master_output = Tapped::new(master_output, capture.clone(), master.clock().clone());
+
+        let output = match backend {
+            AudioBackend::Device => {
+                let (stream, handle) = rodio::OutputStream::try_default().map_err(|_e| {
+                    GameError::AudioError(String::from(
+                        "Could not initialize sound system using default output device (for some reason)",
+                    ))
+                })?;
+                handle.play_raw(master_output)?;
+                Output::Device {
+                    _stream: stream,
+                    handle,
+                }
+            }
+            AudioBackend::Null { .. } => Output::Null {
+                _thread: NullThread::spawn(master_output),
+            },
+            AudioBackend::Manual { .. } => Output::Manual(Mutex::new(master_output)),
+        };
+        let music = master.add_child(MUSIC_BUS);
+        let effects = master.add_child(EFFECTS_BUS);
+
+        Ok(Self {
+            fs: InternalClone::clone(fs),
+            output,
+            capture,
+            buses: vec![master, music, effects],
+            listener: Listener::new(),
+            finished: Arc::new(FinishedQueue::default()),
+        })
+    }
+}
+
+/// Returns the channel count and sample rate the default output device
+/// prefers, so our buses can mix at that rate without resampling twice.
+fn default_output_format() -> (u16, u32) {
+    use rodio::cpal::traits::{DeviceTrait, HostTrait};
+    rodio::cpal::default_host()
+        .default_output_device()
+        .and_then(|device| device.default_output_config().ok())
+        .map(|config| (config.channels(), config.sample_rate().0))
+        .unwrap_or((2, 44_100))
+}
+
+impl AudioContext {
+    /// Returns the audio device, or `None` if the context isn't using one.
+    pub fn device(&self) -> Option<&rodio::OutputStreamHandle> {
+        match &self.output {
+            Output::Device { handle, .. } => Some(handle),
+            Output::Null { .. } | Output::Manual(_) => None,
+        }
+    }
+
+    /// Mixes the next `duration` worth of audio, when using the
+    /// [`Manual`](../conf/enum.AudioBackend.html#variant.Manual) backend.
+    /// Sounds only play, and their time only passes, as they are rendered.
+    ///
+    /// Returns an error with any other backend, since those mix by themselves.
+    pub fn render(&self, duration: time::Duration) -> GameResult {
+        let Output::Manual(output) = &self.output else {
+            return Err(GameError::AudioError(String::from(
+                "Audio can only be rendered on demand with the manual backend",
+            )));
+        };
+        let mut output = output.lock().unwrap_or_else(PoisonError::into_inner);
+        let sample_rate = rodio::Source::sample_rate(&*output);
+        let frames = (duration.as_secs_f64() * f64::from(sample_rate)).round() as u64;
+        output.mix_frames(frames);
+        output.flush();
+        Ok(())
+    }
+
+    /// Starts capturing everything played on the master bus, throwing away
+    /// anything captured before.
+    pub fn start_capture(&self) {
+        self.capture.start()
+    }
+
+    /// Returns everything captured since the capture was started, or since
+    /// this was last called, and carries on capturing.
+    ///
+    /// The audio thread hands over what it captured every few milliseconds,
+    /// so the most recent audio may not have made it in yet, except with the
+    /// manual backend.
+    pub fn take_capture(&self) -> CapturedAudio {
+        self.capture.take()
+    }
+
+    /// Stops capturing, returning everything captured that hasn't
+    /// been taken with [`take_capture()`](#method.take_capture).
+    pub fn stop_capture(&self) -> CapturedAudio {
+        self.capture.stop()
+    }
+
+    /// Returns the master bus, which every other bus is mixed into.
+    pub fn master(&self) -> &Bus {
+        &self.buses[0]
+    }
+
+    /// Returns the bus intended for music.
+    pub fn music(&self) -> &Bus {
+        &self.buses[1]
+    }
+
+    /// Returns the bus intended for sound effects.
+    pub fn effects(&self) -> &Bus {
+        &self.buses[2]
+    }
+
+    /// Returns the listener that every `SpatialSource` is heard by.
+    pub fn listener(&self) -> &Listener {
+        &self.listener
+    }
+
+    /// Returns the audio clock: how much audio has been mixed since the
+    /// context was created.
+    ///
+    /// The clock advances in steps as the output asks for more audio, rather
+    /// than smoothly, and runs ahead of what can be heard by the latency of
+    /// the output device.  It doesn't drift from the audio, unlike the game
+    /// loop's timer, so it's what to schedule sounds with
+    /// [`SoundSource::play_at()`](trait.SoundSource.html#method.play_at) by.
+    pub fn clock(&self) -> time::Duration {
+        self.master().clock().time()
+    }
+
+    /// Takes the ids of every sound which has played to the end since the
+    /// last call, oldest first.  Sounds that are stopped, faded out or
+    /// stolen before reaching the end aren't included.
+    ///
+    /// When running [`event::run()`](../event/fn.run.html), this is done
+    /// for you each frame and the ids are passed to
+    /// [`EventHandler::sound_finished_event()`](../event/trait.EventHandler.html#method.sound_finished_event)
+    /// instead.
+    pub fn take_finished(&self) -> Vec<SoundId> {
+        self.finished.take()
+    }
+
+    /// Returns the bus with the given name, if there is one.
+    pub fn bus(&self, name: &str) -> Option<&Bus> {
+        self.buses.iter().find(|bus| bus.name() == name)
+    }
+
+    /// Returns an iterator over all buses, starting with the master bus.
+    pub fn buses(&self) -> impl Iterator<Item = &Bus> {
+        self.buses.iter()
+    }
+
+    /// Creates a new bus with the given name, mixed into the master bus.
+    ///
+    /// Returns an error if a bus with that name already exists.
+    pub fn add_bus(&mut self, name: &str) -> GameResult<Bus> {
+        if self.bus(name).is_some() {
+            return Err(GameError::AudioError(format!(
+                "An audio bus named '{name}' already exists"
+            )));
+        }
+        let bus = self.master().add_child(name);
+        self.buses.push(bus.clone());
+        Ok(bus)
+    }
+}
+
+impl fmt::Debug for AudioContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<AudioContext: {self:p}>")
+    }
+}
+
+/// Static sound data stored in memory.
+/// It is `Arc`'ed, so cheap to clone.
+#[derive(Clone, Debug)]
+pub struct SoundData(Arc<[u8]>);
+
+impl SoundData {
+    /// Load the file at the given path and create a new `SoundData` from it.
+    pub fn new<P: AsRef<path::Path>>(fs: &impl Has<Filesystem>, path: P) -> GameResult<Self> {
+        let fs = fs.retrieve();
+        let path = path.as_ref();
+        let file = &mut fs.open(path)?;
+        SoundData::from_read(file)
+    }
+
+    /// Copies the data in the given slice into a new `SoundData` object.
+    pub fn from_bytes(data: &[u8]) -> Self {
+        SoundData(Arc::from(data))
+    }
+
+    /// Creates a `SoundData` from any `Read` object; this involves
+    /// copying it into a buffer.
+    pub fn from_read<R>(reader: &mut R) -> GameResult<Self>
+    where
+        R: Read,
+    {
+        let mut buffer = Vec::new();
+        let _ = reader.read_to_end(&mut buffer)?;
+
+        Ok(SoundData::from(buffer))
+    }
+
+    /// Indicates if the data can be played as a sound.
+    pub fn can_play(&self) -> bool {
+        SourceData::Encoded(EncodedData::Memory(self.clone())).can_play()
+    }
+
+    /// Returns how long the sound lasts, or `None` if it can't be decoded.
+    ///
+    /// Some formats, such as WAV, say how long they are up front.  Others
+    /// have to be decoded in their entirety to find out, which can take
+    /// a moment for long sounds.
+    pub fn duration(&self) -> Option<time::Duration> {
+        EncodedData::Memory(self.clone()).duration()
+    }
+
+    /// Reads the loop points from the `LOOPSTART`, and `LOOPEND` or
+    /// `LOOPLENGTH` tags of an OGG or FLAC file, if it has them.
+    ///
+    /// Sources created from tagged data use these loop points by default.
+    pub fn loop_points(&self) -> Option<LoopPoints> {
+        EncodedData::Memory(self.clone()).loop_points()
+    }
+
+    /// Reads the format, channel count, sample rate, duration and tags of
+    /// the data, without playing it.
+    ///
+    /// Finding out the duration may mean decoding the whole sound; see
+    /// [`duration()`](#method.duration).
+    ///
+    /// ```rust,no_run
+    /// # use ggez::audio::SoundData;
+    /// # fn t(ctx: &ggez::Context) -> ggez::GameResult {
+    /// let data = SoundData::new(ctx, "/music.ogg")?;
+    /// let metadata = data.metadata()?;
+    /// println!(
+    ///     "{} by {}",
+    ///     metadata.title().unwrap_or("Untitled"),
+    ///     metadata.artist().unwrap_or("unknown")
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn metadata(&self) -> GameResult<SoundMetadata> {
+        EncodedData::Memory(self.clone()).metadata()
+    }
+
+    /// Decodes the whole sound up front, so that sources playing it don't
+    /// have to decode it again every time they play.
+    ///
+    /// Decoded sounds start playing quicker and cost less to play, which
+    /// suits short sound effects played often, but take up a lot more memory
+    /// than encoded ones; compare their [`memory_usage()`](#method.memory_usage)s.
+    pub fn decode(&self) -> GameResult<DecodedSoundData> {
+        SamplesData::decode(&EncodedData::Memory(self.clone())).map(DecodedSoundData)
+    }
+
+    /// Returns how many bytes of memory the data takes up.
+    pub fn memory_usage(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl From<Arc<[u8]>> for SoundData {
+    #[inline]
+    fn from(arc: Arc<[u8]>) -> Self {
+        SoundData(arc)
+    }
+}
+
+impl From<Vec<u8>> for SoundData {
+    fn from(v: Vec<u8>) -> Self {
+        SoundData(Arc::from(v))
+    }
+}
+
+impl From<Box<[u8]>> for SoundData {
+    fn from(b: Box<[u8]>) -> Self {
+        SoundData(Arc::from(b))
+    }
+}
+
+impl AsRef<[u8]> for SoundData {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+}
+
+/// Sound data decoded into samples ahead of time, as made by
+/// [`SoundData::decode()`](struct.SoundData.html#method.decode).
+/// It is `Arc`'ed, so cheap to clone.
+///
+/// ```rust,no_run
+/// # use ggez::audio::{DecodedSoundData, SoundPool};
+/// # fn t(ctx: &ggez::Context) -> ggez::GameResult {
+/// let data = DecodedSoundData::new(ctx, "/pew.ogg")?;
+/// let mut shots = SoundPool::from_decoded(ctx, data, 8);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct DecodedSoundData(SamplesData);
+
+impl DecodedSoundData {
+    /// Loads and decodes the file at the given path.
+    pub fn new<P: AsRef<path::Path>>(fs: &impl Has<Filesystem>, path: P) -> GameResult<Self> {
+        SoundData::new(fs, path)?.decode()
+    }
+
+    /// Returns the number of channels of the sound.
+    pub fn channels(&self) -> u16 {
+        self.0.channels()
+    }
+
+    /// Returns the sample rate of the sound.
+    pub fn sample_rate(&self) -> u32 {
+        self.0.sample_rate()
+    }
+
+    /// Returns the decoded samples, interleaved if there is more than one channel.
+    pub fn samples(&self) -> &[f32] {
+        self.0.samples()
+    }
+
+    /// Returns how long the sound lasts.
+    pub fn duration(&self) -> time::Duration {
+        self.0.duration()
+    }
+
+    /// Returns the loop points the sound was tagged with before decoding,
+    /// if any; see [`SoundData::loop_points()`](struct.SoundData.html#method.loop_points).
+    pub fn loop_points(&self) -> Option<LoopPoints> {
+        self.0.loop_points()
+    }
+
+    /// Returns how many bytes of memory the samples take up.
+    pub fn memory_usage(&self) -> usize {
+        mem::size_of_val(self.samples())
+    }
+}
+
+/// A trait defining the operations possible on a sound;
+/// it is implemented by both `Source` and `SpatialSource`.
+pub trait SoundSource {
+    /// Plays the audio source; restarts the sound if currently playing
+    fn play(&mut self, audio: &impl Has<AudioContext>) -> GameResult {
+        let audio = audio.retrieve();
+        self.stop(audio)?;
+        self.play_later()
+    }
+
+    /// Plays the `SoundSource`; waits until done if the sound is currently playing
+    fn play_later(&self) -> GameResult;
+
+    /// Plays the audio source, restarting the sound if currently playing,
+    /// starting on exactly the sample at which the
+    /// [audio clock](struct.AudioContext.html#method.clock) reaches `time`.
+    ///
+    /// Until then the source counts as playing, though silently.  If `time`
+    /// has already passed, it starts right away.
+    ///
+    /// ```rust,no_run
+    /// # use ggez::audio::{SoundSource, Source};
+    /// # use std::time::Duration;
+    /// # fn t(ctx: &ggez::Context) -> ggez::GameResult {
+    /// // Start the drums on the next beat of a 120 BPM track.
+    /// let beat = Duration::from_millis(500);
+    /// let now = ctx.audio.clock();
+    /// let next_beat = beat * (now.as_millis() / beat.as_millis() + 1) as u32;
+    /// let mut drums = Source::new(ctx, "/drums.ogg")?;
+    /// drums.play_at(ctx, next_beat)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn play_at(&mut self, audio: &impl Has<AudioContext>, time: time::Duration) -> GameResult {
+        let audio = audio.retrieve();
+        self.stop(audio)?;
+        self.play_later_at(time)
+    }
+
+    /// Plays the `SoundSource` once the audio clock reaches `time`, or once
+    /// it is done with what it is currently playing, whichever is later.
+    ///
+    /// By default this returns an error, for sources which can't be scheduled.
+    fn play_later_at(&self, _time: time::Duration) -> GameResult {
+        Err(GameError::AudioError(
+            "This sound source can't be scheduled".to_owned(),
+        ))
+    }
+
+    /// Play source "in the background"; cannot be stopped
+    fn play_detached(&mut self, audio: &impl Has<AudioContext>) -> GameResult;
+
+    /// Sets the source to repeat playback infinitely on next [`play()`](#method.play)
+    fn set_repeat(&mut self, repeat: bool);
+
+    /// Sets the fade-in time of the source
+    fn set_fade_in(&mut self, dur: time::Duration);
+
+    /// Sets the time from which playback begins, skipping audio up to that point.
+    ///
+    /// Calls to [`elapsed()`](#tymethod.elapsed) will measure from this point, ignoring skipped time.
+    ///
+    /// Effects such as [`set_fade_in()`](#tymethod.set_fade_in) or [`set_pitch()`](#tymethod.set_pitch)
+    /// will apply from this new start.
+    ///
+    /// If [`set_repeat()`](#tymethod.set_repeat) is set to true, then after looping, the audio will return
+    /// to the original beginning of the source, rather than the time specified here.
+    fn set_start(&mut self, dur: time::Duration);
+
+    /// Sets the speed ratio (by adjusting the playback speed)
+    fn set_pitch(&mut self, ratio: f32);
+
+    /// Gets whether or not the source is set to repeat.
+    fn repeat(&self) -> bool;
+
+    /// Sets the section of the sound that repeats, so that everything before
+    /// the loop start plays only once.  `None` repeats the whole sound.
+    ///
+    /// Sources playing OGG files tagged with loop points, such as with a
+    /// `LOOPSTART` comment, use those by default.  This only affects
+    /// repeating sources, and takes effect on the next [`play()`](#method.play).
+    ///
+    /// By default this is ignored, for sources which always repeat the whole sound.
+    fn set_loop_points(&mut self, _points: Option<LoopPoints>) {}
+
+    /// Gets the section of the sound that repeats, if it isn't the whole sound.
+    fn loop_points(&self) -> Option<LoopPoints> {
+        None
+    }
+
+    /// Pauses playback
+    fn pause(&self);
+
+    /// Resumes playback
+    fn resume(&self);
+
+    /// Stops playback
+    fn stop(&mut self, audio: &impl Has<AudioContext>) -> GameResult;
+
+    /// Returns whether or not the source is stopped
+    /// -- that is, has no more data to play.
+    fn stopped(&self) -> bool;
+
+    /// Gets the current volume.
+    fn volume(&self) -> f32;
+
+    /// Sets the current volume.
+    fn set_volume(&mut self, value: f32);
+
+    /// Fades smoothly from the current volume to the given one over `duration`.
+    ///
+    /// While fading, [`volume()`](#tymethod.volume) returns the volume being faded to.
+    ///
+    /// By default this sets the volume right away, for sources which can't fade.
+    fn fade_to(&mut self, volume: f32, _duration: time::Duration) {
+        self.set_volume(volume);
+    }
+
+    /// Fades the source out over `duration`, then stops it, avoiding the click
+    /// an abrupt [`stop()`](#tymethod.stop) can cause.
+    ///
+    /// The source counts as stopped straight away, and can be played again
+    /// while the old sound is still fading out; its volume is left as it was.
+    /// A paused source has nothing to fade, so it is simply stopped.
+    fn fade_out(&mut self, duration: time::Duration);
+
+    /// Get whether or not the source is paused.
+    fn paused(&self) -> bool;
+
+    /// Get whether or not the source is playing (ie, not paused
+    /// and not stopped).
+    fn playing(&self) -> bool;
+
+    /// Get the time the source has been playing since the last call to [`play()`](#method.play).
+    ///
+    /// Time measurement is based on audio samples consumed, so it may drift from the system
+    /// clock over longer periods of time.  For where exactly in the sound playback is,
+    /// see [`position()`](#method.position) instead.
+    fn elapsed(&self) -> time::Duration;
+
+    /// Set the update interval of the internal sample counter.
+    ///
+    /// This parameter determines the precision of the time measured by [`elapsed()`](#method.elapsed).
+    fn set_query_interval(&mut self, t: time::Duration);
+
+    /// Jumps to the given point in the sound while it is playing or paused,
+    /// discarding anything queued up after it with [`play_later()`](#tymethod.play_later).
+    ///
+    /// Does nothing if the source is stopped; use [`set_start()`](#tymethod.set_start)
+    /// to choose where the next [`play()`](#method.play) begins instead.
+    ///
+    /// Encoded sounds are decoded up to the new position, which happens on
+    /// the calling thread and may take a moment when seeking far into a long track.
+    ///
+    /// By default this returns an error, for sources which can't seek.
+    fn seek(&mut self, _pos: time::Duration) -> GameResult {
+        Err(GameError::AudioError(
+            "This sound source can't seek".to_owned(),
+        ))
+    }
+
+    /// Gets the point in the sound playback is at, counted in samples
+    /// of the sound itself, so it isn't affected by [`set_pitch()`](#tymethod.set_pitch).
+    /// Starts over at zero each time a repeating source loops.
+    ///
+    /// By default this is the same as [`elapsed()`](#tymethod.elapsed).
+    fn position(&self) -> time::Duration {
+        self.elapsed()
+    }
+
+    /// Returns the id identifying this source when it finishes playing; see
+    /// [`AudioContext::take_finished()`](struct.AudioContext.html#method.take_finished).
+    fn id(&self) -> SoundId;
+
+    /// Gets the mixer bus the source plays on.
+    fn bus(&self) -> &Bus;
+
+    /// Routes the source to the given mixer bus, so that it is affected by
+    /// that bus's volume, mute and pause state.
+    ///
+    /// This stops the source if it is currently playing.
+    fn set_bus(&mut self, bus: &Bus);
+
+    /// Adds an effect to the source, applied after any effects added before.
+    /// It affects the source right away, including if it is already playing.
+    fn add_effect(&self, effect: Effect) -> EffectHandle;
+
+    /// Removes every effect from the source.
+    fn clear_effects(&self);
+}
+
+/// Internal state used by audio sources.
+#[derive(Debug)]
+pub(crate) struct SourceState {
+    data: SourceData,
+    repeat: bool,
+    fade_in: time::Duration,
+    skip_duration: time::Duration,
+    speed: f32,
+    query_interval: time::Duration,
+    play_time: Arc<AtomicUsize>,
+    position: Arc<PlaybackPosition>,
+    fader: Arc<Fader>,
+    bus: Bus,
+    effects: EffectChain,
+    loop_points: Option<LoopPoints>,
+    id: SoundId,
+    finished: Arc<FinishedQueue>,
+}
+
+impl SourceState {
+    /// Create a new `SourceState` based around the given data,
+    /// playing on the given bus.
+    pub fn new(data: SourceData, bus: &Bus, finished: &Arc<FinishedQueue>) -> Self {
+        SourceState {
+            loop_points: data.loop_points(),
+            data,
+            repeat: false,
+            fade_in: time::Duration::from_millis(0),
+            skip_duration: time::Duration::from_millis(0),
+            speed: 1.0,
+            query_interval: time::Duration::from_millis(100),
+            play_time: Arc::new(AtomicUsize::new(0)),
+            position: Arc::new(PlaybackPosition::default()),
+            fader: Arc::new(Fader::new(1.0)),
+            bus: bus.clone(),
+            effects: EffectChain::new(),
+            id: SoundId::next(),
+            finished: finished.clone(),
+        }
+    }
+    /// Sets the source to repeat playback infinitely on next [`play()`](#method.play)
+    pub fn set_repeat(&mut self, repeat: bool) {
+        self.repeat = repeat;
+    }
+
+    /// Sets the fade-in time of the source.
+    pub fn set_fade_in(&mut self, dur: time::Duration) {
+        self.fade_in = dur;
+    }
+
+    pub fn set_start(&mut self, dur: time::Duration) {
+        self.skip_duration = dur;
+    }
+
+    /// Sets the pitch ratio (by adjusting the playback speed).
+    pub fn set_pitch(&mut self, ratio: f32) {
+        self.speed = ratio;
+    }
+
+    /// Gets whether or not the source is set to repeat.
+    pub fn repeat(&self) -> bool {
+        self.repeat
+    }
+
+    /// Sets the section of the sound that repeats.
+    pub fn set_loop_points(&mut self, points: Option<LoopPoints>) {
+        self.loop_points = points;
+    }
+
+    /// Gets the section of the sound that repeats.
+    pub fn loop_points(&self) -> Option<LoopPoints> {
+        self.loop_points
+    }
+
+    /// Get the time the source has been playing since the last call to [`play()`](#method.play).
+    ///
+    /// Time measurement is based on audio samples consumed, so it may drift from the system
+    /// clock over longer periods of time.
+    pub fn elapsed(&self) -> time::Duration {
+        let t = self.play_time.load(Ordering::SeqCst);
+        time::Duration::from_micros(t as u64)
+    }
+
+    /// Set the update interval of the internal sample counter.
+    ///
+    /// This parameter determines the precision of the time measured by [`elapsed()`](#method.elapsed).
+    pub fn set_query_interval(&mut self, t: time::Duration) {
+        self.query_interval = t;
+    }
+
+    /// Builds the playback of the data, and everything applied on top of it,
+    /// starting `start` into the data.
+    fn sound(
+        &self,
+        start: time::Duration,
+        fade_in: time::Duration,
+    ) -> GameResult<impl rodio::Source<Item = f32> + Send> {
+        use rodio::Source;
+
+        let counter = self.play_time.clone();
+        let period_mus = self.query_interval.as_secs() as usize * 1_000_000
+            + self.query_interval.subsec_micros() as usize;
+
+        let sound = Playback::new(
+            &self.data,
+            self.repeat,
+            self.loop_points,
+            start,
+            self.position.clone(),
+        )?;
+        let sound = Notify::new(sound, self.id, self.finished.clone())
+            .speed(self.speed)
+            .fade_in(fade_in);
+        let sound = Faded::new(Effected::new(sound, &self.effects), self.fader.clone());
+        Ok(sound.periodic_access(self.query_interval, move |_| {
+            let _ = counter.fetch_add(period_mus, Ordering::SeqCst);
+        }))
+    }
+
+    /// Leaves the sounds played so far with a fader of their own, so that
+    /// the source's volume can change without affecting them, and returns it.
+    fn detach_fader(&mut self) -> Arc<Fader> {
+        let fader = Arc::new(Fader::new(self.fader.volume()));
+        mem::replace(&mut self.fader, fader)
+    }
+}
+
+/// A source of audio data that is connected to an output
+/// channel and ready to play.  It will stop playing when
+/// dropped.
+// TODO LATER: Check and see if this matches Love2d's semantics!
+// There's really a lot of work that needs to be done here, since
+// rodio has gotten better (if still somewhat arcane) and our filesystem
+// code has done the data-slurping-from-zip's for us
+// but for now it works.
+pub struct Source {
+    sink: Sink,
+    state: SourceState,
+    panner: Arc<Panner>,
+}
+
+impl Source {
+    /// Create a new `Source` from the given file.
+    pub fn new<P: AsRef<path::Path>>(ctxs: &impl Has<AudioContext>, path: P) -> GameResult<Self> {
+        let audio = ctxs.retrieve();
+        let path = path.as_ref();
+        let data = SoundData::new(&audio.fs, path)?;
+        Source::from_data(audio, data)
+    }
+
+    /// Creates a new `Source` using the given `SoundData` object.
+    pub fn from_data(audio: &impl Has<AudioContext>, data: SoundData) -> GameResult<Self> {
+        let audio = audio.retrieve();
+        if !data.can_play() {
+            return Err(GameError::AudioError(
+                "Could not decode the given audio data".to_string(),
+            ));
+        }
+        Ok(Source::from_source_data(
+            audio,
+            SourceData::Encoded(EncodedData::Memory(data)),
+        ))
+    }
+
+    /// Creates a new `Source` playing the given `DecodedSoundData`, which
+    /// doesn't need decoding each time it plays.
+    pub fn from_decoded(audio: &impl Has<AudioContext>, data: DecodedSoundData) -> Self {
+        Source::from_source_data(audio.retrieve(), SourceData::Samples(data.0))
+    }
+
+    /// Create a new `Source` which streams the given file as it plays,
+    /// rather than loading all of it up front.
+    pub fn new_streaming<P: AsRef<path::Path>>(
+        ctxs: &impl Has<AudioContext>,
+        path: P,
+    ) -> GameResult<Self> {
+        let audio = ctxs.retrieve();
+        let file = audio.fs.open(path)?;
+        Source::from_file(audio, file)
+    }
+
+    /// Creates a new `Source` which decodes the given `File` bit by bit as it plays.
+    ///
+    /// This is the way to go for long music tracks, which would otherwise sit in
+    /// memory in their entirety.  Every playback reads the file independently,
+    /// so repeating and detached playback work as usual.
+    pub fn from_file(audio: &impl Has<AudioContext>, file: File) -> GameResult<Self> {
+        let audio = audio.retrieve();
+        let data = SourceData::Encoded(EncodedData::Stream(StreamReader::new(file)));
+        if !data.can_play() {
+            return Err(GameError::AudioError(
+                "Could not decode the given audio data".to_string(),
+            ));
+        }
+        Ok(Source::from_source_data(audio, data))
+    }
+
+    /// Creates a new `Source` playing raw PCM samples, interleaved if there
+    /// is more than one channel, with values ranging from `-1.0` to `1.0`.
+    pub fn from_samples(
+        audio: &impl Has<AudioContext>,
+        channels: u16,
+        sample_rate: u32,
+        samples: impl Into<Arc<[f32]>>,
+    ) -> GameResult<Self> {
+        let data = SamplesData::new(channels, sample_rate, samples.into())?;
+        Ok(Source::from_source_data(
+            audio.retrieve(),
+            SourceData::Samples(data),
+        ))
+    }
+
+    /// Creates a new `Source` whose samples are produced on the fly by the
+    /// given generator, for synthesized or procedural sound.
+    ///
+    /// The generator is called on the audio thread with a buffer to fill with
+    /// interleaved samples ranging from `-1.0` to `1.0`, and returns how many
+    /// it filled.  Filling fewer than the whole buffer ends the sound, unless
+    /// the source is set to repeat, in which case the generator is simply asked
+    /// for more.  It should be quick about it, or playback will stutter.
+    ///
+    /// The generator is shared, not restarted, between playbacks of the
+    /// source, so playing it again continues from wherever it left off.
+    pub fn from_generator<F>(
+        audio: &impl Has<AudioContext>,
+        channels: u16,
+        sample_rate: u32,
+        generator: F,
+    ) -> GameResult<Self>
+    where
+        F: FnMut(&mut [f32]) -> usize + Send + 'static,
+    {
+        let data = GeneratorData::new(channels, sample_rate, generator)?;
+        Ok(Source::from_source_data(
+            audio.retrieve(),
+            SourceData::Generator(data),
+        ))
+    }
+
+    fn from_source_data(audio: &AudioContext, data: SourceData) -> Self {
+        let bus = audio.master();
+        Source {
+            sink: bus.new_sink(),
+            state: SourceState::new(data, bus, &audio.finished),
+            panner: Arc::new(Panner::new(0.0, PanLaw::default())),
+        }
+    }
+
+    /// Sets the pan of the source, from `-1.0` for fully left through `0.0`
+    /// for centered to `1.0` for fully right.  Mono sounds are spread across
+    /// both speakers; sounds with more channels have their left and right
+    /// channels turned up or down.  Panning does nothing on mono output.
+    ///
+    /// This takes effect smoothly, even on sounds already playing.
+    pub fn set_pan(&mut self, pan: f32) {
+        self.panner.set_pan(pan)
+    }
+
+    /// Gets the current pan of the source.
+    pub fn pan(&self) -> f32 {
+        self.panner.pan()
+    }
+
+    /// Sets how the pan of the source turns into the volume of each speaker.
+    /// Defaults to [`PanLaw::ConstantPower`](enum.PanLaw.html#variant.ConstantPower).
+    pub fn set_pan_law(&mut self, law: PanLaw) {
+        self.panner.set_law(law)
+    }
+
+    /// Gets the pan law of the source.
+    pub fn pan_law(&self) -> PanLaw {
+        self.panner.law()
+    }
+
+    /// Builds the sound to play, panned as set.
+    fn sound(
+        &self,
+        start: time::Duration,
+        fade_in: time::Duration,
+    ) -> GameResult<impl rodio::Source<Item = f32> + Send> {
+        let sound = self.state.sound(start, fade_in)?;
+        let stereo = self.state.bus.channels() >= 2;
+        Ok(Panned::new(sound, self.panner.clone(), stereo))
+    }
+}
+
+impl SoundSource for Source {
+    fn play_later(&self) -> GameResult {
+        // Creating a new Decoder each time seems a little messy,
+        // since it may do checking and data-type detection that is
+        // redundant, but it's not super expensive.  Data that was decoded
+        // up front with `SoundData::decode()` skips all that.
+        // See https://github.com/ggez/ggez/issues/98 for discussion
+        self.sink
+            .append(self.sound(self.state.skip_duration, self.state.fade_in)?);
+        Ok(())
+    }
+
+    fn play_later_at(&self, time: time::Duration) -> GameResult {
+        self.sink.append_at(
+            self.sound(self.state.skip_duration, self.state.fade_in)?,
+            time,
+        );
+        Ok(())
+    }
+
+    fn play_detached(&mut self, audio: &impl Has<AudioContext>) -> GameResult {
+        let audio = audio.retrieve();
+        self.stop(audio)?;
+        self.play_later()?;
+        let _ = self.detach_sink();
+        Ok(())
+    }
+
+    fn set_repeat(&mut self, repeat: bool) {
+        self.state.set_repeat(repeat)
+    }
+    fn set_fade_in(&mut self, dur: time::Duration) {
+        self.state.set_fade_in(dur)
+    }
+    fn set_start(&mut self, dur: time::Duration) {
+        self.state.set_start(dur)
+    }
+    fn set_pitch(&mut self, ratio: f32) {
+        self.state.set_pitch(ratio)
+    }
+    fn repeat(&self) -> bool {
+        self.state.repeat()
+    }
+    fn set_loop_points(&mut self, points: Option<LoopPoints>) {
+        self.state.set_loop_points(points)
+    }
+    fn loop_points(&self) -> Option<LoopPoints> {
+        self.state.loop_points()
+    }
+    fn pause(&self) {
+        self.sink.pause()
+    }
+    fn resume(&self) {
+        self.sink.play()
+    }
+
+    fn stop(&mut self, _audio: &impl Has<AudioContext>) -> GameResult {
+        // Sinks cannot be reused once stopped, so to stop the current
+        // sound we have to drop the old sink and create a new one in its
+        // place, on the same bus.
+        self.reset_sink();
+        Ok(())
+    }
+
+    fn stopped(&self) -> bool {
+        self.sink.empty()
+    }
+
+    fn volume(&self) -> f32 {
+        self.state.fader.volume()
+    }
+
+    fn set_volume(&mut self, value: f32) {
+        self.state.fader.set_volume(value)
+    }
+
+    fn fade_to(&mut self, volume: f32, duration: time::Duration) {
+        self.state.fader.fade_to(volume, duration)
+    }
+
+    fn fade_out(&mut self, duration: time::Duration) {
+        if self.paused() {
+            self.reset_sink();
+            return;
+        }
+        self.detach_sink().fade_out(duration);
+    }
+
+    fn paused(&self) -> bool {
+        self.sink.is_paused()
+    }
+
+    fn playing(&self) -> bool {
+        !self.paused() && !self.stopped()
+    }
+
+    fn elapsed(&self) -> time::Duration {
+        self.state.elapsed()
+    }
+
+    fn set_query_interval(&mut self, t: time::Duration) {
+        self.state.set_query_interval(t)
+    }
+
+    fn seek(&mut self, pos: time::Duration) -> GameResult {
+        if self.stopped() {
+            return Ok(());
+        }
+        // Build the new sound before cutting off the old one, so there's
+        // as little silence in between as possible.
+        let sound = self.sound(pos, time::Duration::ZERO)?;
+        let paused = self.paused();
+        let play_time = self.state.play_time.load(Ordering::SeqCst);
+        self.reset_sink();
+        self.state.play_time.store(play_time, Ordering::SeqCst);
+        if paused {
+            self.sink.pause();
+        }
+        self.sink.append(sound);
+        Ok(())
+    }
+
+    fn position(&self) -> time::Duration {
+        self.state.position.get()
+    }
+
+    fn id(&self) -> SoundId {
+        self.state.id
+    }
+
+    fn bus(&self) -> &Bus {
+        &self.state.bus
+    }
+
+    fn set_bus(&mut self, bus: &Bus) {
+        self.state.bus = bus.clone();
+        self.reset_sink();
+    }
+
+    fn add_effect(&self, effect: Effect) -> EffectHandle {
+        self.state.effects.add(effect)
+    }
+
+    fn clear_effects(&self) {
+        self.state.effects.clear()
+    }
+}
+
+impl Source {
+    /// Replaces the sink with a fresh one on the current bus, stopping playback.
+    fn reset_sink(&mut self) {
+        self.sink = self.state.bus.new_sink();
+        self.state.play_time.store(0, Ordering::SeqCst);
+        self.state.position.reset();
+    }
+
+    /// Lets whatever is playing carry on by itself, with the source
+    /// getting a fresh sink to play on from now on.  Returns the fader
+    /// left controlling the volume of what was playing.
+    fn detach_sink(&mut self) -> Arc<Fader> {
+        let new_sink = self.state.bus.new_sink();
+        let old_sink = mem::replace(&mut self.sink, new_sink);
+        old_sink.detach();
+        self.state.play_time.store(0, Ordering::SeqCst);
+        self.state.position.reset();
+        self.panner = Arc::new(Panner::new(self.panner.pan(), self.panner.law()));
+        self.state.detach_fader()
+    }
+}
+
+impl fmt::Debug for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<Audio source: {self:p}>")
+    }
+}
+
+/// A source of audio data located in space relative to the
+/// [`Listener`](struct.Listener.html), getting quieter with distance
+/// as described by its [`Attenuation`](struct.Attenuation.html).
+/// Will stop playing when dropped.
+pub struct SpatialSource {
+    sink: SpatialSink,
+    state: SourceState,
+    listener: Listener,
+}
+
+impl SpatialSource {
+    /// Create a new `SpatialSource` from the given file.
+    pub fn new<P: AsRef<path::Path>>(
+        fs: &impl Has<Filesystem>,
+        audio: &impl Has<AudioContext>,
+        path: P,
+    ) -> GameResult<Self> {
+        let path = path.as_ref();
+        let data = SoundData::new(fs, path)?;
+        SpatialSource::from_data(audio, data)
+    }
+
+    /// Creates a new `SpatialSource` using the given `SoundData` object.
+    pub fn from_data(audio: &impl Has<AudioContext>, data: SoundData) -> GameResult<Self> {
+        let audio = audio.retrieve();
+        if !data.can_play() {
+            return Err(GameError::AudioError(
+                "Could not decode the given audio data".to_string(),
+            ));
+        }
+        Ok(SpatialSource::from_source_data(
+            audio,
+            SourceData::Encoded(EncodedData::Memory(data)),
+        ))
+    }
+
+    /// Creates a new `SpatialSource` playing the given `DecodedSoundData`, which
+    /// doesn't need decoding each time it plays.
+    pub fn from_decoded(audio: &impl Has<AudioContext>, data: DecodedSoundData) -> Self {
+        SpatialSource::from_source_data(audio.retrieve(), SourceData::Samples(data.0))
+    }
+
+    /// Create a new `SpatialSource` which streams the given file as it plays,
+    /// rather than loading all of it up front.
+    pub fn new_streaming<P: AsRef<path::Path>>(
+        fs: &impl Has<Filesystem>,
+        audio: &impl Has<AudioContext>,
+        path: P,
+    ) -> GameResult<Self> {
+        let file = fs.retrieve().open(path)?;
+        SpatialSource::from_file(audio, file)
+    }
+
+    /// Creates a new `SpatialSource` which decodes the given `File` bit by bit as it plays.
+    ///
+    /// See [`Source::from_file()`](struct.Source.html#method.from_file) for details.
+    pub fn from_file(audio: &impl Has<AudioContext>, file: File) -> GameResult<Self> {
+        let audio = audio.retrieve();
+        let data = SourceData::Encoded(EncodedData::Stream(StreamReader::new(file)));
+        if !data.can_play() {
+            return Err(GameError::AudioError(
+                "Could not decode the given audio data".to_string(),
+            ));
+        }
+        Ok(SpatialSource::from_source_data(audio, data))
+    }
+
+    /// Creates a new `SpatialSource` playing raw PCM samples.
+    ///
+    /// See [`Source::from_samples()`](struct.Source.html#method.from_samples) for details.
+    pub fn from_samples(
+        audio: &impl Has<AudioContext>,
+        channels: u16,
+        sample_rate: u32,
+        samples: impl Into<Arc<[f32]>>,
+    ) -> GameResult<Self> {
+        let data = SamplesData::new(channels, sample_rate, samples.into())?;
+        Ok(SpatialSource::from_source_data(
+            audio.retrieve(),
+            SourceData::Samples(data),
+        ))
+    }
+
+    /// Creates a new `SpatialSource` whose samples are produced on the fly by the
+    /// given generator.
+    ///
+    /// See [`Source::from_generator()`](struct.Source.html#method.from_generator) for details.
+    pub fn from_generator<F>(
+        audio: &impl Has<AudioContext>,
+        channels: u16,
+        sample_rate: u32,
+        generator: F,
+    ) -> GameResult<Self>
+    where
+        F: FnMut(&mut [f32]) -> usize + Send + 'static,
+    {
+        let data = GeneratorData::new(channels, sample_rate, generator)?;
+        Ok(SpatialSource::from_source_data(
+            audio.retrieve(),
+            SourceData::Generator(data),
+        ))
+    }
+
+    fn from_source_data(audio: &AudioContext, data: SourceData) -> Self {
+        let bus = audio.master();
+        let sink = SpatialSink::new(bus, audio.listener(), Emitter::default());
+
+        SpatialSource {
+            sink,
+            state: SourceState::new(data, bus, &audio.finished),
+            listener: audio.listener().clone(),
+        }
+    }
+}
+
+impl SoundSource for SpatialSource {
+    /// Plays the `SpatialSource`; waits until done if the sound is currently playing.
+    fn play_later(&self) -> GameResult {
+        // Creating a new Decoder each time seems a little messy,
+        // since it may do checking and data-type detection that is
+        // redundant, but it's not super expensive.  Data that was decoded
+        // up front with `SoundData::decode()` skips all that.
+        // See https://github.com/ggez/ggez/issues/98 for discussion
+        self.sink.append(
+            self.state
+                .sound(self.state.skip_duration, self.state.fade_in)?,
+        );
+        Ok(())
+    }
+
+    fn play_later_at(&self, time: time::Duration) -> GameResult {
+        self.sink.append_at(
+            self.state
+                .sound(self.state.skip_duration, self.state.fade_in)?,
+            time,
+        );
+        Ok(())
+    }
+
+    fn play_detached(&mut self, audio: &impl Has<AudioContext>) -> GameResult {
+        let audio = audio.retrieve();
+        self.stop(audio)?;
+        self.play_later()?;
+        let _ = self.detach_sink();
+        Ok(())
+    }
+
+    fn set_repeat(&mut self, repeat: bool) {
+        self.state.set_repeat(repeat)
+    }
+
+    fn set_fade_in(&mut self, dur: time::Duration) {
+        self.state.set_fade_in(dur)
+    }
+
+    fn set_start(&mut self, dur: time::Duration) {
+        self.state.set_start(dur)
+    }
+
+    fn set_pitch(&mut self, ratio: f32) {
+        self.state.set_pitch(ratio)
+    }
+
+    fn repeat(&self) -> bool {
+        self.state.repeat()
+    }
+    fn set_loop_points(&mut self, points: Option<LoopPoints>) {
+        self.state.set_loop_points(points)
+    }
+    fn loop_points(&self) -> Option<LoopPoints> {
+        self.state.loop_points()
+    }
+
+    fn pause(&self) {
+        self.sink.pause()
+    }
+
+    fn resume(&self) {
+        self.sink.play()
+    }
+
+    fn stop(&mut self, _audio: &impl Has<AudioContext>) -> GameResult {
+        // Sinks cannot be reused once stopped, so to stop the current
+        // sound we have to drop the old sink and create a new one in its
+        // place, on the same bus.
+        self.reset_sink();
+        Ok(())
+    }
+
+    fn stopped(&self) -> bool {
+        self.sink.empty()
+    }
+
+    fn volume(&self) -> f32 {
+        self.state.fader.volume()
+    }
+
+    fn set_volume(&mut self, value: f32) {
+        self.state.fader.set_volume(value)
+    }
+
+    fn fade_to(&mut self, volume: f32, duration: time::Duration) {
+        self.state.fader.fade_to(volume, duration)
+    }
+
+    fn fade_out(&mut self, duration: time::Duration) {
+        if self.paused() {
+            self.reset_sink();
+            return;
+        }
+        self.detach_sink().fade_out(duration);
+    }
+
+    fn paused(&self) -> bool {
+        self.sink.is_paused()
+    }
+
+    fn playing(&self) -> bool {
+        !self.paused() && !self.stopped()
+    }
+
+    fn elapsed(&self) -> time::Duration {
+        self.state.elapsed()
+    }
+
+    fn set_query_interval(&mut self, t: time::Duration) {
+        self.state.set_query_interval(t)
+    }
+
+    fn seek(&mut self, pos: time::Duration) -> GameResult {
+        if self.stopped() {
+            return Ok(());
+        }
+        // Build the new sound before cutting off the old one, so there's
+        // as little silence in between as possible.
+        let sound = self.state.sound(pos, time::Duration::ZERO)?;
+        let paused = self.paused();
+        let play_time = self.state.play_time.load(Ordering::SeqCst);
+        self.reset_sink();
+        self.state.play_time.store(play_time, Ordering::SeqCst);
+        if paused {
+            self.sink.pause();
+        }
+        self.sink.append(sound);
+        Ok(())
+    }
+
+    fn position(&self) -> time::Duration {
+        self.state.position.get()
+    }
+
+    fn id(&self) -> SoundId {
+        self.state.id
+    }
+
+    fn bus(&self) -> &Bus {
+        &self.state.bus
+    }
+
+    fn set_bus(&mut self, bus: &Bus) {
+        self.state.bus = bus.clone();
+        self.reset_sink();
+    }
+
+    fn add_effect(&self, effect: Effect) -> EffectHandle {
+        self.state.effects.add(effect)
+    }
+
+    fn clear_effects(&self) {
+        self.state.effects.clear()
+    }
+}
+
+impl SpatialSource {
+    /// Replaces the sink with a fresh one on the current bus, stopping playback.
+    fn reset_sink(&mut self) {
+        self.sink = self.new_sink();
+        self.state.play_time.store(0, Ordering::SeqCst);
+        self.state.position.reset();
+    }
+
+    /// Lets whatever is playing carry on by itself, with the source
+    /// getting a fresh sink to play on from now on.  Returns the fader
+    /// left controlling the volume of what was playing.
+    fn detach_sink(&mut self) -> Arc<Fader> {
+        let new_sink = self.new_sink();
+        let old_sink = mem::replace(&mut self.sink, new_sink);
+        old_sink.detach();
+        self.state.play_time.store(0, Ordering::SeqCst);
+        self.state.position.reset();
+        self.state.detach_fader()
+    }
+
+    fn new_sink(&self) -> SpatialSink {
+        SpatialSink::new(&self.state.bus, &self.listener, self.sink.emitter())
+    }
+
+    /// Set location of the sound.
+    pub fn set_position<P>(&mut self, pos: P)
+    where
+        P: Into<mint::Point3<f32>>,
+    {
+        let pos = glam::Vec3::from(pos.into());
+        self.sink.update_emitter(|e| e.position = pos);
+    }
+
+    /// Set locations of the listener's ears, overriding the
+    /// [`Listener`](struct.Listener.html) for this source.
+    pub fn set_ears<P>(&mut self, left: P, right: P)
+    where
+        P: Into<mint::Point3<f32>>,
+    {
+        let ears = (
+            glam::Vec3::from(left.into()),
+            glam::Vec3::from(right.into()),
+        );
+        self.sink.update_emitter(|e| e.ears = Some(ears));
+    }
+
+    /// Undoes [`set_ears()`](#method.set_ears), going back to being heard
+    /// by the audio context's [`Listener`](struct.Listener.html).
+    pub fn clear_ears(&mut self) {
+        self.sink.update_emitter(|e| e.ears = None);
+    }
+
+    /// Sets the velocity of the sound, in units per second, which is
+    /// only used for the doppler effect.
+    pub fn set_velocity<V>(&mut self, velocity: V)
+    where
+        V: Into<mint::Vector3<f32>>,
+    {
+        let velocity = glam::Vec3::from(velocity.into());
+        self.sink.update_emitter(|e| e.velocity = velocity);
+    }
+
+    /// Gets how the volume of the sound falls off with distance.
+    pub fn attenuation(&self) -> Attenuation {
+        self.sink.emitter().attenuation
+    }
+
+    /// Sets how the volume of the sound falls off with distance.
+    pub fn set_attenuation(&mut self, attenuation: Attenuation) {
+        self.sink.update_emitter(|e| e.attenuation = attenuation);
+    }
+
+    /// Sets how strong the doppler effect is, which shifts the pitch of the
+    /// sound as it moves towards or away from the listener.  `0.0`, the
+    /// default, turns it off, and `1.0` is realistic.
+    pub fn set_doppler_factor(&mut self, factor: f32) {
+        self.sink
+            .update_emitter(|e| e.doppler_factor = factor.max(0.0));
+    }
+}
+
+impl fmt::Debug for SpatialSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<Spatial audio source: {self:p}>")
+    }
+}
+
+/// An `AudioContext` with the `Manual` backend, which only plays what it's
+/// told to render, for tests which need to hear what comes out.
+#[cfg(test)]
+pub(crate) fn manual_audio_for_tests(channels: u16, sample_rate: u32) -> AudioContext {
+    let backend = AudioBackend::Manual {
+        channels,
+        sample_rate,
+    };
+    AudioContext::with_backend(&crate::filesystem::dummy_fs_for_tests(), backend).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::dummy_fs_for_tests;
+
+    #[test]
+    fn manual_backend_captures() {
+        let audio = manual_audio_for_tests(1, 1000);
+        assert!(audio.device().is_none());
+        audio.start_capture();
+        audio.render(time::Duration::from_millis(10)).unwrap();
+
+        let mut source = Source::from_samples(&audio, 1, 1000, vec![0.5; 5]).unwrap();
+        source.play(&audio).unwrap();
+        assert!(!source.stopped());
+        audio.render(time::Duration::from_millis(10)).unwrap();
+        assert!(source.stopped());
+
+        let captured = audio.stop_capture();
+        assert_eq!(captured.duration(), time::Duration::from_millis(20));
+        let samples = captured.samples();
+        // The sound starts on the very next frame mixed after playing it.
+        assert!(samples[..10].iter().all(|&s| s == 0.0));
+        assert_eq!(&samples[10..15], &[0.5; 5]);
+        assert!(samples[15..].iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn fade_out_keeps_volume() {
+        let audio = manual_audio_for_tests(1, 1000);
+        let mut source = Source::from_samples(&audio, 1, 1000, vec![0.5; 10]).unwrap();
+        source.set_volume(0.5);
+        source.play(&audio).unwrap();
+        audio.render(time::Duration::from_millis(5)).unwrap();
+        source.fade_out(time::Duration::from_millis(5));
+        assert_eq!(source.volume(), 0.5);
+
+        source.play(&audio).unwrap();
+        audio.start_capture();
+        audio.render(time::Duration::from_millis(20)).unwrap();
+        let captured = audio.stop_capture();
+        // Once the old sound has faded out, the new one plays on at full volume.
+        assert_eq!(&captured.samples()[5..10], &[0.25; 5]);
+    }
+
+    #[test]
+    fn decoded_data() {
+        let fs = dummy_fs_for_tests();
+        let data = SoundData::new(&fs, "/pew.ogg").unwrap();
+        let decoded = data.decode().unwrap();
+        assert_eq!((decoded.channels(), decoded.sample_rate()), (1, 44100));
+        assert_eq!(decoded.duration(), data.duration().unwrap());
+        assert_eq!(decoded.memory_usage(), decoded.samples().len() * 4);
+        assert!(decoded.memory_usage() > data.memory_usage());
+
+        let audio = manual_audio_for_tests(1, 44100);
+        let mut source = Source::from_decoded(&audio, decoded.clone());
+        assert_eq!(source.loop_points(), data.loop_points());
+        source.play(&audio).unwrap();
+        audio.start_capture();
+        audio.render(time::Duration::from_millis(10)).unwrap();
+        let captured = audio.stop_capture();
+        assert_eq!(captured.samples(), &decoded.samples()[..441]);
+
+        assert!(SoundData::from_bytes(&[0; 16]).decode().is_err());
+    }
+
+    #[test]
+    fn reports_finished_sounds() {
+        let audio = manual_audio_for_tests(1, 1000);
+        let mut first = Source::from_samples(&audio, 1, 1000, vec![0.5; 5]).unwrap();
+        let mut second = Source::from_samples(&audio, 1, 1000, vec![0.5; 15]).unwrap();
+        let mut stopped = Source::from_samples(&audio, 1, 1000, vec![0.5; 5]).unwrap();
+        first.play(&audio).unwrap();
+        second.play(&audio).unwrap();
+        stopped.play(&audio).unwrap();
+        stopped.stop(&audio).unwrap();
+
+        audio.render(time::Duration::from_millis(10)).unwrap();
+        assert_eq!(audio.take_finished(), vec![first.id()]);
+        audio.render(time::Duration::from_millis(10)).unwrap();
+        assert_eq!(audio.take_finished(), vec![second.id()]);
+        assert!(audio.take_finished().is_empty());
+    }
+
+    #[test]
+    fn scheduled_playback() {
+        let audio = manual_audio_for_tests(2, 1000);
+        audio.render(time::Duration::from_millis(5)).unwrap();
+        assert_eq!(audio.clock(), time::Duration::from_millis(5));
+
+        let mut source = Source::from_samples(&audio, 1, 1000, vec![0.5; 3]).unwrap();
+        source
+            .play_at(&audio, time::Duration::from_millis(12))
+            .unwrap();
+        audio.start_capture();
+        audio.render(time::Duration::from_millis(10)).unwrap();
+        assert!(!source.stopped());
+        audio.render(time::Duration::from_millis(5)).unwrap();
+        assert!(source.stopped());
+        assert_eq!(audio.clock(), time::Duration::from_millis(20));
+
+        // Seven frames of silence, from 5ms to 12ms, then the sound in both channels.
+        let captured = audio.stop_capture();
+        let samples = captured.samples();
+        assert!(samples[..14].iter().all(|&s| s == 0.0));
+        assert_eq!(&samples[14..20], &[0.5; 6]);
+        assert!(samples[20..].iter().all(|&s| s == 0.0));
+    }
+}
@@ -0,0 +1,81 @@
+//! A helper for playing background music, crossfading from one
+//! track to the next.
+
+use std::time;
+
+use super::{AudioContext, Bus, SoundSource, Source};
+use crate::context::Has;
+use crate::error::GameResult;
+
+/// Plays one music track at a time on the `music` bus, fading smoothly
+/// from the current track into the next whenever it is changed.
+///
+/// ```rust,no_run
+/// # use ggez::audio::{MusicPlayer, SoundSource, Source};
+/// # use std::time::Duration;
+/// # fn t(ctx: &ggez::Context) -> ggez::GameResult {
+/// let mut music = MusicPlayer::new(ctx);
+/// let mut track = Source::new_streaming(ctx, "/boss.ogg")?;
+/// track.set_repeat(true);
+/// music.play(ctx, track, Duration::from_secs(2))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MusicPlayer {
+    bus: Bus,
+    current: Option<Source>,
+}
+
+impl MusicPlayer {
+    /// Creates a new `MusicPlayer`, playing on the audio context's `music` bus.
+    pub fn new(audio: &impl Has<AudioContext>) -> Self {
+        MusicPlayer {
+            bus: audio.retrieve().music().clone(),
+            current: None,
+        }
+    }
+
+    /// Gets the bus tracks are played on.
+    pub fn bus(&self) -> &Bus {
+        &self.bus
+    }
+
+    /// Starts playing `track`, fading it in over `crossfade` while fading out
+    /// whatever was playing before.
+    ///
+    /// The track is moved to the player's bus and faded in up to the volume
+    /// it was set to.  Set it to repeat beforehand for looping music.
+    pub fn play(
+        &mut self,
+        audio: &impl Has<AudioContext>,
+        mut track: Source,
+        crossfade: time::Duration,
+    ) -> GameResult {
+        self.stop(crossfade);
+        let volume = track.volume();
+        track.set_bus(&self.bus);
+        track.set_volume(0.0);
+        track.play(audio)?;
+        track.fade_to(volume, crossfade);
+        self.current = Some(track);
+        Ok(())
+    }
+
+    /// Fades out the current track over `fade`, leaving nothing playing.
+    pub fn stop(&mut self, fade: time::Duration) {
+        if let Some(mut track) = self.current.take() {
+            track.fade_out(fade);
+        }
+    }
+
+    /// Gets the track currently playing, if any.
+    pub fn current(&self) -> Option<&Source> {
+        self.current.as_ref()
+    }
+
+    /// Gets the track currently playing, if any, for adjusting it.
+    pub fn current_mut(&mut self) -> Option<&mut Source> {
+        self.current.as_mut()
+    }
+}
@@ -0,0 +1,307 @@
+//! Where the mixed output of the master bus ends up: the output device,
+//! or software standing in for one, optionally capturing it on the way.
+
+use std::io;
+use std::mem;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time;
+
+use super::bus::BusOutput;
+use crate::error::GameResult;
+
+/// How many frames the audio thread collects before handing them over
+/// to a capture, so it doesn't need to lock for every sample.
+const CAPTURE_CHUNK_FRAMES: usize = 256;
+
+/// How often the null backend wakes up to mix whatever is due.
+const NULL_BACKEND_INTERVAL: time::Duration = time::Duration::from_millis(10);
+
+/// Audio captured from the master bus with
+/// [`AudioContext::start_capture()`](struct.AudioContext.html#method.start_capture).
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedAudio {
+    channels: u16,
+    sample_rate: u32,
+    samples: Vec<f32>,
+}
+
+impl CapturedAudio {
+    /// Returns the number of channels of the audio.
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Returns the sample rate of the audio.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Returns the captured samples, interleaved if there is more than one channel.
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    /// Returns how long the captured audio lasts.
+    pub fn duration(&self) -> time::Duration {
+        let frames = self.samples.len() / usize::from(self.channels);
+        time::Duration::from_secs_f64(frames as f64 / f64::from(self.sample_rate))
+    }
+
+    /// Writes the audio out as a 16-bit WAV file.
+    ///
+    /// ```rust,no_run
+    /// # fn t(ctx: &ggez::Context) -> ggez::GameResult {
+    /// let captured = ctx.audio.stop_capture();
+    /// captured.write_wav(&mut ctx.fs.create("/captured.wav")?)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_wav<W: io::Write>(&self, writer: &mut W) -> GameResult {
+        let block_align = self.channels * 2;
+        let data_len = self.samples.len() as u32 * 2;
+        let mut bytes = Vec::with_capacity(44 + data_len as usize);
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        // Plain integer PCM.
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&self.channels.to_le_bytes());
+        bytes.extend_from_slice(&self.sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(self.sample_rate * u32::from(block_align)).to_le_bytes());
+        bytes.extend_from_slice(&block_align.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        for sample in &self.samples {
+            let sample = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        writer.write_all(&bytes)?;
+        Ok(())
+    }
+}
+
+/// Counts the frames mixed by the master bus, which is what the audio clock
+/// and sounds scheduled to start at a given time go by.
+#[derive(Debug)]
+pub(crate) struct AudioClock {
+    frames: AtomicU64,
+    sample_rate: u32,
+}
+
+impl AudioClock {
+    pub fn new(sample_rate: u32) -> Self {
+        AudioClock {
+            frames: AtomicU64::new(0),
+            sample_rate,
+        }
+    }
+
+    /// Returns how many frames have been mixed so far.  While a frame is
+    /// being mixed, this is the number of that frame.
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Acquire)
+    }
+
+    /// Counts another frame as mixed.
+    pub fn advance(&self) {
+        let _ = self.frames.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Returns how much audio has been mixed so far.
+    pub fn time(&self) -> time::Duration {
+        time::Duration::from_secs_f64(self.frames() as f64 / f64::from(self.sample_rate))
+    }
+
+    /// Returns the frame at which the clock reaches the given time.
+    pub fn frame_at(&self, time: time::Duration) -> u64 {
+        (time.as_secs_f64() * f64::from(self.sample_rate)).round() as u64
+    }
+}
+
+/// Captured samples, shared between the `AudioContext` and the `Tapped`
+/// output collecting them.
+#[derive(Debug)]
+pub(crate) struct Capture {
+    channels: u16,
+    sample_rate: u32,
+    enabled: AtomicBool,
+    samples: Mutex<Vec<f32>>,
+}
+
+impl Capture {
+    pub fn new(channels: u16, sample_rate: u32) -> Self {
+        Capture {
+            channels,
+            sample_rate,
+            enabled: AtomicBool::new(false),
+            samples: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<f32>> {
+        self.samples.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn start(&self) {
+        self.lock().clear();
+        self.enabled.store(true, Ordering::Release);
+    }
+
+    /// Takes everything captured so far.
+    pub fn take(&self) -> CapturedAudio {
+        CapturedAudio {
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            samples: mem::take(&mut *self.lock()),
+        }
+    }
+
+    pub fn stop(&self) -> CapturedAudio {
+        self.enabled.store(false, Ordering::Release);
+        self.take()
+    }
+}
+
+/// The output of the master bus, copying samples into a `Capture` while it
+/// is enabled, and advancing the audio clock.
+pub(crate) struct Tapped {
+    input: BusOutput,
+    capture: Arc<Capture>,
+    pending: Vec<f32>,
+    clock: Arc<AudioClock>,
+    // Which channel of the current frame the next sample is for.
+    channel: u16,
+}
+
+impl Tapped {
+    pub fn new(input: BusOutput, capture: Arc<Capture>, clock: Arc<AudioClock>) -> Self {
+        let chunk = CAPTURE_CHUNK_FRAMES * usize::from(capture.channels);
+        Tapped {
+            input,
+            capture,
+            pending: Vec::with_capacity(chunk),
+            clock,
+            channel: 0,
+        }
+    }
+
+    /// Hands everything collected so far over to the capture.
+    pub fn flush(&mut self) {
+        if !self.pending.is_empty() {
+            self.capture.lock().append(&mut self.pending);
+        }
+    }
+
+    /// Mixes the given number of frames, for backends without a device pulling on them.
+    pub fn mix_frames(&mut self, frames: u64) {
+        let samples = frames * u64::from(self.capture.channels);
+        for _ in 0..samples {
+            let _ = self.next();
+        }
+    }
+}
+
+impl Iterator for Tapped {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        if self.capture.enabled.load(Ordering::Acquire) {
+            self.pending.push(sample);
+            if self.pending.len() == self.pending.capacity() {
+                self.flush();
+            }
+        } else {
+            // Whatever was collected before the capture was stopped
+            // missed the boat; don't let it leak into the next one.
+            self.pending.clear();
+        }
+        self.channel += 1;
+        if self.channel == self.capture.channels {
+            self.channel = 0;
+            self.clock.advance();
+        }
+        Some(sample)
+    }
+}
+
+impl rodio::Source for Tapped {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<time::Duration> {
+        None
+    }
+}
+
+/// A thread standing in for the output device, mixing in real time
+/// until it is dropped.
+pub(crate) struct NullThread {
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl NullThread {
+    pub fn spawn(mut output: Tapped) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let sample_rate = rodio::Source::sample_rate(&output);
+        let handle = thread::spawn(move || {
+            let start = time::Instant::now();
+            let mut frames_mixed = 0;
+            while !thread_stop.load(Ordering::Relaxed) {
+                thread::sleep(NULL_BACKEND_INTERVAL);
+                let frames_due = (start.elapsed().as_secs_f64() * f64::from(sample_rate)) as u64;
+                output.mix_frames(frames_due - frames_mixed);
+                frames_mixed = frames_due;
+            }
+        });
+        NullThread {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for NullThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wav_header() {
+        let captured = CapturedAudio {
+            channels: 2,
+            sample_rate: 8000,
+            samples: vec![0.0, 1.0, -1.0, 0.5],
+        };
+        let mut wav = Vec::new();
+        captured.write_wav(&mut wav).unwrap();
+        assert_eq!(wav.len(), 44 + 8);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(&wav[28..32], &32000u32.to_le_bytes());
+        assert_eq!(&wav[44..], &[0, 0, 0xff, 0x7f, 0x01, 0x80, 0xff, 0x3f]);
+    }
+}
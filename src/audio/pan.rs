@@ -0,0 +1,255 @@
+//! Left/right panning for sources that aren't placed in space.
+
+use std::f32::consts::{FRAC_PI_4, SQRT_2};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time;
+
+/// How long the gains take to follow a change of pan, so that moving
+/// a sound around doesn't click.
+const PAN_RAMP: time::Duration = time::Duration::from_millis(10);
+
+/// How the volume of the left and right channels depends on the pan of
+/// a [`Source`](struct.Source.html).
+///
+/// Both laws leave a centered sound exactly as it is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, smart_default::SmartDefault)]
+pub enum PanLaw {
+    /// Keeps the overall loudness the same wherever the sound is panned,
+    /// so sounds don't seem to dip as they move across.  A sound panned
+    /// hard to one side plays 3dB louder in that speaker than it would centered.
+    #[default]
+    ConstantPower,
+    /// Only ever turns the channel on the far side down, like the balance
+    /// knob on a stereo.  Panned sounds seem quieter, but never get louder.
+    Balance,
+}
+
+impl PanLaw {
+    /// Returns the gains of the left and right channels at the given pan.
+    fn gains(self, pan: f32) -> [f32; 2] {
+        let pan = pan.clamp(-1.0, 1.0);
+        match self {
+            // Not quite what the sums below come out to, but dead center
+            // should leave sounds exactly as they are.
+            _ if pan == 0.0 => [1.0, 1.0],
+            PanLaw::ConstantPower => {
+                let angle = (pan + 1.0) * FRAC_PI_4;
+                [angle.cos() * SQRT_2, angle.sin() * SQRT_2]
+            }
+            PanLaw::Balance => [(1.0 - pan).min(1.0), (1.0 + pan).min(1.0)],
+        }
+    }
+}
+
+/// The pan of a source, shared with the `Panned` stages applying it on
+/// the audio thread, in the same way as a `Fader`.
+#[derive(Debug)]
+pub(crate) struct Panner {
+    settings: Mutex<(f32, PanLaw)>,
+    generation: AtomicUsize,
+}
+
+impl Panner {
+    pub fn new(pan: f32, law: PanLaw) -> Self {
+        Panner {
+            settings: Mutex::new((pan, law)),
+            generation: AtomicUsize::new(0),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, (f32, PanLaw)> {
+        self.settings.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn gains(&self) -> [f32; 2] {
+        let (pan, law) = *self.lock();
+        law.gains(pan)
+    }
+
+    pub fn pan(&self) -> f32 {
+        self.lock().0
+    }
+
+    pub fn set_pan(&self, pan: f32) {
+        self.lock().0 = pan.clamp(-1.0, 1.0);
+        let _ = self.generation.fetch_add(1, Ordering::Release);
+    }
+
+    pub fn law(&self) -> PanLaw {
+        self.lock().1
+    }
+
+    pub fn set_law(&self, law: PanLaw) {
+        self.lock().1 = law;
+        let _ = self.generation.fetch_add(1, Ordering::Release);
+    }
+}
+
+/// Applies a `Panner` to a `rodio` source, turning mono sources into stereo
+/// and scaling the first two channels of anything else.
+///
+/// Panning is left out altogether for mono output, where it would only
+/// make sounds quieter, or silent.
+pub(crate) struct Panned<S> {
+    input: S,
+    panner: Arc<Panner>,
+    stereo: bool,
+    generation: usize,
+    gains: [f32; 2],
+    target: [f32; 2],
+    steps: [f32; 2],
+    remaining: u32,
+    channel: u16,
+    // The second channel of a mono sample, still to be played.
+    right: Option<f32>,
+}
+
+impl<S> Panned<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    pub fn new(input: S, panner: Arc<Panner>, stereo: bool) -> Self {
+        let gains = panner.gains();
+        Panned {
+            generation: panner.generation.load(Ordering::Acquire),
+            gains,
+            target: gains,
+            input,
+            panner,
+            stereo,
+            steps: [0.0; 2],
+            remaining: 0,
+            channel: 0,
+            right: None,
+        }
+    }
+
+    fn sync(&mut self) {
+        let generation = self.panner.generation.load(Ordering::Acquire);
+        if generation != self.generation {
+            self.generation = generation;
+            let frames = (PAN_RAMP.as_secs_f64() * f64::from(self.input.sample_rate())) as u32;
+            self.remaining = frames.max(1);
+            self.target = self.panner.gains();
+            for ((step, target), gain) in self.steps.iter_mut().zip(self.target).zip(self.gains) {
+                *step = (target - gain) / self.remaining as f32;
+            }
+        }
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            for (gain, step) in self.gains.iter_mut().zip(self.steps) {
+                *gain += step;
+            }
+            // Land exactly on the target, whatever rounding there was on the way.
+            if self.remaining == 0 {
+                self.gains = self.target;
+            }
+        }
+    }
+}
+
+impl<S> Iterator for Panned<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if !self.stereo {
+            return self.input.next();
+        }
+        if let Some(right) = self.right.take() {
+            return Some(right);
+        }
+        // Only ever change the gains between frames, so that the
+        // channels of a frame stay in step.
+        if self.channel == 0 {
+            self.sync();
+        }
+        let sample = self.input.next()?;
+        let channels = self.input.channels();
+        if channels == 1 {
+            self.right = Some(sample * self.gains[1]);
+            return Some(sample * self.gains[0]);
+        }
+        let gain = self
+            .gains
+            .get(usize::from(self.channel))
+            .copied()
+            .unwrap_or(1.0);
+        self.channel += 1;
+        if self.channel >= channels {
+            self.channel = 0;
+        }
+        Some(sample * gain)
+    }
+}
+
+impl<S> rodio::Source for Panned<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        let len = self.input.current_frame_len()?;
+        if self.stereo && self.input.channels() == 1 {
+            Some(len * 2 + usize::from(self.right.is_some()))
+        } else {
+            Some(len)
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        if self.stereo {
+            self.input.channels().max(2)
+        } else {
+            self.input.channels()
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<time::Duration> {
+        self.input.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    #[test]
+    fn pan_laws() {
+        for law in [PanLaw::ConstantPower, PanLaw::Balance] {
+            let [left, right] = law.gains(0.0);
+            assert!((left - 1.0).abs() < 1e-6 && (right - 1.0).abs() < 1e-6);
+            let [left, _] = law.gains(1.0);
+            assert!(left.abs() < 1e-6);
+        }
+        let [left, right] = PanLaw::ConstantPower.gains(-1.0);
+        assert!((left - SQRT_2).abs() < 1e-6 && right.abs() < 1e-6);
+        assert_eq!(PanLaw::Balance.gains(-0.5), [1.0, 0.5]);
+    }
+
+    #[test]
+    fn pans_mono_to_stereo() {
+        let panner = Arc::new(Panner::new(0.0, PanLaw::Balance));
+        let mut panned = Panned::new(
+            SamplesBuffer::new(1, 100, vec![1.0f32; 10]),
+            panner.clone(),
+            true,
+        );
+        assert_eq!(rodio::Source::channels(&panned), 2);
+        assert_eq!(panned.next(), Some(1.0));
+        assert_eq!(panned.next(), Some(1.0));
+
+        // The ramp takes a frame at 100Hz.
+        panner.set_pan(1.0);
+        assert_eq!(panned.next(), Some(0.0));
+        assert_eq!(panned.next(), Some(1.0));
+        assert_eq!(panned.collect::<Vec<_>>().len(), 16);
+    }
+}
@@ -0,0 +1,401 @@
+//! A player for sound effects which play many times over, overlapping.
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time;
+
+use super::data::{EncodedData, Playback, PlaybackPosition, SourceData};
+use super::fade::{Faded, Fader};
+use super::finished::{FinishedQueue, Notify, SoundId};
+use super::sink::Sink;
+use super::{AudioContext, Bus, DecodedSoundData, SoundData};
+use crate::context::Has;
+use crate::error::{GameError, GameResult};
+
+/// How long a voice that gets stopped or stolen takes to fade out, so it
+/// doesn't click.
+const STEAL_FADE: time::Duration = time::Duration::from_millis(5);
+
+/// Which voice a `SoundPool` cuts short to make room for a new one once
+/// all of its voices are playing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, smart_default::SmartDefault)]
+pub enum VoiceStealing {
+    /// Stop the voice which started playing first.
+    #[default]
+    Oldest,
+    /// Stop the voice set to play at the lowest volume, or the oldest of those
+    /// if several are equally quiet.  Voices only differ in volume with a
+    /// [volume variation](struct.SoundPool.html#method.set_volume_variation);
+    /// how loud their sound is at the moment doesn't come into it.
+    Quietest,
+    /// Don't stop anything, and don't play the new voice either.
+    Never,
+}
+
+/// Identifies one voice played by a `SoundPool`.
+///
+/// Handles stay valid after the voice ends; they just stop referring to
+/// anything playing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct VoiceHandle(SoundId);
+
+impl VoiceHandle {
+    /// Returns the id identifying the voice when it finishes playing; see
+    /// [`AudioContext::take_finished()`](struct.AudioContext.html#method.take_finished).
+    pub fn id(&self) -> SoundId {
+        self.0
+    }
+}
+
+struct Voice {
+    id: SoundId,
+    sink: Sink,
+    fader: Arc<Fader>,
+    // The randomized volume of the voice, before the pool's volume.
+    gain: f32,
+}
+
+impl Voice {
+    /// Fades the voice out quickly and leaves it to finish on its own.
+    fn stop(self) {
+        self.fader.fade_out(STEAL_FADE);
+        self.sink.detach();
+    }
+}
+
+/// Plays one `SoundData` as many overlapping voices, such as for a
+/// gunshot or footstep that may go off again before the last one is over.
+///
+/// At most `max_voices` play at once; past that, playing another steals
+/// one already playing according to the pool's [`VoiceStealing`] policy.
+/// Each voice can get a slightly random volume and pitch, so that the same
+/// sound played over and over doesn't get monotonous.
+///
+/// ```rust,no_run
+/// # use ggez::audio::{SoundData, SoundPool};
+/// # fn t(ctx: &ggez::Context) -> ggez::GameResult {
+/// let data = SoundData::new(ctx, "/pew.ogg")?;
+/// let mut shots = SoundPool::new(ctx, data, 8)?;
+/// shots.set_pitch_variation(0.1);
+/// let voice = shots.play()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct SoundPool {
+    data: SourceData,
+    bus: Bus,
+    max_voices: usize,
+    stealing: VoiceStealing,
+    volume: f32,
+    pitch: f32,
+    volume_variation: f32,
+    pitch_variation: f32,
+    // Oldest first.
+    voices: Vec<Voice>,
+    finished: Arc<FinishedQueue>,
+    rng: Rng,
+}
+
+impl SoundPool {
+    /// Creates a new `SoundPool` playing the given data, at most `max_voices`
+    /// at a time, on the audio context's `effects` bus.
+    pub fn new(
+        audio: &impl Has<AudioContext>,
+        data: SoundData,
+        max_voices: usize,
+    ) -> GameResult<Self> {
+        if !data.can_play() {
+            return Err(GameError::AudioError(
+                "Could not decode the given audio data".to_string(),
+            ));
+        }
+        Ok(SoundPool::from_source_data(
+            audio.retrieve(),
+            SourceData::Encoded(EncodedData::Memory(data)),
+            max_voices,
+        ))
+    }
+
+    /// Creates a new `SoundPool` playing the given `DecodedSoundData`, which
+    /// doesn't need decoding each time a voice plays; this is the quickest
+    /// way to play short, frequent sound effects.
+    pub fn from_decoded(
+        audio: &impl Has<AudioContext>,
+        data: DecodedSoundData,
+        max_voices: usize,
+    ) -> Self {
+        SoundPool::from_source_data(audio.retrieve(), SourceData::Samples(data.0), max_voices)
+    }
+
+    fn from_source_data(audio: &AudioContext, data: SourceData, max_voices: usize) -> Self {
+        SoundPool {
+            data,
+            bus: audio.effects().clone(),
+            max_voices: max_voices.max(1),
+            stealing: VoiceStealing::default(),
+            volume: 1.0,
+            pitch: 1.0,
+            volume_variation: 0.0,
+            pitch_variation: 0.0,
+            voices: Vec::new(),
+            finished: audio.finished.clone(),
+            rng: Rng::new(),
+        }
+    }
+
+    /// Plays a new voice, stealing one if too many are playing already.
+    ///
+    /// Returns `None` if the voice wasn't played, because the pool is full
+    /// and set to [`VoiceStealing::Never`].
+    pub fn play(&mut self) -> GameResult<Option<VoiceHandle>> {
+        self.start_voice(None)
+    }
+
+    /// Plays a new voice like [`play()`](#method.play), but starting on
+    /// exactly the sample at which the audio clock reaches `time`; see
+    /// [`SoundSource::play_at()`](trait.SoundSource.html#method.play_at).
+    ///
+    /// The voice counts as playing, and may be stolen, from now on.
+    pub fn play_at(&mut self, time: time::Duration) -> GameResult<Option<VoiceHandle>> {
+        self.start_voice(Some(time))
+    }
+
+    fn start_voice(&mut self, start: Option<time::Duration>) -> GameResult<Option<VoiceHandle>> {
+        self.prune();
+        if self.voices.len() >= self.max_voices {
+            let stolen = match self.stealing {
+                VoiceStealing::Oldest => 0,
+                VoiceStealing::Quietest => {
+                    self.voices
+                        .iter()
+                        .enumerate()
+                        .fold(0, |quietest, (i, voice)| {
+                            if voice.gain < self.voices[quietest].gain {
+                                i
+                            } else {
+                                quietest
+                            }
+                        })
+                }
+                VoiceStealing::Never => return Ok(None),
+            };
+            self.voices.remove(stolen).stop();
+        }
+
+        let gain = (self.volume_variation * self.rng.next_signed() + 1.0).max(0.0);
+        let pitch = (self.pitch_variation * self.rng.next_signed() + 1.0).max(0.01);
+        let fader = Arc::new(Fader::new(self.volume * gain));
+        let playback = Playback::new(
+            &self.data,
+            false,
+            None,
+            time::Duration::ZERO,
+            Arc::new(PlaybackPosition::default()),
+        )?;
+        let id = SoundId::next();
+        let sound = Notify::new(playback, id, self.finished.clone());
+        let sound = rodio::Source::speed(sound, self.pitch * pitch);
+        let sound = Faded::new(sound, fader.clone());
+        let sink = self.bus.new_sink();
+        match start {
+            Some(time) => sink.append_at(sound, time),
+            None => sink.append(sound),
+        }
+
+        self.voices.push(Voice {
+            id,
+            sink,
+            fader,
+            gain,
+        });
+        Ok(Some(VoiceHandle(id)))
+    }
+
+    /// Forgets about voices which have finished.
+    fn prune(&mut self) {
+        self.voices.retain(|voice| !voice.sink.empty());
+    }
+
+    /// Stops the given voice, if it's still playing.
+    pub fn stop(&mut self, voice: VoiceHandle) {
+        if let Some(i) = self.voices.iter().position(|v| v.id == voice.0) {
+            self.voices.remove(i).stop();
+        }
+    }
+
+    /// Stops every voice.
+    pub fn stop_all(&mut self) {
+        self.voices.drain(..).for_each(Voice::stop);
+    }
+
+    /// Indicates if the given voice is still playing.
+    pub fn is_playing(&self, voice: VoiceHandle) -> bool {
+        self.voices
+            .iter()
+            .any(|v| v.id == voice.0 && !v.sink.empty())
+    }
+
+    /// Returns how many voices are playing.
+    pub fn playing(&self) -> usize {
+        self.voices.iter().filter(|v| !v.sink.empty()).count()
+    }
+
+    /// Gets the most voices that may play at once.
+    pub fn max_voices(&self) -> usize {
+        self.max_voices
+    }
+
+    /// Sets the most voices that may play at once, stopping the oldest ones
+    /// if more than that are playing.
+    pub fn set_max_voices(&mut self, max_voices: usize) {
+        self.prune();
+        self.max_voices = max_voices.max(1);
+        let excess = self.voices.len().saturating_sub(self.max_voices);
+        self.voices.drain(..excess).for_each(Voice::stop);
+    }
+
+    /// Gets which voice is stolen to make room for a new one.
+    pub fn stealing(&self) -> VoiceStealing {
+        self.stealing
+    }
+
+    /// Sets which voice is stolen to make room for a new one.
+    pub fn set_stealing(&mut self, stealing: VoiceStealing) {
+        self.stealing = stealing;
+    }
+
+    /// Gets the volume voices play at, before any random variation.
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// Sets the volume voices play at, before any random variation.
+    /// This applies to voices already playing as well.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+        for voice in &self.voices {
+            voice.fader.set_volume(volume * voice.gain);
+        }
+    }
+
+    /// Gets the pitch ratio voices play at, before any random variation.
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    /// Sets the pitch ratio voices play at, before any random variation,
+    /// by adjusting the playback speed.  Takes effect from the next voice.
+    pub fn set_pitch(&mut self, ratio: f32) {
+        self.pitch = ratio;
+    }
+
+    /// Sets how much the volume of each voice varies at random: `0.2` plays
+    /// each one anywhere between 80% and 120% of the pool's volume.
+    pub fn set_volume_variation(&mut self, variation: f32) {
+        self.volume_variation = variation.abs();
+    }
+
+    /// Sets how much the pitch of each voice varies at random: `0.1` plays
+    /// each one anywhere between 90% and 110% of the pool's pitch.
+    pub fn set_pitch_variation(&mut self, variation: f32) {
+        self.pitch_variation = variation.abs();
+    }
+
+    /// Gets the bus voices are played on.
+    pub fn bus(&self) -> &Bus {
+        &self.bus
+    }
+
+    /// Sets the bus voices are played on, from the next voice on.
+    pub fn set_bus(&mut self, bus: &Bus) {
+        self.bus = bus.clone();
+    }
+}
+
+impl fmt::Debug for SoundPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<Audio SoundPool: {self:p}>")
+    }
+}
+
+/// A small xorshift generator for varying voices; it needn't be any good,
+/// just different every time.
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        // `RandomState` is seeded randomly, which saves depending on `rand`.
+        let seed = RandomState::new().build_hasher().finish();
+        Rng(seed | 1)
+    }
+
+    /// Returns a number between `-1.0` and `1.0`.
+    fn next_signed(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::data::SamplesData;
+    use super::*;
+    use crate::audio::manual_audio_for_tests;
+    use crate::filesystem::dummy_fs_for_tests;
+
+    #[test]
+    fn steals_voices() {
+        let fs = dummy_fs_for_tests();
+        let audio = manual_audio_for_tests(1, 1000);
+        let data = SoundData::new(&fs, "/pew.ogg").unwrap();
+        let mut pool = SoundPool::new(&audio, data, 2).unwrap();
+
+        let first = pool.play().unwrap().unwrap();
+        let second = pool.play().unwrap().unwrap();
+        let third = pool.play().unwrap().unwrap();
+        assert!(!pool.is_playing(first));
+        assert!(pool.is_playing(second) && pool.is_playing(third));
+        assert_eq!(pool.playing(), 2);
+
+        pool.stop(third);
+        assert!(!pool.is_playing(third));
+        pool.set_stealing(VoiceStealing::Never);
+        assert!(pool.play().unwrap().is_some());
+        assert!(pool.play().unwrap().is_none());
+
+        pool.stop_all();
+        assert_eq!(pool.playing(), 0);
+    }
+
+    #[test]
+    fn stopped_voices_fade_out() {
+        let audio = manual_audio_for_tests(1, 1000);
+        let samples = SamplesData::new(1, 1000, Arc::from(vec![0.5; 50])).unwrap();
+        let mut pool = SoundPool::from_source_data(&audio, SourceData::Samples(samples), 2);
+        let voice = pool.play().unwrap().unwrap();
+        audio.render(time::Duration::from_millis(5)).unwrap();
+        pool.stop(voice);
+        assert!(!pool.is_playing(voice));
+
+        audio.start_capture();
+        audio.render(time::Duration::from_millis(10)).unwrap();
+        let captured = audio.stop_capture();
+        let samples = captured.samples();
+        assert!(samples[0] > 0.0);
+        assert!(samples.windows(2).all(|w| w[1] <= w[0]));
+        assert!(samples[5..].iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn random_range() {
+        let mut rng = Rng::new();
+        for _ in 0..1000 {
+            let x = rng.next_signed();
+            assert!((-1.0..=1.0).contains(&x));
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! The queue of sounds a source plays on its bus.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time;
+
+use super::bus::BoxedSource;
+use super::output::AudioClock;
+
+/// A sound waiting its turn in a `Sink`.
+struct Queued {
+    source: BoxedSource,
+    // The frame of the audio clock the sound starts on, if it is scheduled.
+    start: Option<u64>,
+}
+
+/// State shared between a `Sink` and the `SinkOutput` playing it.
+struct SinkState {
+    queue: Mutex<VecDeque<Queued>>,
+    // How many sounds are queued or playing.
+    sounds: AtomicUsize,
+    paused: AtomicBool,
+    stopped: AtomicBool,
+    detached: AtomicBool,
+}
+
+/// Plays sounds one after the other on a bus, much like a `rodio::Sink`.
+///
+/// Unlike that, a newly appended sound starts on the very next frame the
+/// bus mixes, rather than after a chunk of silence, and pausing takes effect
+/// right away.  Sounds can also be held back until an exact frame of the
+/// audio clock.  That keeps timing exact, which matters for capturing and
+/// scheduling sounds.
+pub(crate) struct Sink {
+    state: Arc<SinkState>,
+    channels: u16,
+    sample_rate: u32,
+    clock: Arc<AudioClock>,
+}
+
+impl Sink {
+    /// Creates a new sink along with its output, which must be played on
+    /// a bus mixing at the given format and going by the given clock.
+    pub fn new(channels: u16, sample_rate: u32, clock: Arc<AudioClock>) -> (Sink, SinkOutput) {
+        let state = Arc::new(SinkState {
+            queue: Mutex::new(VecDeque::new()),
+            sounds: AtomicUsize::new(0),
+            paused: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            detached: AtomicBool::new(false),
+        });
+        let output = SinkOutput {
+            state: state.clone(),
+            current: None,
+            channels,
+            sample_rate,
+            clock: clock.clone(),
+            channel: 0,
+            holding: false,
+        };
+        let sink = Sink {
+            state,
+            channels,
+            sample_rate,
+            clock,
+        };
+        (sink, output)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<Queued>> {
+        self.state
+            .queue
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn push<S>(&self, source: S, start: Option<u64>)
+    where
+        S: rodio::Source<Item = f32> + Send + 'static,
+    {
+        // Converting here means the output never has to deal with sounds
+        // changing format from one to the next, and that scheduled sounds
+        // start on exactly the right frame of the bus.
+        let source =
+            rodio::source::UniformSourceIterator::new(source, self.channels, self.sample_rate);
+        let _ = self.state.sounds.fetch_add(1, Ordering::AcqRel);
+        self.lock().push_back(Queued {
+            source: Box::new(source),
+            start,
+        });
+    }
+
+    /// Queues up a sound to play after everything already appended.
+    pub fn append<S>(&self, source: S)
+    where
+        S: rodio::Source<Item = f32> + Send + 'static,
+    {
+        self.push(source, None)
+    }
+
+    /// Queues up a sound to play once the audio clock reaches `time`, or
+    /// once everything already appended has played, whichever is later.
+    pub fn append_at<S>(&self, source: S, time: time::Duration)
+    where
+        S: rodio::Source<Item = f32> + Send + 'static,
+    {
+        self.push(source, Some(self.clock.frame_at(time)))
+    }
+
+    pub fn play(&self) {
+        self.state.paused.store(false, Ordering::Release);
+    }
+
+    pub fn pause(&self) {
+        self.state.paused.store(true, Ordering::Release);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.paused.load(Ordering::Acquire)
+    }
+
+    /// Indicates if every sound appended has finished playing.
+    pub fn empty(&self) -> bool {
+        self.state.sounds.load(Ordering::Acquire) == 0
+    }
+
+    /// Lets the sink carry on playing whatever it has once dropped,
+    /// rather than stopping.
+    pub fn detach(self) {
+        self.state.detached.store(true, Ordering::Release);
+    }
+}
+
+impl Drop for Sink {
+    fn drop(&mut self) {
+        if !self.state.detached.load(Ordering::Acquire) {
+            self.state.stopped.store(true, Ordering::Release);
+        }
+    }
+}
+
+/// The output of a `Sink`, as mixed into a bus.
+pub(crate) struct SinkOutput {
+    state: Arc<SinkState>,
+    current: Option<Queued>,
+    channels: u16,
+    sample_rate: u32,
+    clock: Arc<AudioClock>,
+    // Which channel of the current frame the next sample is for.
+    channel: u16,
+    // Whether the current frame is silence, because the sink is paused or
+    // the sound up next isn't due yet.
+    holding: bool,
+}
+
+impl SinkOutput {
+    /// Moves on to the next sound in the queue if there isn't one playing.
+    fn load(&mut self) {
+        // Only take the lock if there is something to take.
+        if self.current.is_none() && self.state.sounds.load(Ordering::Acquire) > 0 {
+            self.current = self
+                .state
+                .queue
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .pop_front();
+        }
+    }
+
+    /// Indicates if the current sound is scheduled to start later than now.
+    fn waiting(&self) -> bool {
+        match self.current.as_ref().and_then(|queued| queued.start) {
+            Some(start) => self.clock.frames() < start,
+            None => false,
+        }
+    }
+}
+
+impl Iterator for SinkOutput {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.state.stopped.load(Ordering::Acquire) {
+            return None;
+        }
+        // Holding back halfway through a frame would get the channels mixed up.
+        if self.channel == 0 {
+            self.load();
+            self.holding = self.state.paused.load(Ordering::Acquire) || self.waiting();
+        }
+
+        let sample = if self.holding {
+            0.0
+        } else {
+            loop {
+                self.load();
+                let Some(queued) = self.current.as_mut() else {
+                    // A detached sink with nothing left to play is done for good;
+                    // otherwise keep the sink around, silently, for later sounds.
+                    if self.state.detached.load(Ordering::Acquire) {
+                        return None;
+                    }
+                    break 0.0;
+                };
+                if let Some(sample) = queued.source.next() {
+                    queued.start = None;
+                    break sample;
+                }
+                self.current = None;
+                let _ = self.state.sounds.fetch_sub(1, Ordering::AcqRel);
+                // Sounds end on frame boundaries, since they're converted to our
+                // format, so the next one can still be held back if it isn't due.
+                self.load();
+                if self.waiting() {
+                    self.holding = true;
+                    break 0.0;
+                }
+            }
+        };
+
+        self.channel += 1;
+        if self.channel == self.channels {
+            self.channel = 0;
+        }
+        Some(sample)
+    }
+}
+
+impl rodio::Source for SinkOutput {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<time::Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    #[test]
+    fn plays_in_order_without_gaps() {
+        let clock = Arc::new(AudioClock::new(100));
+        let (sink, mut output) = Sink::new(1, 100, clock);
+        assert_eq!(output.next(), Some(0.0));
+        sink.append(SamplesBuffer::new(1, 100, vec![0.25f32]));
+        sink.append(SamplesBuffer::new(1, 100, vec![0.5f32]));
+        assert!(!sink.empty());
+        assert_eq!(output.next(), Some(0.25));
+        sink.pause();
+        assert_eq!(output.next(), Some(0.0));
+        sink.play();
+        assert_eq!(output.next(), Some(0.5));
+        assert_eq!(output.next(), Some(0.0));
+        assert!(sink.empty());
+
+        sink.append(SamplesBuffer::new(1, 100, vec![1.0f32]));
+        sink.detach();
+        assert_eq!(output.next(), Some(1.0));
+        // Detached with nothing left, so it's finished.
+        assert_eq!(output.next(), None);
+    }
+
+    #[test]
+    fn pausing_keeps_channels_in_place() {
+        let clock = Arc::new(AudioClock::new(100));
+        let (sink, mut output) = Sink::new(2, 100, clock);
+        sink.append(SamplesBuffer::new(2, 100, vec![-1.0f32, 1.0, -1.0, 1.0]));
+        assert_eq!(output.next(), Some(-1.0));
+        sink.pause();
+        // The frame already started is finished before pausing.
+        assert_eq!(output.next(), Some(1.0));
+        assert_eq!(output.next(), Some(0.0));
+        assert_eq!(output.next(), Some(0.0));
+        assert_eq!(output.next(), Some(0.0));
+        sink.play();
+        assert_eq!(output.next(), Some(0.0));
+        assert_eq!(output.next(), Some(-1.0));
+        assert_eq!(output.next(), Some(1.0));
+    }
+
+    #[test]
+    fn starts_on_schedule() {
+        let clock = Arc::new(AudioClock::new(100));
+        let (sink, mut output) = Sink::new(1, 100, clock.clone());
+        sink.append(SamplesBuffer::new(1, 100, vec![0.5f32]));
+        sink.append_at(
+            SamplesBuffer::new(1, 100, vec![1.0f32]),
+            time::Duration::from_millis(30),
+        );
+        let mut played = Vec::new();
+        for _ in 0..5 {
+            played.push(output.next().unwrap());
+            clock.advance();
+        }
+        assert_eq!(played, [0.5, 0.0, 0.0, 1.0, 0.0]);
+        assert!(sink.empty());
+    }
+}
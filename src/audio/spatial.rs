@@ -0,0 +1,313 @@
+//! The sink used by `SpatialSource`, which places sounds around the listener.
+
+use std::f32::consts::FRAC_PI_4;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time;
+
+use glam::Vec3;
+
+use super::listener::{Attenuation, Listener, ListenerState};
+use super::sink::Sink;
+use super::Bus;
+
+/// Lowest and highest pitch the doppler effect may shift a sound to,
+/// so that sources moving at silly speeds don't turn into silly noises.
+const DOPPLER_PITCH_RANGE: (f32, f32) = (0.25, 4.0);
+
+/// Everything about a `SpatialSource` that decides how it sounds to the listener.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Emitter {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    /// Ear positions overriding the listener, as set with `SpatialSource::set_ears()`.
+    pub ears: Option<(Vec3, Vec3)>,
+    pub attenuation: Attenuation,
+    pub doppler_factor: f32,
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Emitter {
+            position: Vec3::ZERO,
+            velocity: Vec3::ZERO,
+            ears: None,
+            attenuation: Attenuation::default(),
+            doppler_factor: 0.0,
+        }
+    }
+}
+
+impl Emitter {
+    /// Works out the volume of the left and right channel, and the
+    /// doppler pitch shift, for the emitter as heard by the listener.
+    fn render(&self, listener: &ListenerState) -> ([f32; 2], f32) {
+        let (center, right) = match self.ears {
+            Some((left, right)) => ((left + right) * 0.5, (right - left).normalize_or_zero()),
+            None => (
+                listener.position,
+                listener.forward.cross(listener.up).normalize_or_zero(),
+            ),
+        };
+        let offset = self.position - center;
+        let distance = offset.length();
+        let direction = offset.normalize_or_zero();
+
+        // Constant-power panning, so sounds don't dip in volume as they pass by.
+        let angle = (direction.dot(right).clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+        let gain = self.attenuation.gain(distance);
+        let gains = [angle.cos() * gain, angle.sin() * gain];
+
+        let pitch = if self.doppler_factor > 0.0 && direction != Vec3::ZERO {
+            let c = listener.speed_of_sound;
+            let limit = c * 0.9;
+            let towards =
+                (listener.velocity.dot(direction) * self.doppler_factor).clamp(-limit, limit);
+            let away = (self.velocity.dot(direction) * self.doppler_factor).clamp(-limit, limit);
+            ((c + towards) / (c + away)).clamp(DOPPLER_PITCH_RANGE.0, DOPPLER_PITCH_RANGE.1)
+        } else {
+            1.0
+        };
+        (gains, pitch)
+    }
+}
+
+fn lock(emitter: &Mutex<Emitter>) -> MutexGuard<'_, Emitter> {
+    emitter.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Like a `Sink`, but places everything played on it around the
+/// listener, as described by an `Emitter`.
+pub(crate) struct SpatialSink {
+    sink: Sink,
+    listener: Listener,
+    emitter: Arc<Mutex<Emitter>>,
+}
+
+impl SpatialSink {
+    pub fn new(bus: &Bus, listener: &Listener, emitter: Emitter) -> Self {
+        SpatialSink {
+            sink: bus.new_sink(),
+            listener: listener.clone(),
+            emitter: Arc::new(Mutex::new(emitter)),
+        }
+    }
+
+    pub fn emitter(&self) -> Emitter {
+        *lock(&self.emitter)
+    }
+
+    /// Changes the emitter, affecting everything playing right away.
+    pub fn update_emitter(&self, f: impl FnOnce(&mut Emitter)) {
+        f(&mut lock(&self.emitter))
+    }
+
+    pub fn append<S>(&self, source: S)
+    where
+        S: rodio::Source<Item = f32> + Send + 'static,
+    {
+        self.sink.append(Spatialized::new(
+            source,
+            self.listener.clone(),
+            self.emitter.clone(),
+        ));
+    }
+
+    pub fn append_at<S>(&self, source: S, time: time::Duration)
+    where
+        S: rodio::Source<Item = f32> + Send + 'static,
+    {
+        self.sink.append_at(
+            Spatialized::new(source, self.listener.clone(), self.emitter.clone()),
+            time,
+        );
+    }
+
+    pub fn play(&self) {
+        self.sink.play()
+    }
+
+    pub fn pause(&self) {
+        self.sink.pause()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.sink.is_paused()
+    }
+
+    pub fn detach(self) {
+        self.sink.detach()
+    }
+
+    pub fn empty(&self) -> bool {
+        self.sink.empty()
+    }
+}
+
+/// Mixes a source down to mono and places it around the listener in
+/// stereo, resampling it on the fly for the doppler effect.
+struct Spatialized<S> {
+    input: S,
+    listener: Listener,
+    emitter: Arc<Mutex<Emitter>>,
+    // The two input frames being interpolated between, and how far along.
+    prev: f32,
+    next: f32,
+    frac: f32,
+    pitch: f32,
+    gains: [f32; 2],
+    steps: [f32; 2],
+    // Frames until the gains and pitch are worked out again.
+    until_refresh: u32,
+    refresh_frames: u32,
+    started: bool,
+    right: Option<f32>,
+}
+
+impl<S> Spatialized<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    fn new(input: S, listener: Listener, emitter: Arc<Mutex<Emitter>>) -> Self {
+        let refresh_frames = (input.sample_rate() / 100).max(1);
+        Spatialized {
+            input,
+            listener,
+            emitter,
+            prev: 0.0,
+            next: 0.0,
+            frac: 1.0,
+            pitch: 1.0,
+            gains: [0.0; 2],
+            steps: [0.0; 2],
+            until_refresh: 0,
+            refresh_frames,
+            started: false,
+            right: None,
+        }
+    }
+
+    /// Works out where the gains should be by the next refresh, so they
+    /// can be ramped to smoothly rather than jumping.
+    fn refresh(&mut self) {
+        let listener = self.listener.get();
+        let (gains, pitch) = lock(&self.emitter).render(&listener);
+        if self.started {
+            for ((step, target), gain) in self.steps.iter_mut().zip(gains).zip(self.gains) {
+                *step = (target - gain) / self.refresh_frames as f32;
+            }
+        } else {
+            self.gains = gains;
+            self.started = true;
+        }
+        self.pitch = pitch;
+        self.until_refresh = self.refresh_frames;
+    }
+
+    fn next_mono(&mut self) -> Option<f32> {
+        let channels = self.input.channels().max(1);
+        let mut sum = 0.0;
+        for _ in 0..channels {
+            sum += self.input.next()?;
+        }
+        Some(sum / f32::from(channels))
+    }
+}
+
+impl<S> Iterator for Spatialized<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(right) = self.right.take() {
+            return Some(right);
+        }
+        if self.until_refresh == 0 {
+            self.refresh();
+        }
+        self.until_refresh -= 1;
+
+        while self.frac >= 1.0 {
+            self.frac -= 1.0;
+            self.prev = self.next;
+            self.next = self.next_mono()?;
+        }
+        let sample = self.prev + (self.next - self.prev) * self.frac;
+        self.frac += self.pitch;
+
+        for (gain, step) in self.gains.iter_mut().zip(self.steps) {
+            *gain += step;
+        }
+        self.right = Some(sample * self.gains[1]);
+        Some(sample * self.gains[0])
+    }
+}
+
+impl<S> rodio::Source for Spatialized<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<time::Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pans_and_attenuates() {
+        let listener = Listener::new().get();
+        let emitter = Emitter {
+            position: Vec3::new(2.0, 0.0, 0.0),
+            ..Emitter::default()
+        };
+        let ([left, right], pitch) = emitter.render(&listener);
+        // Hard right, at half volume two units away.
+        assert!(left.abs() < 1e-6);
+        assert!((right - 0.5).abs() < 1e-6);
+        assert_eq!(pitch, 1.0);
+
+        // Overriding the ears flips things around.
+        let emitter = Emitter {
+            ears: Some((Vec3::X, Vec3::NEG_X)),
+            ..emitter
+        };
+        let ([left, right], _) = emitter.render(&listener);
+        assert!((left - 0.5).abs() < 1e-6);
+        assert!(right.abs() < 1e-6);
+    }
+
+    #[test]
+    fn doppler() {
+        let listener = Listener::new().get();
+        let approaching = Emitter {
+            position: Vec3::new(10.0, 0.0, 0.0),
+            velocity: Vec3::new(-34.3, 0.0, 0.0),
+            doppler_factor: 1.0,
+            ..Emitter::default()
+        };
+        let (_, pitch) = approaching.render(&listener);
+        assert!((pitch - 343.0 / (343.0 - 34.3)).abs() < 1e-4);
+
+        let receding = Emitter {
+            velocity: Vec3::new(34.3, 0.0, 0.0),
+            ..approaching
+        };
+        assert!(receding.render(&listener).1 < 1.0);
+    }
+}
@@ -0,0 +1,633 @@
+//! Reading MOD, S3M and XM files into a `Module`.
+
+use super::module::{
+    Cell, Command, Envelope, Instrument, LoopKind, Module, ModuleFormat, Note, Pattern, Sample,
+    VolumeCommand,
+};
+use crate::error::{GameError, GameResult};
+
+/// The most channels a module can have.
+pub(crate) const MAX_CHANNELS: usize = 64;
+
+fn invalid(reason: &str) -> GameError {
+    GameError::AudioError(format!("Invalid tracker module: {reason}"))
+}
+
+fn bytes(data: &[u8], start: usize, len: usize) -> GameResult<&[u8]> {
+    data.get(start..start.saturating_add(len))
+        .ok_or_else(|| invalid("the file is cut short"))
+}
+
+/// Moves past `len` bytes from `pos`, where `len` comes from the file and
+/// may be anything at all.
+fn skip(pos: usize, len: usize) -> GameResult<usize> {
+    pos.checked_add(len)
+        .ok_or_else(|| invalid("the file is cut short"))
+}
+
+fn u8_at(data: &[u8], pos: usize) -> GameResult<u8> {
+    Ok(bytes(data, pos, 1)?[0])
+}
+
+fn u16_le(data: &[u8], pos: usize) -> GameResult<u16> {
+    let b = bytes(data, pos, 2)?;
+    Ok(u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u16_be(data: &[u8], pos: usize) -> GameResult<u16> {
+    let b = bytes(data, pos, 2)?;
+    Ok(u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn u32_le(data: &[u8], pos: usize) -> GameResult<u32> {
+    let b = bytes(data, pos, 4)?;
+    Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn text(data: &[u8], start: usize, len: usize) -> String {
+    let raw = data.get(start..start + len).unwrap_or_default();
+    let end = raw.iter().position(|&b| b == 0).unwrap_or(raw.len());
+    String::from_utf8_lossy(&raw[..end]).trim_end().to_owned()
+}
+
+/// Indicates if the data looks like a module of any format we can play.
+pub(crate) fn is_module(data: &[u8]) -> bool {
+    data.starts_with(b"Extended Module: ")
+        || data.get(44..48) == Some(b"SCRM")
+        || data.get(1080..1084).and_then(mod_channels).is_some()
+}
+
+impl Module {
+    /// Reads a module, working out its format from its contents.
+    pub fn parse(data: &[u8]) -> GameResult<Self> {
+        let module = if data.starts_with(b"Extended Module: ") {
+            parse_xm(data)?
+        } else if data.get(44..48) == Some(b"SCRM") {
+            parse_s3m(data)?
+        } else if let Some(channels) = data.get(1080..1084).and_then(mod_channels) {
+            parse_mod(data, channels)?
+        } else {
+            return Err(invalid("not a MOD, S3M or XM file"));
+        };
+        if module.channels == 0 || module.channels > MAX_CHANNELS {
+            return Err(invalid("unsupported number of channels"));
+        }
+        Ok(module)
+    }
+}
+
+/// Converts the effect and parameter of a MOD or XM cell.
+fn protracker_command(effect: u8, param: u8) -> Command {
+    let (x, y) = (param >> 4, param & 0x0F);
+    match effect {
+        0x0 if param != 0 => Command::Arpeggio(x, y),
+        0x1 => Command::PortaUp(param),
+        0x2 => Command::PortaDown(param),
+        0x3 => Command::TonePorta(param),
+        0x4 => Command::Vibrato(x, y),
+        0x5 => Command::TonePortaVolumeSlide(param),
+        0x6 => Command::VibratoVolumeSlide(param),
+        0x7 => Command::Tremolo(x, y),
+        0x8 => Command::SetPan(param),
+        0x9 => Command::SampleOffset(param),
+        0xA => Command::VolumeSlide(param),
+        0xB => Command::PositionJump(param),
+        0xC => Command::SetVolume(param),
+        // Given in decimal, oddly.
+        0xD => Command::PatternBreak(x * 10 + y),
+        0xE => match x {
+            0x1 => Command::FinePortaUp(y),
+            0x2 => Command::FinePortaDown(y),
+            0x6 => Command::PatternLoop(y),
+            0x9 => Command::Retrigger(y),
+            0xA => Command::FineVolumeUp(y),
+            0xB => Command::FineVolumeDown(y),
+            0xC => Command::NoteCut(y),
+            0xD => Command::NoteDelay(y),
+            0xE => Command::PatternDelay(y),
+            _ => Command::None,
+        },
+        0xF if param == 0 => Command::None,
+        0xF if param < 32 => Command::SetSpeed(param),
+        0xF => Command::SetTempo(param),
+        // The rest are only in XM files, numbered on from F as letters.
+        0x10 => Command::SetGlobalVolume(param),
+        0x11 => Command::GlobalVolumeSlide(param),
+        0x14 => Command::KeyOff(param),
+        0x19 => Command::PanSlide(param),
+        // Retriggering with a change of volume as well, which we leave out.
+        0x1B => Command::Retrigger(y),
+        0x21 => match x {
+            0x1 => Command::ExtraFinePortaUp(y),
+            0x2 => Command::ExtraFinePortaDown(y),
+            _ => Command::None,
+        },
+        _ => Command::None,
+    }
+}
+
+/// Works out the number of channels of a MOD file from the tag after its
+/// sample list, if it has one we know.
+fn mod_channels(tag: &[u8]) -> Option<usize> {
+    let digit = |b: u8| b.is_ascii_digit().then(|| usize::from(b - b'0'));
+    match tag {
+        b"M.K." | b"M!K!" | b"FLT4" | b"4CHN" => Some(4),
+        b"FLT8" | b"OKTA" | b"CD81" => Some(8),
+        [n, b'C', b'H', b'N'] => digit(*n),
+        [n, m, b'C', b'H'] | [n, m, b'C', b'N'] => Some(digit(*n)? * 10 + digit(*m)?),
+        _ => None,
+    }
+}
+
+fn parse_mod(data: &[u8], channels: usize) -> GameResult<Module> {
+    let mut samples = Vec::with_capacity(31);
+    for i in 0..31 {
+        let header = bytes(data, 20 + i * 30, 30)?;
+        let length = usize::from(u16_be(header, 22)?) * 2;
+        // A signed nibble, in eighths of a semitone.
+        let finetune = ((header[24] << 4) as i8) >> 4;
+        let loop_start = usize::from(u16_be(header, 26)?) * 2;
+        let loop_length = usize::from(u16_be(header, 28)?) * 2;
+        samples.push((
+            length,
+            finetune,
+            header[25].min(64),
+            loop_start,
+            loop_length,
+        ));
+    }
+
+    let song_length = usize::from(u8_at(data, 950)?.clamp(1, 128));
+    let restart = usize::from(u8_at(data, 951)?);
+    let order_table = bytes(data, 952, 128)?;
+    let orders: Vec<usize> = order_table[..song_length]
+        .iter()
+        .map(|&p| usize::from(p))
+        .collect();
+    // Patterns which aren't played still take up space, if they're in the table.
+    let pattern_count = order_table
+        .iter()
+        .map(|&p| usize::from(p) + 1)
+        .max()
+        .unwrap_or(1);
+
+    let mut pos = 1084;
+    let mut patterns = Vec::with_capacity(pattern_count);
+    for _ in 0..pattern_count {
+        let raw = bytes(data, pos, 64 * channels * 4)?;
+        pos += raw.len();
+        let cells = raw
+            .chunks_exact(4)
+            .map(|b| {
+                let period = u16::from(b[0] & 0x0F) << 8 | u16::from(b[1]);
+                let note = if period == 0 {
+                    Note::None
+                } else {
+                    // Period 428 is C-4, the note samples are tuned to.
+                    let semitones = 12.0 * (428.0 / f64::from(period)).log2();
+                    Note::On((48.0 + semitones.round()).clamp(0.0, 119.0) as u8)
+                };
+                Cell {
+                    note,
+                    instrument: (b[0] & 0xF0) | (b[2] >> 4),
+                    volume: VolumeCommand::None,
+                    command: protracker_command(b[2] & 0x0F, b[3]),
+                }
+            })
+            .collect();
+        patterns.push(Pattern { rows: 64, cells });
+    }
+
+    let mut instruments = Vec::with_capacity(31);
+    for (length, finetune, volume, loop_start, loop_length) in samples {
+        // Some files are missing the end of their last sample.
+        let raw = data.get(pos..).unwrap_or_default();
+        let raw = &raw[..length.min(raw.len())];
+        pos += length;
+        let mut sample = Sample {
+            data: raw.iter().map(|&b| f32::from(b as i8) / 128.0).collect(),
+            volume,
+            finetune: finetune.saturating_mul(16),
+            ..Default::default()
+        };
+        if loop_length > 2 {
+            sample.set_loop(LoopKind::Forward, loop_start, loop_start + loop_length);
+        }
+        instruments.push(Instrument::single(sample));
+    }
+
+    Ok(Module {
+        title: text(data, 0, 20),
+        format: ModuleFormat::Mod,
+        channels,
+        restart: if restart < orders.len() { restart } else { 0 },
+        orders,
+        patterns,
+        instruments,
+        speed: 6,
+        tempo: 125,
+        global_volume: 64,
+        linear_periods: false,
+        // Amiga style, left and right alternating in pairs, but not quite
+        // so far apart, which is hard going on headphones.
+        panning: (0..channels)
+            .map(|c| if matches!(c % 4, 0 | 3) { 64 } else { 192 })
+            .collect(),
+    })
+}
+
+/// Converts the command and parameter of an S3M cell.
+fn s3m_command(command: u8, param: u8) -> Command {
+    let (x, y) = (param >> 4, param & 0x0F);
+    match command {
+        1 if param != 0 => Command::SetSpeed(param),
+        2 => Command::PositionJump(param),
+        3 => Command::PatternBreak(x * 10 + y),
+        4 => Command::VolumeSlide(param),
+        5 => Command::PortaDown(param),
+        6 => Command::PortaUp(param),
+        7 => Command::TonePorta(param),
+        8 => Command::Vibrato(x, y),
+        10 => Command::Arpeggio(x, y),
+        11 => Command::VibratoVolumeSlide(param),
+        12 => Command::TonePortaVolumeSlide(param),
+        15 => Command::SampleOffset(param),
+        17 => Command::Retrigger(y),
+        18 => Command::Tremolo(x, y),
+        19 => match x {
+            0x8 => Command::SetPan(y * 17),
+            0xB => Command::PatternLoop(y),
+            0xC => Command::NoteCut(y),
+            0xD => Command::NoteDelay(y),
+            0xE => Command::PatternDelay(y),
+            _ => Command::None,
+        },
+        20 if param >= 32 => Command::SetTempo(param),
+        // Fine vibrato, which is close enough to a quarter of the depth.
+        21 => Command::Vibrato(x, (y / 4).max(1)),
+        22 => Command::SetGlobalVolume(param),
+        24 => Command::SetPan((u16::from(param.min(128)) * 255 / 128) as u8),
+        _ => Command::None,
+    }
+}
+
+fn parse_s3m(data: &[u8]) -> GameResult<Module> {
+    let order_count = usize::from(u16_le(data, 32)?);
+    let instrument_count = usize::from(u16_le(data, 34)?);
+    let pattern_count = usize::from(u16_le(data, 36)?);
+    let signed_samples = u16_le(data, 42)? == 1;
+    let stereo = u8_at(data, 51)? & 0x80 != 0;
+    let default_panning = u8_at(data, 53)? == 252;
+
+    // Only channels 0 to 15 are ordinary sample channels; the rest are off
+    // or for AdLib instruments, which we can't play.
+    let settings = bytes(data, 64, 32)?;
+    let mut channel_map = [None; 32];
+    let mut panning = Vec::new();
+    for (i, &setting) in settings.iter().enumerate() {
+        if setting < 16 {
+            channel_map[i] = Some(panning.len());
+            panning.push(match (stereo, setting < 8) {
+                (false, _) => 128,
+                (true, true) => 0x30,
+                (true, false) => 0xC0,
+            });
+        }
+    }
+    let channels = panning.len();
+
+    let orders: Vec<usize> = bytes(data, 96, order_count)?
+        .iter()
+        .take_while(|&&p| p != 255)
+        .filter(|&&p| p != 254)
+        .map(|&p| usize::from(p))
+        .collect();
+    let pointers = 96 + order_count;
+    let pointer = |i: usize| u16_le(data, pointers + i * 2).map(|p| usize::from(p) * 16);
+
+    if default_panning {
+        let pans = bytes(data, pointers + (instrument_count + pattern_count) * 2, 32)?;
+        for (i, &pan) in pans.iter().enumerate() {
+            if let (Some(channel), true) = (channel_map[i], pan & 0x20 != 0) {
+                panning[channel] = (pan & 0x0F) * 17;
+            }
+        }
+    }
+
+    let mut instruments = Vec::with_capacity(instrument_count);
+    for i in 0..instrument_count {
+        let header = bytes(data, pointer(i)?, 80)?;
+        let mut sample = Sample::default();
+        if header[0] == 1 {
+            let offset = (usize::from(header[13]) << 16 | usize::from(u16_le(header, 14)?)) * 16;
+            let length = u32_le(header, 16)? as usize;
+            let flags = header[31];
+            let wide = flags & 4 != 0;
+            let raw = bytes(
+                data,
+                offset,
+                length.saturating_mul(if wide { 2 } else { 1 }),
+            )?;
+            sample.data = if wide {
+                raw.chunks_exact(2)
+                    .map(|b| {
+                        let s = u16::from_le_bytes([b[0], b[1]]);
+                        let s = if signed_samples {
+                            s as i16
+                        } else {
+                            (s ^ 0x8000) as i16
+                        };
+                        f32::from(s) / 32768.0
+                    })
+                    .collect()
+            } else {
+                raw.iter()
+                    .map(|&b| {
+                        let s = if signed_samples {
+                            b as i8
+                        } else {
+                            (b ^ 0x80) as i8
+                        };
+                        f32::from(s) / 128.0
+                    })
+                    .collect()
+            };
+            sample.volume = header[28].min(64);
+            if flags & 1 != 0 {
+                let start = u32_le(header, 20)? as usize;
+                let end = u32_le(header, 24)? as usize;
+                sample.set_loop(LoopKind::Forward, start, end);
+            }
+            sample.set_c4_rate(u32_le(header, 32)?);
+        }
+        instruments.push(Instrument::single(sample));
+    }
+
+    let mut patterns = Vec::with_capacity(pattern_count);
+    for i in 0..pattern_count {
+        let mut pattern = Pattern::empty(64, channels);
+        let start = pointer(instrument_count + i)?;
+        if start > 0 {
+            let length = usize::from(u16_le(data, start)?);
+            let packed = bytes(data, start + 2, length.saturating_sub(2))?;
+            let mut pos = 0;
+            let mut row = 0;
+            let mut next = || {
+                let b = packed.get(pos).copied();
+                pos += 1;
+                b.ok_or_else(|| invalid("a pattern is cut short"))
+            };
+            while row < 64 {
+                let what = next()?;
+                if what == 0 {
+                    row += 1;
+                    continue;
+                }
+                let mut cell = Cell::default();
+                if what & 0x20 != 0 {
+                    cell.note = match next()? {
+                        255 => Note::None,
+                        254 => Note::Cut,
+                        n => Note::On((n >> 4) * 12 + (n & 0x0F).min(11)),
+                    };
+                    cell.instrument = next()?;
+                }
+                if what & 0x40 != 0 {
+                    cell.volume = VolumeCommand::Set(next()?.min(64));
+                }
+                if what & 0x80 != 0 {
+                    let command = next()?;
+                    cell.command = s3m_command(command, next()?);
+                }
+                if let Some(channel) = channel_map[usize::from(what & 0x1F)] {
+                    pattern.cells[row * channels + channel] = cell;
+                }
+            }
+        }
+        patterns.push(pattern);
+    }
+
+    Ok(Module {
+        title: text(data, 0, 28),
+        format: ModuleFormat::S3m,
+        channels,
+        orders,
+        restart: 0,
+        patterns,
+        instruments,
+        speed: u8_at(data, 49)?.max(1),
+        tempo: u8_at(data, 50)?.max(32),
+        global_volume: u8_at(data, 48)?.min(64),
+        linear_periods: false,
+        panning,
+    })
+}
+
+fn xm_volume(volume: u8) -> VolumeCommand {
+    let y = volume & 0x0F;
+    match volume {
+        0x10..=0x50 => VolumeCommand::Set(volume - 0x10),
+        0x60..=0x6F => VolumeCommand::SlideDown(y),
+        0x70..=0x7F => VolumeCommand::SlideUp(y),
+        0x80..=0x8F => VolumeCommand::FineDown(y),
+        0x90..=0x9F => VolumeCommand::FineUp(y),
+        0xA0..=0xAF => VolumeCommand::VibratoSpeed(y),
+        0xB0..=0xBF => VolumeCommand::Vibrato(y),
+        0xC0..=0xCF => VolumeCommand::Pan(y * 17),
+        0xD0..=0xDF => VolumeCommand::PanSlideLeft(y),
+        0xE0..=0xEF => VolumeCommand::PanSlideRight(y),
+        0xF0..=0xFF => VolumeCommand::TonePorta(y << 4),
+        _ => VolumeCommand::None,
+    }
+}
+
+fn xm_envelope(
+    header: &[u8],
+    points: usize,
+    count: u8,
+    flags: u8,
+    at: [u8; 3],
+) -> Option<Envelope> {
+    if flags & 1 == 0 || count == 0 {
+        return None;
+    }
+    let points = (0..usize::from(count.min(12)))
+        .map(|i| {
+            let x = u16_le(header, points + i * 4).unwrap_or(0);
+            let y = u16_le(header, points + i * 4 + 2).unwrap_or(0);
+            (x, y.min(64) as u8)
+        })
+        .collect();
+    let [sustain, loop_start, loop_end] = at.map(usize::from);
+    Some(Envelope {
+        points,
+        sustain: (flags & 2 != 0).then_some(sustain),
+        repeat: (flags & 4 != 0).then_some((loop_start, loop_end)),
+    })
+}
+
+fn parse_xm(data: &[u8]) -> GameResult<Module> {
+    let header_size = u32_le(data, 60)? as usize;
+    let song_length = usize::from(u16_le(data, 64)?).min(256);
+    let restart = usize::from(u16_le(data, 66)?);
+    let channels = usize::from(u16_le(data, 68)?);
+    let pattern_count = usize::from(u16_le(data, 70)?);
+    let instrument_count = usize::from(u16_le(data, 72)?);
+    let linear_periods = u16_le(data, 74)? & 1 != 0;
+    let orders: Vec<usize> = bytes(data, 80, song_length)?
+        .iter()
+        .map(|&p| usize::from(p))
+        .collect();
+    if channels == 0 || channels > MAX_CHANNELS {
+        return Err(invalid("unsupported number of channels"));
+    }
+
+    let mut pos = skip(60, header_size)?;
+    let mut patterns = Vec::with_capacity(pattern_count);
+    for _ in 0..pattern_count {
+        let length = u32_le(data, pos)? as usize;
+        let rows = usize::from(u16_le(data, pos + 5)?).clamp(1, 256);
+        let packed_size = usize::from(u16_le(data, pos + 7)?);
+        pos = skip(pos, length)?;
+        let packed = bytes(data, pos, packed_size)?;
+        pos = skip(pos, packed_size)?;
+
+        let mut pattern = Pattern::empty(rows, channels);
+        let mut i = 0;
+        let mut next = || {
+            let b = packed.get(i).copied();
+            i += 1;
+            b.ok_or_else(|| invalid("a pattern is cut short"))
+        };
+        if packed_size > 0 {
+            for cell in pattern.cells.iter_mut() {
+                let first = next()?;
+                let (flags, note) = if first & 0x80 != 0 {
+                    let note = if first & 1 != 0 { next()? } else { 0 };
+                    (first, note)
+                } else {
+                    (0x1E, first)
+                };
+                let mut field = |bit: u8| if flags & bit != 0 { next() } else { Ok(0) };
+                let instrument = field(0x02)?;
+                let volume = field(0x04)?;
+                let effect = field(0x08)?;
+                let param = field(0x10)?;
+                *cell = Cell {
+                    note: match note {
+                        1..=96 => Note::On(note - 1),
+                        97 => Note::Off,
+                        _ => Note::None,
+                    },
+                    instrument,
+                    volume: xm_volume(volume),
+                    command: protracker_command(effect, param),
+                };
+            }
+        }
+        patterns.push(pattern);
+    }
+
+    let mut instruments = Vec::with_capacity(instrument_count);
+    for _ in 0..instrument_count {
+        let size = u32_le(data, pos)? as usize;
+        let sample_count = usize::from(u16_le(data, pos + 27)?);
+        if sample_count == 0 {
+            pos = skip(pos, size)?;
+            instruments.push(Instrument::default());
+            continue;
+        }
+        let header = bytes(data, pos, size.max(241))?;
+        let sample_header_size = u32_le(header, 29)? as usize;
+        let mut instrument = Instrument {
+            keymap: header[33..129].to_vec(),
+            volume_envelope: xm_envelope(
+                header,
+                129,
+                header[225],
+                header[233],
+                [header[227], header[228], header[229]],
+            ),
+            panning_envelope: xm_envelope(
+                header,
+                177,
+                header[226],
+                header[234],
+                [header[230], header[231], header[232]],
+            ),
+            fadeout: u16_le(header, 239)?,
+            samples: Vec::with_capacity(sample_count),
+        };
+        pos = skip(pos, size)?;
+
+        let mut headers = Vec::with_capacity(sample_count);
+        for _ in 0..sample_count {
+            headers.push(bytes(data, pos, sample_header_size.max(18))?);
+            pos = skip(pos, sample_header_size)?;
+        }
+        for header in headers {
+            let length = u32_le(header, 0)? as usize;
+            let loop_start = u32_le(header, 4)? as usize;
+            let loop_length = u32_le(header, 8)? as usize;
+            let flags = header[14];
+            let wide = flags & 0x10 != 0;
+            let raw = bytes(data, pos, length)?;
+            pos = skip(pos, length)?;
+
+            // Stored as the difference from one sample to the next.
+            let (samples, scale) = if wide {
+                let mut last = 0i16;
+                let samples = raw
+                    .chunks_exact(2)
+                    .map(|b| {
+                        last = last.wrapping_add(i16::from_le_bytes([b[0], b[1]]));
+                        f32::from(last) / 32768.0
+                    })
+                    .collect();
+                (samples, 2)
+            } else {
+                let mut last = 0i8;
+                let samples = raw
+                    .iter()
+                    .map(|&b| {
+                        last = last.wrapping_add(b as i8);
+                        f32::from(last) / 128.0
+                    })
+                    .collect();
+                (samples, 1)
+            };
+            let mut sample = Sample {
+                data: samples,
+                volume: header[12].min(64),
+                finetune: header[13] as i8,
+                panning: Some(header[15]),
+                relative_note: header[16] as i8,
+                ..Default::default()
+            };
+            let kind = match flags & 3 {
+                1 => LoopKind::Forward,
+                2 => LoopKind::PingPong,
+                _ => LoopKind::None,
+            };
+            let loop_end = loop_start.saturating_add(loop_length);
+            sample.set_loop(kind, loop_start / scale, loop_end / scale);
+            instrument.samples.push(sample);
+        }
+        instruments.push(instrument);
+    }
+
+    Ok(Module {
+        title: text(data, 17, 20),
+        format: ModuleFormat::Xm,
+        channels,
+        restart: if restart < orders.len() { restart } else { 0 },
+        orders,
+        patterns,
+        instruments,
+        speed: u16_le(data, 76)?.clamp(1, 31) as u8,
+        tempo: u16_le(data, 78)?.clamp(32, 255) as u8,
+        global_volume: 64,
+        linear_periods,
+        panning: vec![128; channels],
+    })
+}
@@ -0,0 +1,383 @@
+//! Playing tracker music: MOD, S3M and XM modules.
+#![cfg(feature = "tracker")]
+
+use std::fmt;
+use std::path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time;
+
+use super::bus::BoxedSource;
+use super::data::SourceData;
+use super::{
+    AudioContext, Bus, Effect, EffectHandle, LoopPoints, SoundData, SoundId, SoundSource, Source,
+};
+use crate::context::Has;
+use crate::error::GameResult;
+
+mod load;
+mod module;
+mod player;
+
+use self::load::MAX_CHANNELS;
+use self::module::Module;
+use self::player::ModulePlayer;
+
+/// Where a module is in its song: which entry of its order list it is
+/// playing, the pattern that entry refers to, and the row of that pattern.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct PatternPosition {
+    /// The position in the order list.
+    pub order: usize,
+    /// The pattern being played.
+    pub pattern: usize,
+    /// The row of the pattern being played.
+    pub row: usize,
+}
+
+/// State shared between a `TrackerSource` and whatever is playing it.
+#[derive(Debug, Default)]
+pub(crate) struct TrackerState {
+    // A bit for each muted channel.
+    muted: AtomicU64,
+    repeat: AtomicBool,
+    // The order, pattern and row, packed together so they're read all at once.
+    position: AtomicU64,
+}
+
+impl TrackerState {
+    fn set_position(&self, position: PatternPosition) {
+        let packed =
+            (position.order as u64) << 32 | (position.pattern as u64) << 16 | position.row as u64;
+        self.position.store(packed, Ordering::Relaxed);
+    }
+
+    fn position(&self) -> PatternPosition {
+        let packed = self.position.load(Ordering::Relaxed);
+        PatternPosition {
+            order: (packed >> 32) as usize,
+            pattern: (packed >> 16 & 0xFFFF) as usize,
+            row: (packed & 0xFFFF) as usize,
+        }
+    }
+}
+
+/// A loaded module along with the state of the source playing it.
+#[derive(Clone)]
+pub(crate) struct ModuleData {
+    module: Arc<Module>,
+    state: Arc<TrackerState>,
+}
+
+impl ModuleData {
+    pub fn open(&self) -> BoxedSource {
+        Box::new(ModulePlayer::new(self.module.clone(), self.state.clone()))
+    }
+}
+
+impl fmt::Debug for ModuleData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "<Tracker module '{}': {} channels>",
+            self.module.title, self.module.channels
+        )
+    }
+}
+
+/// A source playing tracker music from a MOD, S3M or XM module.
+/// Will stop playing when dropped.
+///
+/// Modules are played note by note as they go, rather than decoded, so
+/// individual channels can be muted while they play, and the pattern and
+/// row being played can be read to sync gameplay to the music.
+///
+/// Repeating modules loop the way they are written to, carrying on from
+/// wherever the song jumps back to, rather than from the very start; loop
+/// points are ignored.
+///
+/// ```rust,no_run
+/// # use ggez::audio::{SoundSource, TrackerSource};
+/// # fn t(ctx: &ggez::Context) -> ggez::GameResult {
+/// let mut music = TrackerSource::new(ctx, "/song.xm")?;
+/// music.set_repeat(true);
+/// // Keep the drums out of the intro.
+/// music.set_channel_muted(3, true);
+/// music.play(ctx)?;
+/// // ...later, each frame
+/// if music.pattern_position().row % 4 == 0 {
+///     // On the beat.
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct TrackerSource {
+    source: Source,
+    data: ModuleData,
+}
+
+impl TrackerSource {
+    /// Loads the module at the given path.
+    pub fn new<P: AsRef<path::Path>>(ctxs: &impl Has<AudioContext>, path: P) -> GameResult<Self> {
+        let audio = ctxs.retrieve();
+        let data = SoundData::new(&audio.fs, path)?;
+        TrackerSource::from_data(audio, data)
+    }
+
+    /// Creates a new `TrackerSource` from module data that is already loaded.
+    pub fn from_data(audio: &impl Has<AudioContext>, data: SoundData) -> GameResult<Self> {
+        let data = ModuleData {
+            module: Arc::new(Module::parse(data.as_ref())?),
+            state: Arc::new(TrackerState::default()),
+        };
+        let source = Source::from_source_data(audio.retrieve(), SourceData::Module(data.clone()));
+        Ok(TrackerSource { source, data })
+    }
+
+    /// Indicates if the data looks like a module that a `TrackerSource`
+    /// can play.
+    pub fn can_play(data: &SoundData) -> bool {
+        load::is_module(data.as_ref())
+    }
+
+    /// Returns the title of the module.
+    pub fn title(&self) -> &str {
+        &self.data.module.title
+    }
+
+    /// Returns the number of channels in the module.
+    pub fn channels(&self) -> usize {
+        self.data.module.channels
+    }
+
+    /// Mutes or unmutes one of the module's channels, counted from 0.
+    /// Muted channels keep playing, they just can't be heard.
+    pub fn set_channel_muted(&mut self, channel: usize, muted: bool) {
+        if channel >= MAX_CHANNELS {
+            return;
+        }
+        let bit = 1 << channel;
+        if muted {
+            let _ = self.data.state.muted.fetch_or(bit, Ordering::Relaxed);
+        } else {
+            let _ = self.data.state.muted.fetch_and(!bit, Ordering::Relaxed);
+        }
+    }
+
+    /// Gets whether or not one of the module's channels is muted.
+    pub fn channel_muted(&self, channel: usize) -> bool {
+        channel < MAX_CHANNELS && self.data.state.muted.load(Ordering::Relaxed) & 1 << channel != 0
+    }
+
+    /// Returns the pattern and row most recently played.
+    ///
+    /// Like [`AudioContext::clock()`](struct.AudioContext.html#method.clock),
+    /// this runs ahead of what can be heard by the latency of the output device.
+    pub fn pattern_position(&self) -> PatternPosition {
+        self.data.state.position()
+    }
+}
+
+impl SoundSource for TrackerSource {
+    fn play_later(&self) -> GameResult {
+        self.source.play_later()
+    }
+
+    fn play_later_at(&self, time: time::Duration) -> GameResult {
+        self.source.play_later_at(time)
+    }
+
+    fn play_detached(&mut self, audio: &impl Has<AudioContext>) -> GameResult {
+        self.source.play_detached(audio)
+    }
+
+    fn set_repeat(&mut self, repeat: bool) {
+        // The module loops itself, so the source underneath never has to.
+        self.data.state.repeat.store(repeat, Ordering::Relaxed);
+    }
+    fn set_fade_in(&mut self, dur: time::Duration) {
+        self.source.set_fade_in(dur)
+    }
+    fn set_start(&mut self, dur: time::Duration) {
+        self.source.set_start(dur)
+    }
+    fn set_pitch(&mut self, ratio: f32) {
+        self.source.set_pitch(ratio)
+    }
+    fn repeat(&self) -> bool {
+        self.data.state.repeat.load(Ordering::Relaxed)
+    }
+    fn set_loop_points(&mut self, _points: Option<LoopPoints>) {}
+    fn loop_points(&self) -> Option<LoopPoints> {
+        None
+    }
+    fn pause(&self) {
+        self.source.pause()
+    }
+    fn resume(&self) {
+        self.source.resume()
+    }
+
+    fn stop(&mut self, audio: &impl Has<AudioContext>) -> GameResult {
+        self.source.stop(audio)
+    }
+
+    fn stopped(&self) -> bool {
+        self.source.stopped()
+    }
+
+    fn volume(&self) -> f32 {
+        self.source.volume()
+    }
+
+    fn set_volume(&mut self, value: f32) {
+        self.source.set_volume(value)
+    }
+
+    fn fade_to(&mut self, volume: f32, duration: time::Duration) {
+        self.source.fade_to(volume, duration)
+    }
+
+    fn fade_out(&mut self, duration: time::Duration) {
+        self.source.fade_out(duration)
+    }
+
+    fn paused(&self) -> bool {
+        self.source.paused()
+    }
+
+    fn playing(&self) -> bool {
+        self.source.playing()
+    }
+
+    fn elapsed(&self) -> time::Duration {
+        self.source.elapsed()
+    }
+
+    fn set_query_interval(&mut self, t: time::Duration) {
+        self.source.set_query_interval(t)
+    }
+
+    fn seek(&mut self, pos: time::Duration) -> GameResult {
+        self.source.seek(pos)
+    }
+
+    fn position(&self) -> time::Duration {
+        self.source.position()
+    }
+
+    fn id(&self) -> SoundId {
+        self.source.id()
+    }
+
+    fn bus(&self) -> &Bus {
+        self.source.bus()
+    }
+
+    fn set_bus(&mut self, bus: &Bus) {
+        self.source.set_bus(bus)
+    }
+
+    fn add_effect(&self, effect: Effect) -> EffectHandle {
+        self.source.add_effect(effect)
+    }
+
+    fn clear_effects(&self) {
+        self.source.clear_effects()
+    }
+}
+
+impl fmt::Debug for TrackerSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<Tracker source: {self:p}>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::module::ModuleFormat;
+    use super::*;
+
+    /// A 4 channel MOD playing one looping note on its first channel.
+    fn test_mod() -> Vec<u8> {
+        let mut data = vec![0; 1084];
+        data[..4].copy_from_slice(b"test");
+        // Sample 1: 64 bytes long, full volume, looping all of it.
+        let header = &mut data[20..50];
+        header[22..24].copy_from_slice(&32u16.to_be_bytes());
+        header[25] = 64;
+        header[28..30].copy_from_slice(&32u16.to_be_bytes());
+        data[950] = 1;
+        data[1080..1084].copy_from_slice(b"M.K.");
+        // A C-4 on the first row of the first channel, and nothing else.
+        let mut pattern = vec![0; 64 * 4 * 4];
+        pattern[..4].copy_from_slice(&[0x01, 0xAC, 0x10, 0x00]);
+        data.extend(pattern);
+        data.extend((0..64).map(|i| if i < 32 { 0x60 } else { 0xA0 }));
+        data
+    }
+
+    fn render(state: &Arc<TrackerState>) -> Vec<f32> {
+        let module = Arc::new(Module::parse(&test_mod()).unwrap());
+        ModulePlayer::new(module, state.clone()).collect()
+    }
+
+    #[test]
+    fn loads_mods() {
+        let module = Module::parse(&test_mod()).unwrap();
+        assert_eq!(module.title, "test");
+        assert_eq!(module.format, ModuleFormat::Mod);
+        assert_eq!(module.channels, 4);
+        assert_eq!(module.orders, [0]);
+        assert!(load::is_module(&test_mod()));
+
+        assert!(!load::is_module(b"RIFF"));
+        assert!(Module::parse(b"RIFF").is_err());
+        assert!(Module::parse(&test_mod()[..1200]).is_err());
+    }
+
+    #[test]
+    fn rejects_broken_xms() {
+        let xm = |header_size: u32, pattern_length: u32| {
+            let mut data = b"Extended Module: ".to_vec();
+            data.resize(336, 0);
+            data[60..64].copy_from_slice(&header_size.to_le_bytes());
+            data[64] = 1;
+            data[68] = 4;
+            data[70] = 1;
+            data.extend(pattern_length.to_le_bytes());
+            data.extend([0, 64, 0, 0, 0]);
+            data
+        };
+        assert!(Module::parse(&xm(276, 9)).is_ok());
+        assert!(Module::parse(&xm(u32::MAX, 9)).is_err());
+        assert!(Module::parse(&xm(276, u32::MAX)).is_err());
+    }
+
+    #[test]
+    fn plays_mods() {
+        let state = Arc::new(TrackerState::default());
+        let samples = render(&state);
+        // 64 rows of 6 ticks, at 125 BPM.
+        let frames = 64 * 6 * player::RENDER_RATE as usize / 50;
+        assert_eq!(samples.len(), frames * 2);
+        assert!(samples.iter().any(|&s| s > 0.1));
+        assert!(samples.iter().all(|s| s.abs() <= 1.0));
+        assert_eq!(
+            state.position(),
+            PatternPosition {
+                order: 0,
+                pattern: 0,
+                row: 63
+            }
+        );
+
+        state.muted.store(1, Ordering::Relaxed);
+        assert!(render(&state).iter().all(|&s| s == 0.0));
+
+        state.repeat.store(true, Ordering::Relaxed);
+        let module = Arc::new(Module::parse(&test_mod()).unwrap());
+        let player = ModulePlayer::new(module, state);
+        assert_eq!(player.take(frames * 4).count(), frames * 4);
+    }
+}
@@ -0,0 +1,248 @@
+//! Tracker modules, in one representation whichever format they came in.
+
+/// The format a module was loaded from, where it changes how it plays.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum ModuleFormat {
+    /// ProTracker and compatible MOD files.
+    Mod,
+    /// Scream Tracker 3 modules.
+    S3m,
+    /// FastTracker 2 extended modules.
+    Xm,
+}
+
+/// A song made of patterns, played in the order given by its order list,
+/// using the samples of its instruments.
+pub(crate) struct Module {
+    pub title: String,
+    pub format: ModuleFormat,
+    pub channels: usize,
+    // Indices into `patterns`.
+    pub orders: Vec<usize>,
+    // Where in the order list the song carries on from once it reaches the end.
+    pub restart: usize,
+    pub patterns: Vec<Pattern>,
+    pub instruments: Vec<Instrument>,
+    pub speed: u8,
+    pub tempo: u8,
+    pub global_volume: u8,
+    // Whether pitches slide in equal steps of frequency, rather than of
+    // the Amiga's period.
+    pub linear_periods: bool,
+    // The initial panning of each channel, from 0 for left to 255 for right.
+    pub panning: Vec<u8>,
+}
+
+/// The rows of notes played by every channel at once.
+pub(crate) struct Pattern {
+    pub rows: usize,
+    // Row by row, a cell for each channel.
+    pub cells: Vec<Cell>,
+}
+
+impl Pattern {
+    /// Creates a pattern with nothing in it.
+    pub fn empty(rows: usize, channels: usize) -> Self {
+        Pattern {
+            rows,
+            cells: vec![Cell::default(); rows * channels],
+        }
+    }
+}
+
+/// What a channel is told to do on a single row.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub(crate) struct Cell {
+    pub note: Note,
+    // Counted from 1, with 0 meaning no instrument.
+    pub instrument: u8,
+    pub volume: VolumeCommand,
+    pub command: Command,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Note {
+    #[default]
+    None,
+    /// Plays a note, counted in semitones from C-0.
+    On(u8),
+    /// Releases the note, letting its instrument fade out.
+    Off,
+    /// Silences the note at once.
+    Cut,
+}
+
+/// The extra commands that fit in the volume column of an XM pattern.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum VolumeCommand {
+    #[default]
+    None,
+    Set(u8),
+    SlideDown(u8),
+    SlideUp(u8),
+    FineDown(u8),
+    FineUp(u8),
+    VibratoSpeed(u8),
+    Vibrato(u8),
+    Pan(u8),
+    PanSlideLeft(u8),
+    PanSlideRight(u8),
+    TonePorta(u8),
+}
+
+/// The effect commands of every format, with parameters of 0 generally
+/// meaning to use the last parameter given to the same command.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Command {
+    #[default]
+    None,
+    Arpeggio(u8, u8),
+    PortaUp(u8),
+    PortaDown(u8),
+    TonePorta(u8),
+    Vibrato(u8, u8),
+    TonePortaVolumeSlide(u8),
+    VibratoVolumeSlide(u8),
+    Tremolo(u8, u8),
+    SetPan(u8),
+    SampleOffset(u8),
+    VolumeSlide(u8),
+    PositionJump(u8),
+    SetVolume(u8),
+    PatternBreak(u8),
+    FinePortaUp(u8),
+    FinePortaDown(u8),
+    ExtraFinePortaUp(u8),
+    ExtraFinePortaDown(u8),
+    FineVolumeUp(u8),
+    FineVolumeDown(u8),
+    PatternLoop(u8),
+    Retrigger(u8),
+    NoteCut(u8),
+    NoteDelay(u8),
+    PatternDelay(u8),
+    SetSpeed(u8),
+    SetTempo(u8),
+    SetGlobalVolume(u8),
+    GlobalVolumeSlide(u8),
+    KeyOff(u8),
+    PanSlide(u8),
+}
+
+/// Samples for playing notes with, along with how their volume and
+/// panning change over time.
+#[derive(Default)]
+pub(crate) struct Instrument {
+    pub samples: Vec<Sample>,
+    // The sample played for each note.
+    pub keymap: Vec<u8>,
+    pub volume_envelope: Option<Envelope>,
+    pub panning_envelope: Option<Envelope>,
+    // How quickly released notes fade out, out of 65536 per tick.
+    pub fadeout: u16,
+}
+
+impl Instrument {
+    /// Creates an instrument which plays the same sample for every note.
+    pub fn single(sample: Sample) -> Self {
+        Instrument {
+            samples: vec![sample],
+            ..Default::default()
+        }
+    }
+
+    /// Returns the index of the sample played for the given note, if there is one.
+    pub fn sample_index(&self, note: u8) -> Option<usize> {
+        let index = self.keymap.get(usize::from(note)).copied().unwrap_or(0);
+        let index = usize::from(index);
+        (index < self.samples.len()).then_some(index)
+    }
+}
+
+/// A curve an instrument's volume or panning follows while a note plays.
+pub(crate) struct Envelope {
+    // Ticks into the note, and the value there from 0 to 64.
+    pub points: Vec<(u16, u8)>,
+    // The point held for as long as the note isn't released.
+    pub sustain: Option<usize>,
+    // The points looped between.
+    pub repeat: Option<(usize, usize)>,
+}
+
+impl Envelope {
+    /// Returns the value of the envelope the given number of ticks in.
+    pub fn value(&self, tick: u16) -> f32 {
+        let next = self.points.iter().position(|&(x, _)| x > tick);
+        match next {
+            Some(0) => f32::from(self.points[0].1),
+            Some(next) => {
+                let (x0, y0) = self.points[next - 1];
+                let (x1, y1) = self.points[next];
+                let t = f32::from(tick - x0) / f32::from(x1 - x0);
+                f32::from(y0) + (f32::from(y1) - f32::from(y0)) * t
+            }
+            None => self.points.last().map_or(64.0, |&(_, y)| f32::from(y)),
+        }
+    }
+
+    /// Returns the tick after the given one, holding at the sustain point
+    /// while the note is held and going around the loop if there is one.
+    pub fn advance(&self, tick: u16, held: bool) -> u16 {
+        let at = |point: usize| self.points.get(point).map(|&(x, _)| x);
+        if held && self.sustain.and_then(at) == Some(tick) {
+            return tick;
+        }
+        let tick = tick.saturating_add(1);
+        match self.repeat {
+            Some((start, end)) if at(end).is_some_and(|end| tick >= end) => {
+                at(start).unwrap_or(tick)
+            }
+            _ => tick,
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum LoopKind {
+    #[default]
+    None,
+    Forward,
+    PingPong,
+}
+
+/// A recorded sound, played at different speeds to make different notes.
+#[derive(Default)]
+pub(crate) struct Sample {
+    pub data: Vec<f32>,
+    pub loop_kind: LoopKind,
+    pub loop_start: usize,
+    pub loop_end: usize,
+    pub volume: u8,
+    pub panning: Option<u8>,
+    // Semitones added to every note played, so that samples recorded at
+    // different rates play in tune.
+    pub relative_note: i8,
+    // Fine tuning, in 1/128ths of a semitone.
+    pub finetune: i8,
+}
+
+impl Sample {
+    /// Sets the loop, ignoring it if it is empty or outside the sample.
+    pub fn set_loop(&mut self, kind: LoopKind, start: usize, end: usize) {
+        let end = end.min(self.data.len());
+        if kind != LoopKind::None && start + 1 < end {
+            self.loop_kind = kind;
+            self.loop_start = start;
+            self.loop_end = end;
+        }
+    }
+
+    /// Tunes the sample so that C-4 plays at the given rate, as MOD and
+    /// S3M samples are tuned.
+    pub fn set_c4_rate(&mut self, rate: u32) {
+        let semitones = 12.0 * (f64::from(rate.max(1)) / 8363.0).log2();
+        let relative = semitones.floor();
+        self.relative_note = relative.clamp(-96.0, 96.0) as i8;
+        self.finetune = ((semitones - relative) * 128.0).round().min(127.0) as i8;
+    }
+}
@@ -0,0 +1,713 @@
+//! Playing a `Module`, one tick at a time.
+
+use std::f32::consts::PI;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time;
+
+use super::module::{Cell, Command, LoopKind, Module, ModuleFormat, Note, Sample, VolumeCommand};
+use super::{PatternPosition, TrackerState};
+
+/// The sample rate modules are played at.
+pub(crate) const RENDER_RATE: u32 = 48000;
+
+/// Amiga periods, in quarters as S3M counts them, are this over the frequency.
+const AMIGA_CLOCK: f32 = 14_317_456.0;
+
+/// Reads a command's parameter, or the one remembered from last time if
+/// it is 0.  ProTracker doesn't remember parameters for most commands,
+/// which `remembers` says.
+fn recall(memory: &mut u8, param: u8, remembers: bool) -> u8 {
+    if param != 0 || !remembers {
+        *memory = param;
+    }
+    *memory
+}
+
+/// The vibrato and tremolo waveform, from -255 to 255 over 64 steps.
+fn wave(position: u8) -> f32 {
+    (2.0 * PI * f32::from(position & 63) / 64.0).sin() * 255.0
+}
+
+/// Works out the period to play a note of a sample at.
+fn note_period(module: &Module, note: u8, sample: &Sample) -> f32 {
+    let note =
+        f32::from(note) + f32::from(sample.relative_note) + f32::from(sample.finetune) / 128.0;
+    if module.linear_periods {
+        7680.0 - note * 64.0
+    } else {
+        AMIGA_CLOCK / (8363.0 * ((note - 48.0) / 12.0).exp2())
+    }
+}
+
+/// Works out the frequency a period plays at, raised by some semitones.
+fn frequency(module: &Module, period: f32, semitones: u8) -> f32 {
+    let semitones = f32::from(semitones);
+    if module.linear_periods {
+        8363.0 * ((4608.0 - period + semitones * 64.0) / 768.0).exp2()
+    } else {
+        AMIGA_CLOCK / period * (semitones / 12.0).exp2()
+    }
+}
+
+#[derive(Default)]
+struct Channel {
+    cell: Cell,
+    instrument: Option<usize>,
+    // The instrument and sample playing.
+    sample: Option<(usize, usize)>,
+    note: u8,
+    playing: bool,
+    position: f64,
+    backwards: bool,
+    step: f64,
+    period: f32,
+    target_period: f32,
+    volume: i32,
+    pan: i32,
+    key_on: bool,
+    fadeout: i32,
+    volume_tick: u16,
+    panning_tick: u16,
+    left: f32,
+    right: f32,
+
+    // Changes to pitch and volume lasting only for the current tick.
+    arpeggio: u8,
+    vibrato_offset: f32,
+    tremolo_offset: i32,
+
+    // The last parameters given to each command.
+    porta_up: u8,
+    porta_down: u8,
+    fine_porta_up: u8,
+    fine_porta_down: u8,
+    extra_fine_porta_up: u8,
+    extra_fine_porta_down: u8,
+    tone_porta: u8,
+    vibrato_speed: u8,
+    vibrato_depth: u8,
+    vibrato_position: u8,
+    tremolo_speed: u8,
+    tremolo_depth: u8,
+    tremolo_position: u8,
+    volume_slide: u8,
+    fine_volume_up: u8,
+    fine_volume_down: u8,
+    sample_offset: u8,
+    retrigger: u8,
+    global_volume_slide: u8,
+    pan_slide: u8,
+    loop_row: usize,
+    loop_count: u8,
+}
+
+impl Channel {
+    /// Starts or changes the note, as the cell says.
+    fn trigger(&mut self, module: &Module, cell: &Cell) {
+        let porta = matches!(
+            cell.command,
+            Command::TonePorta(_) | Command::TonePortaVolumeSlide(_)
+        ) || matches!(cell.volume, VolumeCommand::TonePorta(_));
+        if cell.instrument > 0 && usize::from(cell.instrument) <= module.instruments.len() {
+            self.instrument = Some(usize::from(cell.instrument - 1));
+        }
+
+        match cell.note {
+            Note::On(note) => {
+                let Some(index) = self.instrument else {
+                    return;
+                };
+                let instrument = &module.instruments[index];
+                let Some(sample_index) = instrument.sample_index(note) else {
+                    self.playing = false;
+                    return;
+                };
+                let period = note_period(module, note, &instrument.samples[sample_index]);
+                if porta && self.playing {
+                    self.target_period = period;
+                } else {
+                    self.note = note;
+                    self.sample = Some((index, sample_index));
+                    self.period = period;
+                    self.target_period = period;
+                    self.playing = true;
+                    self.backwards = false;
+                    self.position = 0.0;
+                    if let Command::SampleOffset(param) = cell.command {
+                        let offset = recall(&mut self.sample_offset, param, true);
+                        self.position = f64::from(offset) * 256.0;
+                    }
+                    self.vibrato_position = 0;
+                    self.tremolo_position = 0;
+                }
+            }
+            Note::Off => self.key_off(module),
+            Note::Cut => self.volume = 0,
+            Note::None => {}
+        }
+
+        // An instrument resets the volume and panning, with or without a note.
+        if cell.instrument > 0 && cell.note != Note::Off {
+            let sample = self.instrument.and_then(|index| {
+                let instrument = &module.instruments[index];
+                instrument.samples.get(instrument.sample_index(self.note)?)
+            });
+            if let Some(sample) = sample {
+                self.volume = i32::from(sample.volume);
+                if let Some(pan) = sample.panning {
+                    self.pan = i32::from(pan);
+                }
+            }
+            self.key_on = true;
+            self.fadeout = 65536;
+            self.volume_tick = 0;
+            self.panning_tick = 0;
+        }
+    }
+
+    fn key_off(&mut self, module: &Module) {
+        self.key_on = false;
+        let envelope = self
+            .instrument
+            .and_then(|index| module.instruments[index].volume_envelope.as_ref());
+        // Without an envelope there's nothing to fade out with.
+        if envelope.is_none() {
+            self.volume = 0;
+        }
+    }
+
+    fn volume_slide(&mut self, s3m: bool) {
+        let (x, y) = (self.volume_slide >> 4, self.volume_slide & 0x0F);
+        if s3m && ((x == 0xF && y > 0) || (y == 0xF && x > 0)) {
+            // Fine slides, done on the first tick only.
+            return;
+        }
+        if x > 0 {
+            self.volume += i32::from(x);
+        } else {
+            self.volume -= i32::from(y);
+        }
+    }
+
+    fn tone_porta(&mut self) {
+        let speed = f32::from(self.tone_porta) * 4.0;
+        if self.period < self.target_period {
+            self.period = (self.period + speed).min(self.target_period);
+        } else {
+            self.period = (self.period - speed).max(self.target_period);
+        }
+    }
+
+    fn vibrato(&mut self) {
+        self.vibrato_offset = wave(self.vibrato_position) * f32::from(self.vibrato_depth) / 32.0;
+        self.vibrato_position = self.vibrato_position.wrapping_add(self.vibrato_speed) & 63;
+    }
+
+    fn tremolo(&mut self) {
+        self.tremolo_offset =
+            (wave(self.tremolo_position) * f32::from(self.tremolo_depth) / 64.0) as i32;
+        self.tremolo_position = self.tremolo_position.wrapping_add(self.tremolo_speed) & 63;
+    }
+
+    /// Works out the pitch and volume of the channel for this tick, and
+    /// moves its envelopes on.
+    fn update(&mut self, module: &Module, global_volume: i32) {
+        self.volume = self.volume.clamp(0, 64);
+        self.pan = self.pan.clamp(0, 255);
+        self.period = self.period.max(1.0);
+
+        let period = self.period + self.vibrato_offset;
+        self.step =
+            f64::from(frequency(module, period.max(1.0), self.arpeggio)) / f64::from(RENDER_RATE);
+
+        let mut volume = (self.volume + self.tremolo_offset).clamp(0, 64) as f32 / 64.0;
+        let mut pan = self.pan as f32;
+        if let Some(instrument) = self.instrument.map(|index| &module.instruments[index]) {
+            if let Some(envelope) = &instrument.volume_envelope {
+                volume *= envelope.value(self.volume_tick) / 64.0;
+                self.volume_tick = envelope.advance(self.volume_tick, self.key_on);
+                if !self.key_on {
+                    self.fadeout = (self.fadeout - i32::from(instrument.fadeout)).max(0);
+                }
+                volume *= self.fadeout as f32 / 65536.0;
+            }
+            if let Some(envelope) = &instrument.panning_envelope {
+                let swing = (envelope.value(self.panning_tick) - 32.0) / 32.0;
+                pan += swing * (128.0 - (pan - 128.0).abs());
+                self.panning_tick = envelope.advance(self.panning_tick, self.key_on);
+            }
+        }
+        volume *= global_volume as f32 / 64.0;
+        self.left = volume * (2.0 * (255.0 - pan) / 255.0).min(1.0);
+        self.right = volume * (2.0 * pan / 255.0).min(1.0);
+    }
+
+    /// Plays the next sample, interpolating between the recorded ones.
+    fn play(&mut self, sample: &Sample) -> f32 {
+        let index = self.position as usize;
+        let Some(&current) = sample.data.get(index) else {
+            self.playing = false;
+            return 0.0;
+        };
+        let next = if sample.loop_kind == LoopKind::Forward && index + 1 >= sample.loop_end {
+            sample.data[sample.loop_start]
+        } else {
+            sample.data.get(index + 1).copied().unwrap_or(0.0)
+        };
+        let fraction = (self.position - index as f64) as f32;
+        let value = current + (next - current) * fraction;
+
+        let (start, end) = (sample.loop_start as f64, sample.loop_end as f64);
+        if self.backwards {
+            self.position -= self.step;
+            if self.position < start {
+                self.position = (2.0 * start - self.position).min(end - 1.0);
+                self.backwards = false;
+            }
+            return value;
+        }
+        self.position += self.step;
+        match sample.loop_kind {
+            LoopKind::None => {}
+            LoopKind::Forward if self.position >= end => {
+                self.position = start + (self.position - end) % (end - start);
+            }
+            LoopKind::PingPong if self.position >= end => {
+                self.position = (2.0 * (end - 1.0) - self.position).max(start);
+                self.backwards = true;
+            }
+            LoopKind::Forward | LoopKind::PingPong => {}
+        }
+        value
+    }
+}
+
+/// Plays a `Module` as a stereo `rodio` source.
+pub(crate) struct ModulePlayer {
+    module: Arc<Module>,
+    state: Arc<TrackerState>,
+    channels: Vec<Channel>,
+    order: usize,
+    row: usize,
+    tick: u32,
+    speed: u32,
+    tempo: u32,
+    global_volume: i32,
+    pattern_delay: u32,
+    // Where to go once the row is over, if not on to the next.
+    jump_order: Option<usize>,
+    break_row: Option<usize>,
+    loop_row: Option<usize>,
+    // Which rows of which orders have been played, to tell when the song
+    // has come back around to somewhere it has already been.
+    visited: Vec<bool>,
+    frames_left: u32,
+    muted: u64,
+    gain: f32,
+    right: Option<f32>,
+    finished: bool,
+}
+
+impl ModulePlayer {
+    pub fn new(module: Arc<Module>, state: Arc<TrackerState>) -> Self {
+        let channels = module
+            .panning
+            .iter()
+            .map(|&pan| Channel {
+                pan: i32::from(pan),
+                fadeout: 65536,
+                ..Default::default()
+            })
+            .collect();
+        ModulePlayer {
+            channels,
+            order: 0,
+            row: 0,
+            tick: 0,
+            speed: u32::from(module.speed),
+            tempo: u32::from(module.tempo),
+            global_volume: i32::from(module.global_volume),
+            pattern_delay: 0,
+            jump_order: None,
+            break_row: None,
+            loop_row: None,
+            visited: vec![false; module.orders.len() * 256],
+            frames_left: 0,
+            muted: 0,
+            gain: 1.0 / (module.channels as f32).sqrt(),
+            right: None,
+            finished: false,
+            module,
+            state,
+        }
+    }
+
+    /// Finds the row to play next, starting the song over if it has come
+    /// to an end and is set to repeat.  Returns `false` if it is over.
+    fn find_row(&mut self) -> bool {
+        let repeat = self.state.repeat.load(Ordering::Relaxed);
+        let module = &*self.module;
+        // Every order could be skipped over, so give up after trying them all.
+        for _ in 0..=module.orders.len() {
+            if self.order >= module.orders.len() {
+                if !repeat {
+                    return false;
+                }
+                self.order = module.restart;
+                self.row = 0;
+                self.visited.fill(false);
+            }
+            match module
+                .orders
+                .get(self.order)
+                .and_then(|&p| module.patterns.get(p))
+            {
+                Some(pattern) if self.row < pattern.rows => {}
+                _ => {
+                    self.order += 1;
+                    self.row = 0;
+                    continue;
+                }
+            }
+            // Pattern loops come back to the same rows on purpose.
+            let looping = self.channels.iter().any(|channel| channel.loop_count > 0);
+            if self.visited[self.order * 256 + self.row] && !looping {
+                if !repeat {
+                    return false;
+                }
+                self.visited.fill(false);
+            }
+            self.visited[self.order * 256 + self.row] = true;
+            return true;
+        }
+        false
+    }
+
+    /// Plays the notes of a new row, and the commands that happen at its start.
+    fn start_row(&mut self) -> bool {
+        if !self.find_row() {
+            return false;
+        }
+        let module = self.module.clone();
+        let pattern_index = module.orders[self.order];
+        let pattern = &module.patterns[pattern_index];
+        self.state.set_position(PatternPosition {
+            order: self.order,
+            pattern: pattern_index,
+            row: self.row,
+        });
+
+        let remembers = module.format != ModuleFormat::Mod;
+        let s3m = module.format == ModuleFormat::S3m;
+        let cells = &pattern.cells[self.row * module.channels..][..module.channels];
+        for (channel, cell) in self.channels.iter_mut().zip(cells) {
+            channel.cell = *cell;
+            let delayed = matches!(cell.command, Command::NoteDelay(delay) if delay > 0);
+            if !delayed {
+                channel.trigger(&module, cell);
+            }
+
+            match cell.volume {
+                VolumeCommand::Set(volume) if !delayed => channel.volume = i32::from(volume),
+                VolumeCommand::FineDown(v) => channel.volume -= i32::from(v),
+                VolumeCommand::FineUp(v) => channel.volume += i32::from(v),
+                VolumeCommand::VibratoSpeed(speed) if speed > 0 => channel.vibrato_speed = speed,
+                VolumeCommand::Vibrato(depth) if depth > 0 => channel.vibrato_depth = depth,
+                VolumeCommand::Pan(pan) => channel.pan = i32::from(pan),
+                VolumeCommand::TonePorta(speed) if speed > 0 => channel.tone_porta = speed,
+                _ => {}
+            }
+
+            match cell.command {
+                Command::SetVolume(volume) => channel.volume = i32::from(volume.min(64)),
+                Command::SetPan(pan) => channel.pan = i32::from(pan),
+                Command::PositionJump(order) => {
+                    self.jump_order = Some(usize::from(order));
+                    self.break_row = self.break_row.or(Some(0));
+                }
+                Command::PatternBreak(row) => {
+                    self.break_row = Some(usize::from(row));
+                    self.jump_order = self.jump_order.or(Some(self.order + 1));
+                }
+                Command::SetSpeed(speed) if speed > 0 => self.speed = u32::from(speed),
+                Command::SetTempo(tempo) if tempo >= 32 => self.tempo = u32::from(tempo),
+                Command::SetGlobalVolume(volume) => {
+                    self.global_volume = i32::from(volume.min(64));
+                }
+                Command::FinePortaUp(param) => {
+                    let param = recall(&mut channel.fine_porta_up, param, remembers);
+                    channel.period -= f32::from(param) * 4.0;
+                }
+                Command::FinePortaDown(param) => {
+                    let param = recall(&mut channel.fine_porta_down, param, remembers);
+                    channel.period += f32::from(param) * 4.0;
+                }
+                Command::ExtraFinePortaUp(param) => {
+                    let param = recall(&mut channel.extra_fine_porta_up, param, remembers);
+                    channel.period -= f32::from(param);
+                }
+                Command::ExtraFinePortaDown(param) => {
+                    let param = recall(&mut channel.extra_fine_porta_down, param, remembers);
+                    channel.period += f32::from(param);
+                }
+                Command::FineVolumeUp(param) => {
+                    let param = recall(&mut channel.fine_volume_up, param, remembers);
+                    channel.volume += i32::from(param);
+                }
+                Command::FineVolumeDown(param) => {
+                    let param = recall(&mut channel.fine_volume_down, param, remembers);
+                    channel.volume -= i32::from(param);
+                }
+                Command::PatternLoop(0) => channel.loop_row = self.row,
+                Command::PatternLoop(count) => {
+                    if channel.loop_count == 0 {
+                        channel.loop_count = count;
+                        self.loop_row = Some(channel.loop_row);
+                    } else {
+                        channel.loop_count -= 1;
+                        if channel.loop_count > 0 {
+                            self.loop_row = Some(channel.loop_row);
+                        }
+                    }
+                }
+                Command::PatternDelay(delay) if self.pattern_delay == 0 => {
+                    self.pattern_delay = u32::from(delay);
+                }
+                Command::NoteCut(0) => channel.volume = 0,
+                Command::KeyOff(0) => channel.key_off(&module),
+                Command::TonePorta(param) => {
+                    let _ = recall(&mut channel.tone_porta, param, true);
+                }
+                Command::Vibrato(speed, depth) => {
+                    let _ = recall(&mut channel.vibrato_speed, speed, true);
+                    let _ = recall(&mut channel.vibrato_depth, depth, true);
+                }
+                Command::Tremolo(speed, depth) => {
+                    let _ = recall(&mut channel.tremolo_speed, speed, true);
+                    let _ = recall(&mut channel.tremolo_depth, depth, true);
+                }
+                Command::VolumeSlide(param)
+                | Command::TonePortaVolumeSlide(param)
+                | Command::VibratoVolumeSlide(param) => {
+                    let param = recall(&mut channel.volume_slide, param, remembers);
+                    let (x, y) = (param >> 4, param & 0x0F);
+                    if s3m && y == 0xF && x > 0 {
+                        channel.volume += i32::from(x);
+                    } else if s3m && x == 0xF && y > 0 {
+                        channel.volume -= i32::from(y);
+                    }
+                }
+                Command::PortaUp(param) => {
+                    let param = recall(&mut channel.porta_up, param, remembers);
+                    if s3m && param >= 0xE0 {
+                        let fine = if param >= 0xF0 { 4.0 } else { 1.0 };
+                        channel.period -= f32::from(param & 0x0F) * fine;
+                    }
+                }
+                Command::PortaDown(param) => {
+                    let param = recall(&mut channel.porta_down, param, remembers);
+                    if s3m && param >= 0xE0 {
+                        let fine = if param >= 0xF0 { 4.0 } else { 1.0 };
+                        channel.period += f32::from(param & 0x0F) * fine;
+                    }
+                }
+                Command::Retrigger(param) => {
+                    let _ = recall(&mut channel.retrigger, param, remembers);
+                }
+                Command::GlobalVolumeSlide(param) => {
+                    let _ = recall(&mut channel.global_volume_slide, param, true);
+                }
+                Command::PanSlide(param) => {
+                    let _ = recall(&mut channel.pan_slide, param, true);
+                }
+                _ => {}
+            }
+        }
+        true
+    }
+
+    /// Carries on the commands of the current row, on a tick after its first.
+    fn continue_row(&mut self, tick: u32) {
+        let module = self.module.clone();
+        let s3m = module.format == ModuleFormat::S3m;
+        let tick_u8 = tick.min(255) as u8;
+        for channel in &mut self.channels {
+            let cell = channel.cell;
+            match cell.command {
+                Command::Arpeggio(x, y) => channel.arpeggio = [0, x, y][(tick % 3) as usize],
+                Command::PortaUp(_) if !(s3m && channel.porta_up >= 0xE0) => {
+                    channel.period -= f32::from(channel.porta_up) * 4.0;
+                }
+                Command::PortaDown(_) if !(s3m && channel.porta_down >= 0xE0) => {
+                    channel.period += f32::from(channel.porta_down) * 4.0;
+                }
+                Command::TonePorta(_) => channel.tone_porta(),
+                Command::Vibrato(..) => channel.vibrato(),
+                Command::TonePortaVolumeSlide(_) => {
+                    channel.tone_porta();
+                    channel.volume_slide(s3m);
+                }
+                Command::VibratoVolumeSlide(_) => {
+                    channel.vibrato();
+                    channel.volume_slide(s3m);
+                }
+                Command::Tremolo(..) => channel.tremolo(),
+                Command::VolumeSlide(_) => channel.volume_slide(s3m),
+                Command::Retrigger(_)
+                    if channel.retrigger > 0
+                        && tick.is_multiple_of(u32::from(channel.retrigger)) =>
+                {
+                    channel.position = 0.0;
+                    channel.backwards = false;
+                    channel.playing = channel.sample.is_some();
+                }
+                Command::NoteCut(at) if at == tick_u8 => channel.volume = 0,
+                Command::NoteDelay(at) if at == tick_u8 => {
+                    channel.trigger(&module, &cell);
+                    if let VolumeCommand::Set(volume) = cell.volume {
+                        channel.volume = i32::from(volume);
+                    }
+                }
+                Command::KeyOff(at) if at == tick_u8 => channel.key_off(&module),
+                Command::GlobalVolumeSlide(_) => {
+                    let (x, y) = (
+                        channel.global_volume_slide >> 4,
+                        channel.global_volume_slide & 0x0F,
+                    );
+                    self.global_volume = if x > 0 {
+                        (self.global_volume + i32::from(x)).min(64)
+                    } else {
+                        (self.global_volume - i32::from(y)).max(0)
+                    };
+                }
+                Command::PanSlide(_) => {
+                    let (x, y) = (channel.pan_slide >> 4, channel.pan_slide & 0x0F);
+                    channel.pan += if x > 0 { i32::from(x) } else { -i32::from(y) };
+                }
+                _ => {}
+            }
+            match cell.volume {
+                VolumeCommand::SlideDown(v) => channel.volume -= i32::from(v),
+                VolumeCommand::SlideUp(v) => channel.volume += i32::from(v),
+                VolumeCommand::PanSlideLeft(v) => channel.pan -= i32::from(v),
+                VolumeCommand::PanSlideRight(v) => channel.pan += i32::from(v),
+                VolumeCommand::Vibrato(_) => channel.vibrato(),
+                VolumeCommand::TonePorta(_) => channel.tone_porta(),
+                _ => {}
+            }
+        }
+    }
+
+    /// Moves on to the next row, wherever the commands say that is.
+    fn next_row(&mut self) {
+        let jump_order = self.jump_order.take();
+        let break_row = self.break_row.take();
+        if let Some(row) = self.loop_row.take() {
+            self.row = row;
+        } else if let Some(order) = jump_order {
+            self.order = order;
+            self.row = break_row.unwrap_or(0);
+        } else {
+            self.row += 1;
+            let rows = self
+                .module
+                .orders
+                .get(self.order)
+                .and_then(|&p| self.module.patterns.get(p));
+            if rows.is_none_or(|pattern| self.row >= pattern.rows) {
+                self.order += 1;
+                self.row = 0;
+            }
+        }
+    }
+
+    /// Plays a tick, returning `false` once the song is over.
+    fn process_tick(&mut self) -> bool {
+        for channel in &mut self.channels {
+            channel.arpeggio = 0;
+            channel.vibrato_offset = 0.0;
+            channel.tremolo_offset = 0;
+        }
+        if self.tick == 0 {
+            if !self.start_row() {
+                return false;
+            }
+        } else if !self.tick.is_multiple_of(self.speed) {
+            // Rows repeated by a pattern delay carry on their commands, but
+            // don't play their notes again.
+            self.continue_row(self.tick % self.speed);
+        }
+
+        let module = self.module.clone();
+        for channel in &mut self.channels {
+            channel.update(&module, self.global_volume);
+        }
+        self.muted = self.state.muted.load(Ordering::Relaxed);
+
+        self.tick += 1;
+        if self.tick >= self.speed * (1 + self.pattern_delay) {
+            self.tick = 0;
+            self.pattern_delay = 0;
+            self.next_row();
+        }
+        self.frames_left = RENDER_RATE * 5 / (2 * self.tempo);
+        true
+    }
+
+    fn mix(&mut self) -> (f32, f32) {
+        let (mut left, mut right) = (0.0, 0.0);
+        for (i, channel) in self.channels.iter_mut().enumerate() {
+            let Some((instrument, sample)) = channel.sample else {
+                continue;
+            };
+            if !channel.playing {
+                continue;
+            }
+            let value = channel.play(&self.module.instruments[instrument].samples[sample]);
+            if self.muted & (1 << i) == 0 {
+                left += value * channel.left;
+                right += value * channel.right;
+            }
+        }
+        (left * self.gain, right * self.gain)
+    }
+}
+
+impl Iterator for ModulePlayer {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(right) = self.right.take() {
+            return Some(right);
+        }
+        while self.frames_left == 0 {
+            if self.finished || !self.process_tick() {
+                self.finished = true;
+                return None;
+            }
+        }
+        self.frames_left -= 1;
+        let (left, right) = self.mix();
+        self.right = Some(right);
+        Some(left)
+    }
+}
+
+impl rodio::Source for ModulePlayer {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        RENDER_RATE
+    }
+
+    fn total_duration(&self) -> Option<time::Duration> {
+        None
+    }
+}
@@ -337,6 +337,252 @@ pub enum Backend {
     BrowserWebGpu,
 }
 
+/// Where mixed audio is sent.
+/// The default is `Device`.
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    serde::Serialize,
+    serde::Deserialize,
+    PartialEq,
+    Eq,
+    smart_default::SmartDefault,
+)]
+#[serde(tag = "type")]
+pub enum AudioBackend {
+    /// Plays on the default output device.
+    #[default]
+    Device,
+    /// Mixes in software in real time, just like a device would, but without
+    /// playing the result anywhere.  For machines without sound hardware.
+    Null {
+        /// Number of channels to mix.
+        channels: u16,
+        /// Sample rate to mix at.
+        sample_rate: u32,
+    },
+    /// Mixes only when
+    /// [`AudioContext::render()`](../audio/struct.AudioContext.html#method.render)
+    /// is called, for tests which need to know exactly when a sound plays.
+    Manual {
+        /// Number of channels to mix.
+        channels: u16,
+        /// Sample rate to mix at.
+        sample_rate: u32,
+    },
+}
+
+/// The shape of the dead zone around the center of an analog stick, where
+/// it is read as not being pushed at all.
+/// The default is `Radial`.
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    serde::Serialize,
+    serde::Deserialize,
+    PartialEq,
+    Eq,
+    smart_default::SmartDefault,
+)]
+pub enum DeadzoneShape {
+    /// A circle around the center, so that how far the stick has to move
+    /// to count doesn't depend on which way it goes.
+    #[default]
+    Radial,
+    /// A cross through the center, with each axis ignoring small movements
+    /// on its own.  This makes it easy to push perfectly straight, but
+    /// snaps diagonals that are nearly straight onto the axis.
+    Axial,
+}
+
+/// How far an axis reads as being pushed, against how far it physically is,
+/// once it's past the dead zone.
+/// The default is `Linear`.
+#[derive(
+    Debug, Copy, Clone, serde::Serialize, serde::Deserialize, PartialEq, smart_default::SmartDefault,
+)]
+#[serde(tag = "type")]
+pub enum ResponseCurve {
+    /// Reads exactly as far as it's pushed.
+    #[default]
+    Linear,
+    /// Raises how far it's pushed to a power; above `1.0` this gives finer
+    /// control near the center, and below it, near the edge.
+    Power {
+        /// The power to raise to.
+        exponent: f32,
+    },
+}
+
+impl ResponseCurve {
+    /// Applies the curve to a value from `0.0` to `1.0`.
+    pub(crate) fn apply(self, value: f32) -> f32 {
+        match self {
+            ResponseCurve::Linear => value,
+            ResponseCurve::Power { exponent } => value.powf(exponent),
+        }
+    }
+}
+
+/// A builder structure containing how the raw values read from a gamepad
+/// axis are cleaned up, to hide stick drift and worn out or imprecise
+/// hardware.
+///
+/// Defaults:
+///
+/// ```rust
+/// # use ggez::conf::*;
+/// # fn main() { assert_eq!(
+/// AxisSettings {
+///     deadzone: 0.1,
+///     deadzone_shape: DeadzoneShape::Radial,
+///     anti_deadzone: 0.0,
+///     saturation: 1.0,
+///     curve: ResponseCurve::Linear,
+/// }
+/// # , AxisSettings::default()); }
+/// ```
+#[derive(
+    Debug, Copy, Clone, smart_default::SmartDefault, serde::Serialize, serde::Deserialize, PartialEq,
+)]
+pub struct AxisSettings {
+    /// How far from the center the axis has to be pushed before it counts
+    /// as being pushed at all, from `0.0` to `1.0`.
+    #[default = 0.1]
+    pub deadzone: f32,
+    /// The shape of the dead zone, for the two axes of an analog stick.
+    /// Other axes are on their own, so it makes no difference to them.
+    #[default(DeadzoneShape::Radial)]
+    pub deadzone_shape: DeadzoneShape,
+    /// The smallest value the axis reads once it is out of the dead zone,
+    /// for games which have a dead zone of their own to make up for.
+    #[default = 0.0]
+    pub anti_deadzone: f32,
+    /// How far the axis has to be pushed to read as pushed all the way,
+    /// for sticks which don't quite reach the edge.
+    #[default = 1.0]
+    pub saturation: f32,
+    // curve is serialized as a table, so it must be at the end of the struct for toml
+    /// How the value read changes with how far the axis is pushed.
+    #[default(ResponseCurve::Linear)]
+    pub curve: ResponseCurve,
+}
+
+impl AxisSettings {
+    /// Set how far the axis has to be pushed to count.
+    #[must_use]
+    pub fn deadzone(mut self, deadzone: f32) -> Self {
+        self.deadzone = deadzone;
+        self
+    }
+
+    /// Set the shape of the dead zone.
+    #[must_use]
+    pub fn deadzone_shape(mut self, shape: DeadzoneShape) -> Self {
+        self.deadzone_shape = shape;
+        self
+    }
+
+    /// Set the smallest value read outside of the dead zone.
+    #[must_use]
+    pub fn anti_deadzone(mut self, anti_deadzone: f32) -> Self {
+        self.anti_deadzone = anti_deadzone;
+        self
+    }
+
+    /// Set how far the axis has to be pushed to read as pushed all the way.
+    #[must_use]
+    pub fn saturation(mut self, saturation: f32) -> Self {
+        self.saturation = saturation;
+        self
+    }
+
+    /// Set the response curve.
+    #[must_use]
+    pub fn curve(mut self, curve: ResponseCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    /// Cleans up a raw axis value, from `-1.0` to `1.0`, on its own.
+    pub fn apply(&self, value: f32) -> f32 {
+        self.scale(value.abs()).copysign(value)
+    }
+
+    /// Cleans up how far from the center an axis, or a stick, is pushed.
+    pub(crate) fn scale(&self, distance: f32) -> f32 {
+        if distance <= self.deadzone {
+            return 0.0;
+        }
+        let range = self.saturation - self.deadzone;
+        let pushed = if range > 0.0 {
+            ((distance - self.deadzone) / range).min(1.0)
+        } else {
+            1.0
+        };
+        self.anti_deadzone + (1.0 - self.anti_deadzone) * self.curve.apply(pushed)
+    }
+}
+
+/// A builder structure containing the gamepad mappings to load and the
+/// settings gamepad axes start with, which can be changed for each gamepad
+/// and axis at runtime with
+/// [`GamepadContext`](../input/gamepad/struct.GamepadContext.html).
+///
+/// Defaults:
+///
+/// ```rust
+/// # use ggez::conf::*;
+/// # fn main() { assert_eq!(
+/// GamepadSetup {
+///     mappings: "".to_owned(),
+///     sticks: AxisSettings::default(),
+///     triggers: AxisSettings::default(),
+/// }
+/// # , GamepadSetup::default()); }
+/// ```
+#[derive(
+    Debug, Clone, smart_default::SmartDefault, serde::Serialize, serde::Deserialize, PartialEq,
+)]
+pub struct GamepadSetup {
+    /// A file path to SDL style gamepad mappings, such as a `gamecontrollerdb.txt`,
+    /// for gamepads that aren't recognized out of the box.
+    /// It takes a path rooted in the `resources` directory (see the [`filesystem`](../filesystem/index.html)
+    /// module for details), and an empty string loads no mappings besides the built in ones.
+    #[default(String::new())]
+    pub mappings: String,
+    /// Settings for the axes of the analog sticks.
+    pub sticks: AxisSettings,
+    /// Settings for every other axis, such as analog triggers, including
+    /// those reported as the `LeftTrigger2` and `RightTrigger2` buttons.
+    pub triggers: AxisSettings,
+}
+
+impl GamepadSetup {
+    /// Set the file to load gamepad mappings from.
+    #[must_use]
+    pub fn mappings(mut self, mappings: &str) -> Self {
+        self.mappings = mappings.to_owned();
+        self
+    }
+
+    /// Set the settings for the axes of the analog sticks.
+    #[must_use]
+    pub fn sticks(mut self, settings: AxisSettings) -> Self {
+        self.sticks = settings;
+        self
+    }
+
+    /// Set the settings for every other axis.
+    #[must_use]
+    pub fn triggers(mut self, settings: AxisSettings) -> Self {
+        self.triggers = settings;
+        self
+    }
+}
+
 /// The possible number of samples for multisample anti-aliasing.
 #[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
 pub enum NumSamples {
@@ -390,6 +636,8 @@ impl From<NumSamples> for u8 {
 ///     window_mode: WindowMode::default(),
 ///     window_setup: WindowSetup::default(),
 ///     backend: Backend::default(),
+///     audio_backend: AudioBackend::default(),
+///     gamepad_setup: GamepadSetup::default(),
 /// }
 /// # , Conf::default()); }
 /// ```
@@ -403,6 +651,12 @@ pub struct Conf {
     pub window_setup: WindowSetup,
     /// Graphics backend configuration
     pub backend: Backend,
+    /// Audio backend configuration
+    #[serde(default)]
+    pub audio_backend: AudioBackend,
+    /// Gamepad axis configuration
+    #[serde(default)]
+    pub gamepad_setup: GamepadSetup,
 }
 
 impl Conf {
@@ -441,6 +695,20 @@ impl Conf {
         self.backend = backend;
         self
     }
+
+    /// Sets the audio backend
+    #[must_use]
+    pub fn audio_backend(mut self, audio_backend: AudioBackend) -> Self {
+        self.audio_backend = audio_backend;
+        self
+    }
+
+    /// Sets the gamepad axis configuration
+    #[must_use]
+    pub fn gamepad_setup(mut self, gamepad_setup: GamepadSetup) -> Self {
+        self.gamepad_setup = gamepad_setup;
+        self
+    }
 }
 
 #[cfg(test)]
@@ -49,9 +49,13 @@ pub struct Context {
     pub keyboard: input::keyboard::KeyboardContext,
     /// Mouse input context.
     pub mouse: input::mouse::MouseContext,
+    /// Touch input context.
+    pub touch: input::touch::TouchContext,
     /// Gamepad input context.
     #[cfg(feature = "gamepad")]
     pub gamepad: input::gamepad::GamepadContext,
+    /// Input action context.
+    pub actions: input::action::ActionContext,
 
     /// The Conf object the Context was created with.
     /// It's here just so that we can see the original settings,
@@ -173,9 +177,11 @@ impl Context {
         fs: Filesystem,
     ) -> GameResult<(Context, winit::event_loop::EventLoop<()>)> {
         #[cfg(feature = "audio")]
-        let audio_context = audio::AudioContext::new(&fs)?;
+        let audio_context = audio::AudioContext::with_backend(&fs, conf.audio_backend)?;
         let events_loop = winit::event_loop::EventLoop::new();
         let timer_context = timer::TimeContext::new();
+        #[cfg(feature = "gamepad")]
+        let gamepad_context = input::gamepad::GamepadContext::new(&conf.gamepad_setup, &fs)?;
         let graphics_context =
             graphics::context::GraphicsContext::new(game_id, &events_loop, &conf, &fs)?;
 
@@ -190,8 +196,10 @@ impl Context {
             audio: audio_context,
             keyboard: input::keyboard::KeyboardContext::new(),
             mouse: input::mouse::MouseContext::new(),
+            touch: input::touch::TouchContext::new(),
             #[cfg(feature = "gamepad")]
-            gamepad: input::gamepad::GamepadContext::new()?,
+            gamepad: gamepad_context,
+            actions: input::action::ActionContext::new(),
         };
 
         Ok((ctx, events_loop))
@@ -250,6 +258,20 @@ impl ContextBuilder {
         self
     }
 
+    /// Sets the audio backend.
+    #[must_use]
+    pub fn audio_backend(mut self, backend: conf::AudioBackend) -> Self {
+        self.conf.audio_backend = backend;
+        self
+    }
+
+    /// Sets the gamepad axis configuration.
+    #[must_use]
+    pub fn gamepad_setup(mut self, setup: conf::GamepadSetup) -> Self {
+        self.conf.gamepad_setup = setup;
+        self
+    }
+
     /// Sets all the config options, overriding any previous
     /// ones from [`window_setup()`](#method.window_setup),
     /// [`window_mode()`](#method.window_mode), and
@@ -29,13 +29,16 @@ pub mod winit_event {
         TouchPhase, WindowEvent,
     };
 }
+#[cfg(feature = "audio")]
+pub use crate::audio::SoundId;
 #[cfg(feature = "gamepad")]
 pub use crate::input::gamepad::GamepadId;
 use crate::input::keyboard::{KeyCode, KeyInput, KeyMods};
+pub use crate::input::touch::Gesture;
 use crate::GameError;
 
 use self::winit_event::{
-    ElementState, Event, KeyboardInput, MouseScrollDelta, TouchPhase, WindowEvent,
+    DeviceEvent, ElementState, Event, KeyboardInput, MouseScrollDelta, TouchPhase, WindowEvent,
 };
 /// `winit` event loop.
 pub use winit::event_loop::{ControlFlow, EventLoop};
@@ -56,6 +59,8 @@ pub enum ErrorOrigin {
     MouseButtonUpEvent,
     /// error originated in `mouse_motion_event()`
     MouseMotionEvent,
+    /// error originated in `raw_mouse_motion_event()`
+    RawMouseMotionEvent,
     /// error originated in `mouse_enter_or_leave()`
     MouseEnterOrLeave,
     /// error originated in `mouse_wheel_event()`
@@ -68,12 +73,20 @@ pub enum ErrorOrigin {
     TextInputEvent,
     /// error originated in `touch_event()`
     TouchEvent,
+    /// error originated in `gesture_event()`
+    GestureEvent,
     /// error originated in `gamepad_button_down_event()`
     GamepadButtonDownEvent,
     /// error originated in `gamepad_button_up_event()`
     GamepadButtonUpEvent,
     /// error originated in `gamepad_axis_event()`
     GamepadAxisEvent,
+    /// error originated in `gamepad_connected()`
+    GamepadConnected,
+    /// error originated in `gamepad_disconnected()`
+    GamepadDisconnected,
+    /// error originated in `sound_finished_event()`
+    SoundFinishedEvent,
     /// error originated in `focus_event()`
     FocusEvent,
     /// error originated in `quit_event()`
@@ -144,6 +157,16 @@ where
         Ok(())
     }
 
+    /// The mouse was moved, as reported by the device itself; it provides how far it
+    /// moved in x and y, in device units.  Unlike
+    /// [`mouse_motion_event()`](#method.mouse_motion_event), this isn't stopped by the
+    /// edges of the screen or affected by pointer acceleration, which makes it the one to
+    /// use for controlling a camera, particularly with
+    /// [`mouse::set_relative_mode()`](../input/mouse/fn.set_relative_mode.html).
+    fn raw_mouse_motion_event(&mut self, _ctx: &mut Context, _dx: f32, _dy: f32) -> Result<(), E> {
+        Ok(())
+    }
+
     /// mouse entered or left window area
     fn mouse_enter_or_leave(&mut self, _ctx: &mut Context, _entered: bool) -> Result<(), E> {
         Ok(())
@@ -185,14 +208,21 @@ where
 
     /// An event from a touchscreen has been triggered; it provides the x and y location
     /// inside the window as well as the state of the tap (such as Started, Moved, Ended, etc)
-    /// By default, touch events will trigger mouse behavior
+    /// and the `id` of the touch, which stays the same for as long as the finger is down.
+    /// By default, the primary touch (see [`TouchContext`](../input/touch/struct.TouchContext.html))
+    /// will trigger mouse behavior, and any others are ignored.
     fn touch_event(
         &mut self,
         ctx: &mut Context,
         phase: TouchPhase,
         x: f64,
         y: f64,
+        id: u64,
     ) -> Result<(), E> {
+        if !ctx.touch.is_primary(id) {
+            return Ok(());
+        }
+
         ctx.mouse.handle_move(x as f32, y as f32);
 
         match phase {
@@ -213,6 +243,14 @@ where
         Ok(())
     }
 
+    /// A touchscreen gesture was recognized, such as a tap or a pinch.
+    /// These are handled once per frame, before [`update()`](#tymethod.update);
+    /// the same gestures can also be read from
+    /// [`TouchContext::gestures()`](../input/touch/struct.TouchContext.html#method.gestures).
+    fn gesture_event(&mut self, _ctx: &mut Context, _gesture: Gesture) -> Result<(), E> {
+        Ok(())
+    }
+
     /// A gamepad button was pressed; `id` identifies which gamepad.
     #[cfg(feature = "gamepad")]
     fn gamepad_button_down_event(
@@ -236,6 +274,10 @@ where
     }
 
     /// A gamepad axis moved; `id` identifies which gamepad.
+    ///
+    /// The value is cleaned up with the dead zone and other
+    /// [axis settings](../input/gamepad/struct.GamepadContext.html#method.axis_settings)
+    /// first, and this isn't called when that leaves it unchanged.
     #[cfg(feature = "gamepad")]
     fn gamepad_axis_event(
         &mut self,
@@ -247,6 +289,27 @@ where
         Ok(())
     }
 
+    /// A gamepad was connected; `id` identifies which gamepad.
+    /// Gamepads which are already connected when the game starts don't
+    /// cause this to be called.
+    #[cfg(feature = "gamepad")]
+    fn gamepad_connected(&mut self, _ctx: &mut Context, _id: GamepadId) -> Result<(), E> {
+        Ok(())
+    }
+
+    /// A gamepad was disconnected; `id` identifies which gamepad.
+    #[cfg(feature = "gamepad")]
+    fn gamepad_disconnected(&mut self, _ctx: &mut Context, _id: GamepadId) -> Result<(), E> {
+        Ok(())
+    }
+
+    /// A sound played to the end; `id` identifies which source or
+    /// [`SoundPool`](../audio/struct.SoundPool.html) voice it was.
+    #[cfg(feature = "audio")]
+    fn sound_finished_event(&mut self, _ctx: &mut Context, _id: SoundId) -> Result<(), E> {
+        Ok(())
+    }
+
     /// Called when the window is shown or hidden.
     fn focus_event(&mut self, _ctx: &mut Context, _gained: bool) -> Result<(), E> {
         Ok(())
@@ -387,14 +450,7 @@ where
                     };
                 }
                 WindowEvent::MouseWheel { delta, .. } => {
-                    let (x, y) = match delta {
-                        MouseScrollDelta::LineDelta(x, y) => (x, y),
-                        MouseScrollDelta::PixelDelta(pos) => {
-                            let scale_factor = ctx.gfx.window.scale_factor();
-                            let dpi::LogicalPosition { x, y } = pos.to_logical::<f32>(scale_factor);
-                            (x, y)
-                        }
-                    };
+                    let (x, y) = scroll_amount(ctx, delta);
                     let res = state.mouse_wheel_event(ctx, x, y);
                     if catch_error(ctx, res, state, control_flow, ErrorOrigin::MouseWheelEvent) {
                         return;
@@ -445,8 +501,13 @@ where
                     };
                 }
                 WindowEvent::Touch(touch) => {
-                    let res =
-                        state.touch_event(ctx, touch.phase, touch.location.x, touch.location.y);
+                    let res = state.touch_event(
+                        ctx,
+                        touch.phase,
+                        touch.location.x,
+                        touch.location.y,
+                        touch.id,
+                    );
                     if catch_error(ctx, res, state, control_flow, ErrorOrigin::TouchEvent) {
                         return;
                     };
@@ -479,6 +540,21 @@ where
                     // trace!("ignoring window event {:?}", x);
                 }
             },
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } => {
+                let res = state.raw_mouse_motion_event(ctx, delta.0 as f32, delta.1 as f32);
+                if catch_error(
+                    ctx,
+                    res,
+                    state,
+                    control_flow,
+                    ErrorOrigin::RawMouseMotionEvent,
+                ) {
+                    return;
+                };
+            }
             Event::DeviceEvent { .. } => (),
             Event::Resumed => (),
             Event::Suspended => (),
@@ -531,10 +607,65 @@ where
                                 return;
                             };
                         }
+                        gilrs::EventType::Connected => {
+                            let res = state.gamepad_connected(ctx, GamepadId(id));
+                            if catch_error(
+                                ctx,
+                                res,
+                                state,
+                                control_flow,
+                                ErrorOrigin::GamepadConnected,
+                            ) {
+                                return;
+                            };
+                        }
+                        gilrs::EventType::Disconnected => {
+                            let res = state.gamepad_disconnected(ctx, GamepadId(id));
+                            if catch_error(
+                                ctx,
+                                res,
+                                state,
+                                control_flow,
+                                ErrorOrigin::GamepadDisconnected,
+                            ) {
+                                return;
+                            };
+                        }
                         _ => {}
                     }
                 }
 
+                #[cfg(feature = "audio")]
+                for id in ctx.audio.take_finished() {
+                    let res = state.sound_finished_event(ctx, id);
+                    if catch_error(
+                        ctx,
+                        res,
+                        state,
+                        control_flow,
+                        ErrorOrigin::SoundFinishedEvent,
+                    ) {
+                        return;
+                    };
+                }
+
+                ctx.touch.update();
+                // Handlers are free to clear the gestures, so go through a copy.
+                let gestures = ctx.touch.gestures().to_vec();
+                for gesture in gestures {
+                    let res = state.gesture_event(ctx, gesture);
+                    if catch_error(ctx, res, state, control_flow, ErrorOrigin::GestureEvent) {
+                        return;
+                    };
+                }
+
+                ctx.actions.update_state(
+                    &ctx.keyboard,
+                    &ctx.mouse,
+                    #[cfg(feature = "gamepad")]
+                    &ctx.gamepad,
+                );
+
                 let res = state.update(ctx);
                 if catch_error(ctx, res, state, control_flow, ErrorOrigin::Update) {
                     return;
@@ -565,10 +696,14 @@ where
                 // necessary because it's calculated cumulatively each cycle
                 ctx.mouse.reset_delta();
 
-                // Copy the state of the keyboard into the KeyboardContext
-                // and the mouse into the MouseContext
+                // Copy the state of the keyboard into the KeyboardContext,
+                // the mouse into the MouseContext and the gamepads into
+                // the GamepadContext
                 ctx.keyboard.save_keyboard_state();
                 ctx.mouse.save_mouse_state();
+                ctx.touch.clear_gestures();
+                #[cfg(feature = "gamepad")]
+                ctx.gamepad.save_gamepad_state();
             }
             Event::RedrawRequested(_) => (),
             Event::RedrawEventsCleared => (),
@@ -599,11 +734,31 @@ where
     false
 }
 
+/// Converts a scroll from the mouse wheel to lines, or logical pixels for
+/// devices which scroll smoothly.
+fn scroll_amount(ctx: &Context, delta: MouseScrollDelta) -> (f32, f32) {
+    match delta {
+        MouseScrollDelta::LineDelta(x, y) => (x, y),
+        MouseScrollDelta::PixelDelta(pos) => {
+            let scale_factor = ctx.gfx.window.scale_factor();
+            let dpi::LogicalPosition { x, y } = pos.to_logical::<f32>(scale_factor);
+            (x, y)
+        }
+    }
+}
+
 /// Feeds an `Event` into the `Context` so it can update any internal
 /// state it needs to, such as detecting window resizes.  If you are
 /// rolling your own event loop, you should call this on the events
 /// you receive before processing them yourself.
 pub fn process_event(ctx: &mut Context, event: &mut winit::event::Event<()>) {
+    if let winit_event::Event::DeviceEvent {
+        event: winit_event::DeviceEvent::MouseMotion { delta },
+        ..
+    } = event
+    {
+        ctx.mouse.handle_raw_motion(delta.0 as f32, delta.1 as f32);
+    }
     if let winit_event::Event::WindowEvent { event, .. } = event {
         match event {
             winit_event::WindowEvent::Resized(physical_size) => {
@@ -616,6 +771,10 @@ pub fn process_event(ctx: &mut Context, event: &mut winit::event::Event<()>) {
                 ctx.mouse
                     .handle_move(physical_position.x as f32, physical_position.y as f32);
             }
+            winit_event::WindowEvent::MouseWheel { delta, .. } => {
+                let (x, y) = scroll_amount(ctx, *delta);
+                ctx.mouse.handle_scroll(x, y);
+            }
             winit_event::WindowEvent::MouseInput { button, state, .. } => {
                 let pressed = match state {
                     winit_event::ElementState::Pressed => true,
@@ -623,6 +782,14 @@ pub fn process_event(ctx: &mut Context, event: &mut winit::event::Event<()>) {
                 };
                 ctx.mouse.set_button(*button, pressed);
             }
+            winit_event::WindowEvent::Touch(touch) => {
+                ctx.touch.handle_touch(
+                    touch.id,
+                    touch.phase,
+                    touch.location.x as f32,
+                    touch.location.y as f32,
+                );
+            }
             winit_event::WindowEvent::ModifiersChanged(mods) => {
                 ctx.keyboard.set_modifiers(KeyMods::from(*mods))
             }
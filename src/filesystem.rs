@@ -34,6 +34,7 @@
 
 use crate::{
     conf,
+    input::action::Bindings,
     vfs::{self, OverlayFS, VFS},
     Context, GameError, GameResult,
 };
@@ -49,6 +50,7 @@ use std::{
 pub use crate::vfs::OpenOptions;
 
 const CONFIG_NAME: &str = "/conf.toml";
+const BINDINGS_NAME: &str = "/bindings.toml";
 
 /// A structure that contains the filesystem state and cache.
 #[derive(Debug)]
@@ -393,6 +395,38 @@ impl Filesystem {
         }
     }
 
+    /// Looks for a file named `/bindings.toml` in any resource directory
+    /// and loads the input bindings in it if it finds it.
+    /// If it can't read it for some reason, returns an error.
+    pub fn read_bindings(&self) -> GameResult<Bindings> {
+        let bindings_path = path::Path::new(BINDINGS_NAME);
+        if self.is_file(bindings_path) {
+            let mut file = self.open(bindings_path)?;
+            let b = Bindings::from_toml_file(&mut file)?;
+            Ok(b)
+        } else {
+            Err(GameError::ConfigError(String::from(
+                "Bindings file not found",
+            )))
+        }
+    }
+
+    /// Takes a set of input `Bindings` and saves it to the user directory,
+    /// overwriting any file already there.
+    pub fn write_bindings(&self, bindings: &Bindings) -> GameResult {
+        let bindings_path = path::Path::new(BINDINGS_NAME);
+        let mut file = self.create(bindings_path)?;
+        bindings.to_toml_file(&mut file)?;
+        if self.is_file(bindings_path) {
+            Ok(())
+        } else {
+            Err(GameError::ConfigError(format!(
+                "Failed to write bindings file at {}",
+                bindings_path.to_string_lossy()
+            )))
+        }
+    }
+
     /// Returns the full path to the resource directory
     /// (even if it doesn't exist)
     pub fn resources_dir(&self) -> &path::Path {
@@ -562,30 +596,33 @@ pub fn write_config(ctx: &Context, conf: &conf::Conf) -> GameResult {
     ctx.fs.write_config(conf)
 }
 
+/// A `Filesystem` over the `resources` directory of the repo, for tests
+/// which need to load things.
+#[cfg(test)]
+pub(crate) fn dummy_fs_for_tests() -> Filesystem {
+    let mut path = path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("resources");
+    let physfs = vfs::PhysicalFS::new(&path, false);
+    let mut ofs = vfs::OverlayFS::new();
+    ofs.push_front(Box::new(physfs));
+    Filesystem {
+        vfs: Arc::new(Mutex::new(ofs)),
+
+        resources_dir: "".into(),
+        zip_dir: "".into(),
+        user_config_dir: "".into(),
+        user_data_dir: "".into(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::conf;
     use crate::error::GameError;
-    use crate::filesystem::{env, vfs, Arc, Filesystem, Mutex, CONFIG_NAME};
+    use crate::filesystem::{dummy_fs_for_tests, CONFIG_NAME};
     use std::io::{Read, Write};
     use std::path;
 
-    fn dummy_fs_for_tests() -> Filesystem {
-        let mut path = path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        path.push("resources");
-        let physfs = vfs::PhysicalFS::new(&path, false);
-        let mut ofs = vfs::OverlayFS::new();
-        ofs.push_front(Box::new(physfs));
-        Filesystem {
-            vfs: Arc::new(Mutex::new(ofs)),
-
-            resources_dir: "".into(),
-            zip_dir: "".into(),
-            user_config_dir: "".into(),
-            user_data_dir: "".into(),
-        }
-    }
-
     #[test]
     fn headless_test_file_exists() {
         let f = dummy_fs_for_tests();
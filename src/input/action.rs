@@ -0,0 +1,609 @@
+//! Named actions and axes, bound to whichever keys, buttons and sticks
+//! the player likes.
+//!
+//! Rather than asking whether the space bar is pressed, a game asks
+//! whether "jump" is, and the [`Bindings`](struct.Bindings.html) decide
+//! what that means.  Bindings can be changed while the game runs, and
+//! saved to and loaded from TOML, much like a [`Conf`](../../conf/struct.Conf.html).
+//!
+//! The state of every action is worked out once a frame, just before
+//! [`EventHandler::update()`](../../event/trait.EventHandler.html#tymethod.update)
+//! is called, so "just pressed" means the same thing it does for the
+//! keyboard and mouse contexts.
+//!
+//! ```rust,no_run
+//! use ggez::input::action::{AxisBinding, Binding, Bindings};
+//! use ggez::input::keyboard::KeyCode;
+//! use ggez::input::mouse::MouseButton;
+//!
+//! # fn t(ctx: &mut ggez::Context) -> ggez::GameResult {
+//! let defaults = Bindings::new()
+//!     .action("jump", [Binding::Key(KeyCode::Space)])
+//!     .action("fire", [Binding::MouseButton(MouseButton::Left)])
+//!     .axis(
+//!         "move_x",
+//!         [AxisBinding::Buttons {
+//!             negative: Binding::Key(KeyCode::A),
+//!             positive: Binding::Key(KeyCode::D),
+//!         }],
+//!     );
+//! // Use whatever the player saved last time, if anything.
+//! let bindings = ctx.fs.read_bindings().unwrap_or(defaults);
+//! ctx.actions.set_bindings(bindings);
+//!
+//! // ...later, in `update()`
+//! if ctx.actions.is_action_just_pressed("jump") {
+//!     // Jump!
+//! }
+//! let speed = ctx.actions.axis_value("move_x") * 200.0;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+
+#[cfg(feature = "gamepad")]
+use super::gamepad::{GamepadContext, GamepadState};
+use super::keyboard::{KeyCode, KeyboardContext, ScanCode};
+use super::mouse::{MouseButton, MouseContext};
+use crate::error::GameResult;
+
+#[cfg(feature = "gamepad")]
+use gilrs::{Axis, Button};
+
+/// How far an analog input has to go before the action it is bound to
+/// counts as pressed.
+const PRESS_THRESHOLD: f32 = 0.5;
+
+/// A single input that can be bound to an action.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(from = "BindingDef", into = "BindingDef")]
+pub enum Binding {
+    /// A key, by what it means in the player's keyboard layout.
+    Key(KeyCode),
+    /// A key, by where it is on the keyboard.
+    ScanCode(ScanCode),
+    /// A mouse button.
+    MouseButton(MouseButton),
+    /// A button on any connected gamepad.
+    #[cfg(feature = "gamepad")]
+    GamepadButton(Button),
+    /// Pushing an axis of any connected gamepad one way, such as tilting
+    /// the left stick to the left.
+    #[cfg(feature = "gamepad")]
+    GamepadAxis {
+        /// The axis.
+        axis: Axis,
+        /// Whether the axis counts when it goes positive, rather than negative.
+        positive: bool,
+    },
+}
+
+// TOML has no way of writing enum variants that hold a value, so bindings
+// are written as tables with a `type` field instead.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type")]
+enum BindingDef {
+    Key {
+        key: KeyCode,
+    },
+    ScanCode {
+        code: ScanCode,
+    },
+    MouseButton {
+        button: MouseButtonDef,
+    },
+    #[cfg(feature = "gamepad")]
+    GamepadButton {
+        button: Button,
+    },
+    #[cfg(feature = "gamepad")]
+    GamepadAxis {
+        axis: Axis,
+        positive: bool,
+    },
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum MouseButtonDef {
+    Named(MouseButton),
+    Other(u16),
+}
+
+impl From<Binding> for BindingDef {
+    fn from(binding: Binding) -> Self {
+        match binding {
+            Binding::Key(key) => BindingDef::Key { key },
+            Binding::ScanCode(code) => BindingDef::ScanCode { code },
+            Binding::MouseButton(MouseButton::Other(button)) => BindingDef::MouseButton {
+                button: MouseButtonDef::Other(button),
+            },
+            Binding::MouseButton(button) => BindingDef::MouseButton {
+                button: MouseButtonDef::Named(button),
+            },
+            #[cfg(feature = "gamepad")]
+            Binding::GamepadButton(button) => BindingDef::GamepadButton { button },
+            #[cfg(feature = "gamepad")]
+            Binding::GamepadAxis { axis, positive } => BindingDef::GamepadAxis { axis, positive },
+        }
+    }
+}
+
+impl From<BindingDef> for Binding {
+    fn from(def: BindingDef) -> Self {
+        match def {
+            BindingDef::Key { key } => Binding::Key(key),
+            BindingDef::ScanCode { code } => Binding::ScanCode(code),
+            BindingDef::MouseButton {
+                button: MouseButtonDef::Named(button),
+            } => Binding::MouseButton(button),
+            BindingDef::MouseButton {
+                button: MouseButtonDef::Other(button),
+            } => Binding::MouseButton(MouseButton::Other(button)),
+            #[cfg(feature = "gamepad")]
+            BindingDef::GamepadButton { button } => Binding::GamepadButton(button),
+            #[cfg(feature = "gamepad")]
+            BindingDef::GamepadAxis { axis, positive } => Binding::GamepadAxis { axis, positive },
+        }
+    }
+}
+
+/// An input that can be bound to an axis, which goes from `-1.0` to `1.0`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type")]
+pub enum AxisBinding {
+    /// A pair of inputs, one pushing the axis each way.
+    Buttons {
+        /// The input that moves the axis towards `-1.0`.
+        negative: Binding,
+        /// The input that moves the axis towards `1.0`.
+        positive: Binding,
+    },
+    /// An axis of any connected gamepad.
+    #[cfg(feature = "gamepad")]
+    GamepadAxis {
+        /// The axis.
+        axis: Axis,
+        /// Whether to flip the axis around, such as for inverted look controls.
+        #[serde(default)]
+        inverted: bool,
+    },
+}
+
+/// The inputs bound to every action and axis.
+///
+/// An action or axis can have any number of inputs bound to it, and
+/// uses whichever of them is pushed furthest.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Bindings {
+    // Actions with nothing bound are empty arrays, which TOML needs written
+    // before any tables.
+    #[serde(default, serialize_with = "empty_first")]
+    actions: BTreeMap<String, Vec<Binding>>,
+    #[serde(default, serialize_with = "empty_first")]
+    axes: BTreeMap<String, Vec<AxisBinding>>,
+}
+
+impl Bindings {
+    /// Creates a set of bindings with nothing bound.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a TOML file from the given `Read` and attempts to parse
+    /// `Bindings` from it.
+    pub fn from_toml_file<R: io::Read>(file: &mut R) -> GameResult<Bindings> {
+        let mut s = String::new();
+        let _ = file.read_to_string(&mut s)?;
+        let decoded = toml::from_str(&s)?;
+        Ok(decoded)
+    }
+
+    /// Saves the `Bindings` to the given `Write` object,
+    /// formatted as TOML.
+    pub fn to_toml_file<W: io::Write>(&self, file: &mut W) -> GameResult {
+        let s = toml::to_vec(self)?;
+        file.write_all(&s)?;
+        Ok(())
+    }
+
+    /// Binds the given inputs to an action, replacing whatever it was
+    /// bound to before.
+    #[must_use]
+    pub fn action(mut self, action: &str, bindings: impl IntoIterator<Item = Binding>) -> Self {
+        let _ = self
+            .actions
+            .insert(action.to_owned(), bindings.into_iter().collect());
+        self
+    }
+
+    /// Binds the given inputs to an axis, replacing whatever it was
+    /// bound to before.
+    #[must_use]
+    pub fn axis(mut self, axis: &str, bindings: impl IntoIterator<Item = AxisBinding>) -> Self {
+        let _ = self
+            .axes
+            .insert(axis.to_owned(), bindings.into_iter().collect());
+        self
+    }
+
+    /// Adds an input to those bound to an action.
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        let bindings = self.actions.entry(action.to_owned()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    /// Removes an input from those bound to an action.
+    pub fn unbind(&mut self, action: &str, binding: Binding) {
+        if let Some(bindings) = self.actions.get_mut(action) {
+            bindings.retain(|b| *b != binding);
+        }
+    }
+
+    /// Adds an input to those bound to an axis.
+    pub fn bind_axis(&mut self, axis: &str, binding: AxisBinding) {
+        let bindings = self.axes.entry(axis.to_owned()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    /// Removes an input from those bound to an axis.
+    pub fn unbind_axis(&mut self, axis: &str, binding: AxisBinding) {
+        if let Some(bindings) = self.axes.get_mut(axis) {
+            bindings.retain(|b| *b != binding);
+        }
+    }
+
+    /// Returns the inputs bound to an action.
+    pub fn action_bindings(&self, action: &str) -> &[Binding] {
+        self.actions.get(action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the inputs bound to an axis.
+    pub fn axis_bindings(&self, axis: &str) -> &[AxisBinding] {
+        self.axes.get(axis).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the actions an input is bound to, for finding out what a
+    /// player would be taking the input away from by rebinding it.
+    pub fn actions_bound_to(&self, binding: Binding) -> impl Iterator<Item = &str> {
+        self.actions
+            .iter()
+            .filter(move |(_, bindings)| bindings.contains(&binding))
+            .map(|(action, _)| action.as_str())
+    }
+}
+
+fn empty_first<S, T>(map: &BTreeMap<String, Vec<T>>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    T: serde::Serialize,
+{
+    use serde::ser::SerializeMap;
+
+    let (empty, bound): (Vec<_>, Vec<_>) = map.iter().partition(|(_, v)| v.is_empty());
+    let mut out = serializer.serialize_map(Some(map.len()))?;
+    for (k, v) in empty.into_iter().chain(bound) {
+        out.serialize_entry(k, v)?;
+    }
+    out.end()
+}
+
+/// The devices actions are read from.
+struct Inputs<'a> {
+    keyboard: &'a KeyboardContext,
+    mouse: &'a MouseContext,
+    #[cfg(feature = "gamepad")]
+    gamepad: &'a GamepadContext,
+}
+
+impl Inputs<'_> {
+    /// Returns how far an input is pushed, from `0.0` to `1.0`.
+    fn value(&self, binding: Binding) -> f32 {
+        let pressed = match binding {
+            Binding::Key(key) => self.keyboard.is_key_pressed(key),
+            Binding::ScanCode(code) => self.keyboard.is_scancode_pressed(code),
+            Binding::MouseButton(button) => self.mouse.button_pressed(button),
+            #[cfg(feature = "gamepad")]
+            Binding::GamepadButton(button) => {
+                return self.gamepad_max(|gamepad| gamepad.button_value(button));
+            }
+            #[cfg(feature = "gamepad")]
+            Binding::GamepadAxis { axis, positive } => {
+                let sign = if positive { 1.0 } else { -1.0 };
+                return self.gamepad_max(|gamepad| (gamepad.axis_value(axis) * sign).max(0.0));
+            }
+        };
+        if pressed {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns where an axis is, from `-1.0` to `1.0`.
+    fn axis_value(&self, binding: AxisBinding) -> f32 {
+        match binding {
+            AxisBinding::Buttons { negative, positive } => {
+                self.value(positive) - self.value(negative)
+            }
+            #[cfg(feature = "gamepad")]
+            AxisBinding::GamepadAxis { axis, inverted } => {
+                let sign = if inverted { -1.0 } else { 1.0 };
+                let mut value = 0.0f32;
+                for gamepad in self.gamepad.states() {
+                    let v = gamepad.axis_value(axis) * sign;
+                    if v.abs() > value.abs() {
+                        value = v;
+                    }
+                }
+                value
+            }
+        }
+    }
+
+    #[cfg(feature = "gamepad")]
+    fn gamepad_max(&self, f: impl Fn(&GamepadState) -> f32) -> f32 {
+        self.gamepad.states().map(f).fold(0.0, f32::max)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+struct ActionState {
+    value: f32,
+    previous_value: f32,
+}
+
+/// Keeps track of the state of the actions and axes in a set of
+/// [`Bindings`](struct.Bindings.html).
+///
+/// Actions and axes which aren't bound to anything are never pressed,
+/// and sit at `0.0`.
+#[derive(Debug, Default)]
+pub struct ActionContext {
+    bindings: Bindings,
+    actions: HashMap<String, ActionState>,
+    axes: HashMap<String, f32>,
+}
+
+impl ActionContext {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the bindings in use.
+    pub fn bindings(&self) -> &Bindings {
+        &self.bindings
+    }
+
+    /// Returns the bindings in use, to rebind things.  Changes show up
+    /// in the state of actions from the next frame.
+    pub fn bindings_mut(&mut self) -> &mut Bindings {
+        &mut self.bindings
+    }
+
+    /// Replaces the bindings in use.  Changes show up in the state of
+    /// actions from the next frame.
+    pub fn set_bindings(&mut self, bindings: Bindings) {
+        self.bindings = bindings;
+    }
+
+    /// Checks if an action is currently pressed.
+    pub fn is_action_pressed(&self, action: &str) -> bool {
+        self.action_value(action) >= PRESS_THRESHOLD
+    }
+
+    /// Checks if an action has been pressed down this frame.
+    pub fn is_action_just_pressed(&self, action: &str) -> bool {
+        self.actions.get(action).is_some_and(|state| {
+            state.value >= PRESS_THRESHOLD && state.previous_value < PRESS_THRESHOLD
+        })
+    }
+
+    /// Checks if an action has been released this frame.
+    pub fn is_action_just_released(&self, action: &str) -> bool {
+        self.actions.get(action).is_some_and(|state| {
+            state.value < PRESS_THRESHOLD && state.previous_value >= PRESS_THRESHOLD
+        })
+    }
+
+    /// Returns how far an action is pressed, from `0.0` to `1.0`.  Only
+    /// analog inputs such as gamepad triggers and sticks give anything
+    /// in between, and an action counts as pressed from halfway.
+    pub fn action_value(&self, action: &str) -> f32 {
+        self.actions.get(action).map_or(0.0, |state| state.value)
+    }
+
+    /// Returns where an axis is, from `-1.0` to `1.0`.
+    pub fn axis_value(&self, axis: &str) -> f32 {
+        self.axes.get(axis).copied().unwrap_or(0.0)
+    }
+
+    /// Works out the state of every action and axis from the current
+    /// state of the input devices.
+    ///
+    /// Called once a frame by the event loop; if you are writing your own,
+    /// call this after handling input events and before updating the game.
+    pub fn update_state(
+        &mut self,
+        keyboard: &KeyboardContext,
+        mouse: &MouseContext,
+        #[cfg(feature = "gamepad")] gamepad: &GamepadContext,
+    ) {
+        let inputs = Inputs {
+            keyboard,
+            mouse,
+            #[cfg(feature = "gamepad")]
+            gamepad,
+        };
+        self.actions
+            .retain(|action, _| self.bindings.actions.contains_key(action));
+        for (action, bindings) in &self.bindings.actions {
+            let value = bindings
+                .iter()
+                .map(|&binding| inputs.value(binding))
+                .fold(0.0, f32::max);
+            let state = self.actions.entry(action.clone()).or_default();
+            state.previous_value = state.value;
+            state.value = value;
+        }
+        self.axes.clear();
+        for (axis, bindings) in &self.bindings.axes {
+            let mut value = 0.0f32;
+            for &binding in bindings {
+                let v = inputs.axis_value(binding);
+                if v.abs() > value.abs() {
+                    value = v;
+                }
+            }
+            let _ = self.axes.insert(axis.clone(), value.clamp(-1.0, 1.0));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bindings() -> Bindings {
+        Bindings::new()
+            .action(
+                "jump",
+                [Binding::Key(KeyCode::Space), Binding::ScanCode(57)],
+            )
+            .action(
+                "fire",
+                [
+                    Binding::MouseButton(MouseButton::Left),
+                    Binding::MouseButton(MouseButton::Other(8)),
+                ],
+            )
+            .axis(
+                "move_x",
+                [AxisBinding::Buttons {
+                    negative: Binding::Key(KeyCode::A),
+                    positive: Binding::Key(KeyCode::D),
+                }],
+            )
+    }
+
+    /// Tries to encode and decode some `Bindings`
+    /// and makes sure it gets the same result it had.
+    #[test]
+    fn headless_encode_round_trip() {
+        let mut b1 = bindings();
+        #[cfg(feature = "gamepad")]
+        {
+            b1.bind("jump", Binding::GamepadButton(Button::South));
+            b1.bind(
+                "fire",
+                Binding::GamepadAxis {
+                    axis: Axis::RightZ,
+                    positive: true,
+                },
+            );
+            b1.bind_axis(
+                "move_x",
+                AxisBinding::GamepadAxis {
+                    axis: Axis::LeftStickX,
+                    inverted: false,
+                },
+            );
+        }
+        // Something unbound altogether, after things that aren't.
+        b1.unbind("jump", Binding::Key(KeyCode::Space));
+        b1.unbind("jump", Binding::ScanCode(57));
+        #[cfg(feature = "gamepad")]
+        b1.unbind("jump", Binding::GamepadButton(Button::South));
+        assert!(b1.action_bindings("jump").is_empty());
+        let mut writer = Vec::new();
+        b1.to_toml_file(&mut writer).unwrap();
+        let mut reader = writer.as_slice();
+        let b2 = Bindings::from_toml_file(&mut reader).unwrap();
+        assert_eq!(b1, b2);
+
+        let mut empty: &[u8] = b"";
+        assert_eq!(
+            Bindings::from_toml_file(&mut empty).unwrap(),
+            Bindings::new()
+        );
+    }
+
+    #[test]
+    fn rebinding() {
+        let mut b = bindings();
+        b.bind("jump", Binding::Key(KeyCode::Space));
+        b.bind("jump", Binding::Key(KeyCode::W));
+        assert_eq!(
+            b.action_bindings("jump"),
+            [
+                Binding::Key(KeyCode::Space),
+                Binding::ScanCode(57),
+                Binding::Key(KeyCode::W)
+            ]
+        );
+        b.unbind("jump", Binding::ScanCode(57));
+        assert_eq!(b.action_bindings("jump").len(), 2);
+        assert_eq!(
+            b.actions_bound_to(Binding::Key(KeyCode::W))
+                .collect::<Vec<_>>(),
+            ["jump"]
+        );
+        assert!(b.action_bindings("duck").is_empty());
+    }
+
+    #[test]
+    fn tracks_actions() {
+        let mut keyboard = KeyboardContext::new();
+        let mut mouse = MouseContext::new();
+        #[cfg(feature = "gamepad")]
+        let gamepad = GamepadContext::new(
+            &Default::default(),
+            &crate::filesystem::dummy_fs_for_tests(),
+        )
+        .unwrap();
+        let mut actions = ActionContext::new();
+        actions.set_bindings(bindings());
+        let mut frame = |keyboard: &mut KeyboardContext, mouse: &mut MouseContext| {
+            actions.update_state(
+                keyboard,
+                mouse,
+                #[cfg(feature = "gamepad")]
+                &gamepad,
+            );
+            keyboard.save_keyboard_state();
+            mouse.save_mouse_state();
+            (
+                actions.is_action_pressed("jump"),
+                actions.is_action_just_pressed("jump"),
+                actions.is_action_just_released("jump"),
+                actions.axis_value("move_x"),
+            )
+        };
+
+        assert_eq!(frame(&mut keyboard, &mut mouse), (false, false, false, 0.0));
+        keyboard.set_key(KeyCode::Space, true);
+        keyboard.set_key(KeyCode::A, true);
+        assert_eq!(frame(&mut keyboard, &mut mouse), (true, true, false, -1.0));
+        // Held with another of its bindings as well, and both directions at once.
+        keyboard.set_scancode(57, true);
+        keyboard.set_key(KeyCode::D, true);
+        assert_eq!(frame(&mut keyboard, &mut mouse), (true, false, false, 0.0));
+        keyboard.set_key(KeyCode::Space, false);
+        keyboard.set_scancode(57, false);
+        keyboard.set_key(KeyCode::A, false);
+        assert_eq!(frame(&mut keyboard, &mut mouse), (false, false, true, 1.0));
+
+        mouse.set_button(MouseButton::Left, true);
+        let _ = frame(&mut keyboard, &mut mouse);
+        assert!(actions.is_action_just_pressed("fire"));
+        assert_eq!(actions.action_value("fire"), 1.0);
+        assert!(!actions.is_action_pressed("duck"));
+        assert_eq!(actions.axis_value("move_y"), 0.0);
+    }
+}
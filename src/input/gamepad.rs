@@ -5,8 +5,11 @@
 //! cross-platform support.  Why not give it a hand?
 #![cfg(feature = "gamepad")]
 
-use gilrs::ConnectedGamepadsIterator;
+use gilrs::{Axis, Button, ConnectedGamepadsIterator, EventType, GilrsBuilder};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
+use std::io::Read;
+use std::path;
 
 pub use gilrs::{self, Event, Gamepad, Gilrs};
 
@@ -14,12 +17,350 @@ pub use gilrs::{self, Event, Gamepad, Gilrs};
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct GamepadId(pub(crate) gilrs::GamepadId);
 
-use crate::context::Context;
-use crate::error::GameResult;
+/// How many players gamepads are handed out to.
+pub const MAX_PLAYERS: usize = 4;
+
+use crate::conf::{AxisSettings, DeadzoneShape, GamepadSetup};
+use crate::context::{Context, Has};
+use crate::error::{GameError, GameResult};
+use crate::filesystem::Filesystem;
+
+/// Returns the other axis of the analog stick an axis belongs to, if it
+/// belongs to one.
+fn stick_partner(axis: Axis) -> Option<Axis> {
+    match axis {
+        Axis::LeftStickX => Some(Axis::LeftStickY),
+        Axis::LeftStickY => Some(Axis::LeftStickX),
+        Axis::RightStickX => Some(Axis::RightStickY),
+        Axis::RightStickY => Some(Axis::RightStickX),
+        _ => None,
+    }
+}
+
+/// Returns the axis whose settings apply to an analog trigger, for when
+/// `gilrs` reports the trigger as a button rather than as that axis.
+fn trigger_axis(button: Button) -> Option<Axis> {
+    match button {
+        Button::LeftTrigger2 => Some(Axis::LeftZ),
+        Button::RightTrigger2 => Some(Axis::RightZ),
+        _ => None,
+    }
+}
+
+/// The settings for every axis, along with those changed for particular
+/// axes and gamepads.
+#[derive(Debug, Default)]
+struct AxisConfig {
+    setup: GamepadSetup,
+    axes: HashMap<Axis, AxisSettings>,
+    gamepads: HashMap<(GamepadId, Axis), AxisSettings>,
+}
+
+impl AxisConfig {
+    fn get(&self, id: GamepadId, axis: Axis) -> AxisSettings {
+        if let Some(&settings) = self.gamepads.get(&(id, axis)) {
+            settings
+        } else if let Some(&settings) = self.axes.get(&axis) {
+            settings
+        } else if stick_partner(axis).is_some() {
+            self.setup.sticks
+        } else {
+            self.setup.triggers
+        }
+    }
+}
+
+/// Checks that every line of some SDL style gamepad mappings makes sense,
+/// as `gilrs` skips over any that don't without a word.
+fn check_mappings(mappings: &str, source: &str) -> GameResult {
+    for (number, line) in mappings.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        check_mapping(line).map_err(|reason| {
+            GameError::GamepadError(format!(
+                "Invalid gamepad mapping on line {} of {source}: {reason}: {line}",
+                number + 1
+            ))
+        })?;
+    }
+    Ok(())
+}
+
+/// Checks a single mapping: a GUID, a name, then what each button and axis
+/// of the gamepad is, such as `a:b0` or `leftx:a0`.
+fn check_mapping(line: &str) -> Result<(), String> {
+    let mut fields = line.split(',');
+    let guid = fields.next().unwrap_or_default();
+    if guid.len() != 32 || !guid.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("'{guid}' isn't a GUID"));
+    }
+    if fields.next().is_none_or(str::is_empty) {
+        return Err(String::from("it has no name"));
+    }
+    for field in fields.filter(|field| !field.is_empty()) {
+        let Some((key, value)) = field.split_once(':') else {
+            return Err(format!("'{field}' isn't a mapping"));
+        };
+        if key.is_empty() {
+            return Err(format!("'{field}' doesn't say what it maps"));
+        }
+        // Anything else, such as the platform, is up to SDL and `gilrs`.
+        if key != "platform" && !value.is_empty() && !is_element(value) {
+            return Err(format!("'{value}' isn't a button, axis or hat"));
+        }
+    }
+    Ok(())
+}
+
+/// Checks for an element of a gamepad, as mappings name them: `b3`, `a1`,
+/// `-a2`, `a4~` or `h0.1`.
+fn is_element(element: &str) -> bool {
+    let digits = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    let element = element.trim_start_matches(['+', '-']);
+    if let Some(button) = element.strip_prefix('b') {
+        digits(button)
+    } else if let Some(axis) = element.strip_prefix('a') {
+        digits(axis.strip_suffix('~').unwrap_or(axis))
+    } else if let Some(hat) = element.strip_prefix('h') {
+        hat.split_once('.')
+            .is_some_and(|(hat, mask)| digits(hat) && digits(mask))
+    } else {
+        false
+    }
+}
+
+/// A gamepad given to a player.
+#[derive(Copy, Clone, Debug)]
+struct PlayerSlot {
+    id: GamepadId,
+    uuid: [u8; 16],
+    connected: bool,
+}
+
+/// Which gamepad each player has.  Players keep their gamepads when they
+/// are disconnected, so that they get them back when they reconnect.
+#[derive(Debug, Default)]
+struct PlayerSlots {
+    slots: [Option<PlayerSlot>; MAX_PLAYERS],
+}
+
+impl PlayerSlots {
+    fn index(&self, id: GamepadId) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|slot| slot.is_some_and(|slot| slot.id == id))
+    }
+
+    /// Gives a gamepad which has just been connected to a player, if there's
+    /// one without a gamepad.
+    fn connect(&mut self, id: GamepadId, uuid: [u8; 16]) {
+        let index = self.index(id).or_else(|| {
+            // A disconnected gamepad of the same kind is most likely this
+            // one back again, under a new ID.
+            self.slots
+                .iter()
+                .position(|slot| slot.is_some_and(|slot| !slot.connected && slot.uuid == uuid))
+                .or_else(|| self.slots.iter().position(Option::is_none))
+        });
+        if let Some(index) = index {
+            self.slots[index] = Some(PlayerSlot {
+                id,
+                uuid,
+                connected: true,
+            });
+        }
+    }
+
+    fn disconnect(&mut self, id: GamepadId) {
+        if let Some(slot) = self.slots.iter_mut().flatten().find(|slot| slot.id == id) {
+            slot.connected = false;
+        }
+    }
+
+    fn disconnect_all(&mut self) {
+        for slot in self.slots.iter_mut().flatten() {
+            slot.connected = false;
+        }
+    }
+
+    fn player(&self, id: GamepadId) -> Option<usize> {
+        self.index(id).map(|index| index + 1)
+    }
+
+    fn gamepad(&self, player: usize) -> Option<GamepadId> {
+        let slot = self.slots.get(player.checked_sub(1)?)?.as_ref()?;
+        slot.connected.then_some(slot.id)
+    }
+
+    /// Gives a gamepad to a player, swapping with whichever player had it.
+    fn assign(&mut self, id: GamepadId, uuid: [u8; 16], player: usize) {
+        let Some(index) = player.checked_sub(1).filter(|&index| index < MAX_PLAYERS) else {
+            return;
+        };
+        let slot = PlayerSlot {
+            id,
+            uuid,
+            connected: true,
+        };
+        match self.index(id) {
+            Some(old) => self.slots.swap(old, index),
+            None => self.slots[index] = Some(slot),
+        }
+    }
+
+    fn release(&mut self, player: usize) {
+        if let Some(slot) = player
+            .checked_sub(1)
+            .and_then(|index| self.slots.get_mut(index))
+        {
+            *slot = None;
+        }
+    }
+}
+
+/// The state of a single gamepad, as of the events handled so far.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct GamepadState {
+    pressed_buttons: HashSet<Button>,
+    previously_pressed_buttons: HashSet<Button>,
+    button_values: HashMap<Button, f32>,
+    raw_trigger_values: HashMap<Button, f32>,
+    raw_axis_values: HashMap<Axis, f32>,
+    // Cleaned up according to the axis settings.
+    axis_values: HashMap<Axis, f32>,
+}
+
+impl GamepadState {
+    pub(crate) fn is_button_pressed(&self, button: Button) -> bool {
+        self.pressed_buttons.contains(&button)
+    }
+
+    fn is_button_just_pressed(&self, button: Button) -> bool {
+        self.pressed_buttons.contains(&button) && !self.previously_pressed_buttons.contains(&button)
+    }
+
+    fn is_button_just_released(&self, button: Button) -> bool {
+        !self.pressed_buttons.contains(&button) && self.previously_pressed_buttons.contains(&button)
+    }
+
+    pub(crate) fn button_value(&self, button: Button) -> f32 {
+        match self.button_values.get(&button) {
+            Some(&value) => value,
+            None if self.is_button_pressed(button) => 1.0,
+            None => 0.0,
+        }
+    }
+
+    pub(crate) fn axis_value(&self, axis: Axis) -> f32 {
+        self.axis_values.get(&axis).copied().unwrap_or(0.0)
+    }
+
+    /// Updates the state from a gamepad event.
+    fn handle_event(&mut self, event: &EventType) {
+        match *event {
+            EventType::ButtonPressed(button, _) => self.set_button(button, true),
+            EventType::ButtonReleased(button, _) => self.set_button(button, false),
+            EventType::ButtonChanged(button, value, _) if trigger_axis(button).is_some() => {
+                let _ = self.raw_trigger_values.insert(button, value);
+            }
+            EventType::ButtonChanged(button, value, _) => {
+                let _ = self.button_values.insert(button, value);
+            }
+            EventType::AxisChanged(axis, value, _) => {
+                let _ = self.raw_axis_values.insert(axis, value);
+            }
+            EventType::Disconnected => self.release_all(),
+            _ => (),
+        }
+    }
+
+    fn set_button(&mut self, button: Button, pressed: bool) {
+        if pressed {
+            let _ = self.pressed_buttons.insert(button);
+        } else {
+            let _ = self.pressed_buttons.remove(&button);
+        }
+    }
+
+    /// Lets go of everything, but keeps what was pressed last frame so
+    /// that it still shows up as just released.
+    fn release_all(&mut self) {
+        self.pressed_buttons.clear();
+        self.button_values.clear();
+        self.raw_trigger_values.clear();
+        self.raw_axis_values.clear();
+        self.axis_values.clear();
+    }
+
+    /// Works out the value of every axis, and of the analog triggers, from
+    /// its raw value.
+    fn process_axes(&mut self, settings: impl Fn(Axis) -> AxisSettings) {
+        for (&button, &raw) in &self.raw_trigger_values {
+            let value = trigger_axis(button).map_or(raw, |axis| settings(axis).apply(raw));
+            let _ = self.button_values.insert(button, value.clamp(0.0, 1.0));
+        }
+        self.axis_values.clear();
+        for (&axis, &raw) in &self.raw_axis_values {
+            let settings = settings(axis);
+            let value = match stick_partner(axis) {
+                // Sticks with a radial dead zone are cleaned up as a whole,
+                // keeping the direction they point in.
+                Some(partner) if settings.deadzone_shape == DeadzoneShape::Radial => {
+                    let other = self.raw_axis_values.get(&partner).copied().unwrap_or(0.0);
+                    let distance = raw.hypot(other);
+                    if distance > 0.0 {
+                        raw * settings.scale(distance) / distance
+                    } else {
+                        0.0
+                    }
+                }
+                _ => settings.apply(raw),
+            };
+            let _ = self.axis_values.insert(axis, value.clamp(-1.0, 1.0));
+        }
+    }
+
+    fn save_state(&mut self) {
+        self.previously_pressed_buttons
+            .clone_from(&self.pressed_buttons);
+    }
+}
 
 /// A structure that contains gamepad state using `gilrs`.
+///
+/// As well as handing out `gilrs` events and gamepads, it keeps track of
+/// which buttons are pressed on each gamepad and where their axes are, so
+/// they can be checked at any time in the same way as the keyboard and mouse.
+///
+/// Axis values are cleaned up according to their
+/// [`AxisSettings`](../../conf/struct.AxisSettings.html), which start out as
+/// given by the [`GamepadSetup`](../../conf/struct.GamepadSetup.html) in the
+/// `Conf` and can be changed for each axis and gamepad.  This goes for the
+/// values in axis events too, and movements that don't change the cleaned up
+/// value, such as drift inside the dead zone, don't send events at all.
+/// Analog triggers which `gilrs` reports as the `LeftTrigger2` and
+/// `RightTrigger2` buttons are cleaned up the same way, with the settings of
+/// `Axis::LeftZ` and `Axis::RightZ`, in their button values and events.
+///
+/// Gamepads are handed out to players 1 to [`MAX_PLAYERS`](constant.MAX_PLAYERS.html)
+/// in the order they connect, and each player keeps their gamepad if it is
+/// disconnected, getting it back when it reconnects.
+///
+/// Gamepads `gilrs` doesn't know how to map can be given SDL style mappings,
+/// such as from a `gamecontrollerdb.txt`, with the `mappings` of the
+/// [`GamepadSetup`](../../conf/struct.GamepadSetup.html) or at runtime with
+/// [`load_mappings()`](#method.load_mappings).
 pub struct GamepadContext {
     pub(crate) gilrs: Gilrs,
+    states: HashMap<GamepadId, GamepadState>,
+    settings: AxisConfig,
+    players: PlayerSlots,
+    // Every mapping loaded so far, as `gilrs` only takes them on creation.
+    mappings: String,
+    // Events for the other axis of a stick which moved along with it.
+    pending: VecDeque<Event>,
 }
 
 impl fmt::Debug for GamepadContext {
@@ -29,23 +370,105 @@ impl fmt::Debug for GamepadContext {
 }
 
 impl GamepadContext {
-    pub(crate) fn new() -> GameResult<Self> {
-        let gilrs = Gilrs::new()?;
-        Ok(GamepadContext { gilrs })
+    pub(crate) fn new(setup: &GamepadSetup, fs: &Filesystem) -> GameResult<Self> {
+        let mappings = if setup.mappings.is_empty() {
+            String::new()
+        } else {
+            read_mappings(fs, &setup.mappings)?
+        };
+        let gilrs = GilrsBuilder::new().add_mappings(&mappings).build()?;
+        let mut gamepad = GamepadContext::from(gilrs);
+        gamepad.settings.setup = setup.clone();
+        gamepad.mappings = mappings;
+        Ok(gamepad)
     }
 }
 
 impl From<Gilrs> for GamepadContext {
     /// Converts from a `Gilrs` custom instance to a `GilrsGamepadContext`
     fn from(gilrs: Gilrs) -> Self {
-        Self { gilrs }
+        let mut players = PlayerSlots::default();
+        for (id, gamepad) in gilrs.gamepads() {
+            players.connect(GamepadId(id), gamepad.uuid());
+        }
+        Self {
+            gilrs,
+            states: HashMap::new(),
+            settings: AxisConfig::default(),
+            players,
+            mappings: String::new(),
+            pending: VecDeque::new(),
+        }
     }
 }
 
 impl GamepadContext {
-    /// Returns a gamepad event.
+    /// Returns a gamepad event, updating the state of the gamepad it came from.
+    ///
+    /// Axis events, and button events for analog triggers, carry the value as
+    /// cleaned up by the [axis settings](#method.axis_settings), and are left
+    /// out when that doesn't change.  With a radial dead zone, moving one axis
+    /// of a stick can change the value of the other, which then gets an event
+    /// of its own.
     pub fn next_event(&mut self) -> Option<Event> {
-        self.gilrs.next_event()
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+        loop {
+            let mut event = self.gilrs.next_event()?;
+            let id = GamepadId(event.id);
+            let state = self.states.entry(id).or_default();
+            state.handle_event(&event.event);
+            if let EventType::ButtonChanged(button, _, code) = event.event {
+                if trigger_axis(button).is_some() {
+                    let before = state.button_value(button);
+                    state.process_axes(|axis| self.settings.get(id, axis));
+                    let value = state.button_value(button);
+                    if value == before {
+                        continue;
+                    }
+                    event.event = EventType::ButtonChanged(button, value, code);
+                }
+            }
+            if let EventType::AxisChanged(axis, _, code) = event.event {
+                let partner = stick_partner(axis);
+                let before = state.axis_value(axis);
+                let partner_before = partner.map(|partner| state.axis_value(partner));
+                state.process_axes(|axis| self.settings.get(id, axis));
+
+                if let (Some(partner), Some(partner_before)) = (partner, partner_before) {
+                    let value = state.axis_value(partner);
+                    let code = self
+                        .gilrs
+                        .connected_gamepad(event.id)
+                        .and_then(|gamepad| gamepad.axis_code(partner))
+                        .filter(|_| value != partner_before);
+                    if let Some(code) = code {
+                        self.pending.push_back(Event {
+                            event: EventType::AxisChanged(partner, value, code),
+                            ..event
+                        });
+                    }
+                }
+                let value = state.axis_value(axis);
+                if value == before {
+                    match self.pending.pop_front() {
+                        Some(event) => return Some(event),
+                        None => continue,
+                    }
+                }
+                event.event = EventType::AxisChanged(axis, value, code);
+            }
+            match event.event {
+                EventType::Connected => {
+                    let uuid = self.uuid(id).unwrap_or_default();
+                    self.players.connect(id, uuid);
+                }
+                EventType::Disconnected => self.players.disconnect(id),
+                _ => (),
+            }
+            return Some(event);
+        }
     }
 
     /// Returns the `Gamepad` associated with an `id`.
@@ -59,6 +482,173 @@ impl GamepadContext {
             wrapped: self.gilrs.gamepads(),
         }
     }
+
+    /// Returns the name of a gamepad, if it is connected.
+    pub fn name(&self, id: GamepadId) -> Option<String> {
+        self.gilrs
+            .connected_gamepad(id.0)
+            .map(|gamepad| gamepad.name().to_owned())
+    }
+
+    /// Returns the UUID of a gamepad, if it is connected.  This identifies
+    /// the kind of gamepad it is, rather than the gamepad itself, so two
+    /// gamepads of the same kind share one.
+    pub fn uuid(&self, id: GamepadId) -> Option<[u8; 16]> {
+        self.gilrs
+            .connected_gamepad(id.0)
+            .map(|gamepad| gamepad.uuid())
+    }
+
+    /// Returns the player a gamepad belongs to, from 1 to `MAX_PLAYERS`.
+    /// Players keep their gamepads while they are disconnected.
+    pub fn player(&self, id: GamepadId) -> Option<usize> {
+        self.players.player(id)
+    }
+
+    /// Returns the gamepad a player has, if it is connected.
+    pub fn player_gamepad(&self, player: usize) -> Option<GamepadId> {
+        self.players.gamepad(player)
+    }
+
+    /// Gives a gamepad to a player, from 1 to `MAX_PLAYERS`.  If another
+    /// player has it, they swap gamepads with each other.
+    pub fn set_player(&mut self, id: GamepadId, player: usize) {
+        let uuid = self.uuid(id).unwrap_or_default();
+        self.players.assign(id, uuid, player);
+    }
+
+    /// Takes a player's gamepad off them, so that the next gamepad to
+    /// connect goes to them instead of them waiting for theirs to come back.
+    pub fn release_player(&mut self, player: usize) {
+        self.players.release(player);
+    }
+
+    /// Checks if a button is currently pressed down on a gamepad.
+    pub fn is_button_pressed(&self, id: GamepadId, button: Button) -> bool {
+        self.states
+            .get(&id)
+            .is_some_and(|state| state.is_button_pressed(button))
+    }
+
+    /// Checks if a button has been pressed down on a gamepad this frame.
+    pub fn is_button_just_pressed(&self, id: GamepadId, button: Button) -> bool {
+        self.states
+            .get(&id)
+            .is_some_and(|state| state.is_button_just_pressed(button))
+    }
+
+    /// Checks if a button has been released on a gamepad this frame.
+    pub fn is_button_just_released(&self, id: GamepadId, button: Button) -> bool {
+        self.states
+            .get(&id)
+            .is_some_and(|state| state.is_button_just_released(button))
+    }
+
+    /// Returns how far a button is pressed on a gamepad, from `0.0` to `1.0`.
+    /// Only analog buttons such as triggers give anything in between.
+    pub fn button_value(&self, id: GamepadId, button: Button) -> f32 {
+        self.states
+            .get(&id)
+            .map_or(0.0, |state| state.button_value(button))
+    }
+
+    /// Returns the value of an axis on a gamepad, from `-1.0` to `1.0`.
+    pub fn axis_value(&self, id: GamepadId, axis: Axis) -> f32 {
+        self.states
+            .get(&id)
+            .map_or(0.0, |state| state.axis_value(axis))
+    }
+
+    /// Returns the settings used to clean up an axis of a gamepad.
+    pub fn axis_settings(&self, id: GamepadId, axis: Axis) -> AxisSettings {
+        self.settings.get(id, axis)
+    }
+
+    /// Changes the settings for every axis which hasn't had settings of its own set.
+    /// The mappings file in it isn't loaded; use
+    /// [`load_mappings()`](#method.load_mappings) for that.
+    pub fn set_gamepad_setup(&mut self, setup: GamepadSetup) {
+        self.settings.setup = setup;
+        self.reprocess_axes();
+    }
+
+    /// Sets the settings for an axis of every gamepad, except those
+    /// which have had settings set for the axis on their own.
+    pub fn set_axis_settings(&mut self, axis: Axis, settings: AxisSettings) {
+        let _ = self.settings.axes.insert(axis, settings);
+        self.reprocess_axes();
+    }
+
+    /// Sets the settings for an axis of a single gamepad, such as to calibrate
+    /// a worn out stick.
+    pub fn set_gamepad_axis_settings(&mut self, id: GamepadId, axis: Axis, settings: AxisSettings) {
+        let _ = self.settings.gamepads.insert((id, axis), settings);
+        self.reprocess_axes();
+    }
+
+    /// Forgets the settings set for particular axes and gamepads, so that
+    /// every axis goes back to those in the `GamepadSetup`.
+    pub fn reset_axis_settings(&mut self) {
+        self.settings.axes.clear();
+        self.settings.gamepads.clear();
+        self.reprocess_axes();
+    }
+
+    fn reprocess_axes(&mut self) {
+        for (&id, state) in &mut self.states {
+            state.process_axes(|axis| self.settings.get(id, axis));
+        }
+    }
+
+    /// Loads SDL style gamepad mappings from a file, one mapping per line,
+    /// on top of those already loaded.
+    ///
+    /// Gamepads are reopened to pick up the new mappings, which forgets
+    /// their state and may change their IDs, though players keep their
+    /// gamepads.  If any line is invalid, nothing is loaded.
+    pub fn load_mappings<P: AsRef<path::Path>>(
+        &mut self,
+        fs: &impl Has<Filesystem>,
+        path: P,
+    ) -> GameResult {
+        let mappings = read_mappings(fs.retrieve(), path)?;
+        self.add_mappings(&mappings)
+    }
+
+    /// Adds SDL style gamepad mappings, one mapping per line, on top of
+    /// those already loaded.  The same as
+    /// [`load_mappings()`](#method.load_mappings), but from a string.
+    pub fn add_mappings(&mut self, mappings: &str) -> GameResult {
+        check_mappings(mappings, "the mappings given")?;
+        let all = format!("{}\n{mappings}", self.mappings);
+        let gilrs = GilrsBuilder::new().add_mappings(&all).build()?;
+        self.mappings = all;
+
+        // The gamepads open again as if they were new ones, so their players
+        // find them again by what kind of gamepad they are.
+        self.players.disconnect_all();
+        self.states.clear();
+        self.pending.clear();
+        for (id, gamepad) in gilrs.gamepads() {
+            self.players.connect(GamepadId(id), gamepad.uuid());
+        }
+        self.gilrs = gilrs;
+        Ok(())
+    }
+
+    /// Copies the current state of the gamepads into the context. If you are writing your own event loop
+    /// you need to call this at the end of every update in order to use the functions `is_button_just_pressed`
+    /// and `is_button_just_released`. Otherwise this is handled for you.
+    pub fn save_gamepad_state(&mut self) {
+        for state in self.states.values_mut() {
+            state.save_state();
+        }
+    }
+
+    /// Returns the state of every gamepad that has sent any events.
+    pub(crate) fn states(&self) -> impl Iterator<Item = &GamepadState> {
+        self.states.values()
+    }
 }
 
 /// An iterator of the connected gamepads
@@ -92,36 +682,194 @@ pub fn gamepads(ctx: &Context) -> GamepadsIterator {
     ctx.gamepad.gamepads()
 }
 
+/// Reads a mappings file, checking every line.
+fn read_mappings<P: AsRef<path::Path>>(fs: &Filesystem, path: P) -> GameResult<String> {
+    let path = path.as_ref();
+    let mut mappings = String::new();
+    let _ = fs.open(path)?.read_to_string(&mut mappings)?;
+    check_mappings(&mappings, &path.to_string_lossy())?;
+    Ok(mappings)
+}
+
 // Properties gamepads might want:
 // Number of buttons
 // Number of axes
-// Name/ID
-// Is it connected?  (For consoles?)
 // Whether or not they support vibration
 
-/*
-/// Lists all gamepads.  With metainfo, maybe?
-pub fn list_gamepads() {
-    unimplemented!()
-}
-
-/// Returns the state of the given axis on a gamepad.
-pub fn axis() {
-    unimplemented!()
-}
-
-/// Returns the state of the given button on a gamepad.
-pub fn button_pressed() {
-    unimplemented!()
-}
-*/
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::conf::ResponseCurve;
 
     #[test]
     fn gilrs_init() {
-        assert!(GamepadContext::new().is_ok());
+        let fs = crate::filesystem::dummy_fs_for_tests();
+        assert!(GamepadContext::new(&GamepadSetup::default(), &fs).is_ok());
+    }
+
+    #[test]
+    fn mappings() {
+        let xbox = "030000005e0400008e02000014010000,Xbox 360 Controller,a:b0,b:b1,\
+                    dpup:h0.1,leftx:a0,lefty:a1,-rightx:a3~,+righttrigger:a5,platform:Linux,";
+        let more = format!("# Comment\n\n{xbox}\n  \n{xbox}");
+        assert!(check_mappings(xbox, "test").is_ok());
+        assert!(check_mappings(&more, "test").is_ok());
+        assert!(check_mappings("", "test").is_ok());
+
+        let bad = [
+            "nope,Xbox,a:b0",
+            "030000005e0400008e02000014010000,,a:b0",
+            "030000005e0400008e02000014010000,Xbox,a",
+            "030000005e0400008e02000014010000,Xbox,a:c0",
+            "030000005e0400008e02000014010000,Xbox,dpup:h0",
+            "030000005e0400008e02000014010000,Xbox,:b0",
+        ];
+        for line in bad {
+            let mappings = format!("{xbox}\n# Comment\n{line}\n{xbox}");
+            let Err(GameError::GamepadError(error)) = check_mappings(&mappings, "/db.txt") else {
+                panic!("{line} should be invalid");
+            };
+            assert!(error.contains("line 3 of /db.txt"), "{error}");
+            assert!(error.ends_with(line), "{error}");
+        }
+
+        let fs = crate::filesystem::dummy_fs_for_tests();
+        let mut gamepad = GamepadContext::new(&GamepadSetup::default(), &fs).unwrap();
+        assert!(gamepad.add_mappings(xbox).is_ok());
+        assert!(gamepad.add_mappings(bad[0]).is_err());
+        assert!(gamepad.mappings.contains(xbox));
+        assert!(!gamepad.mappings.contains(bad[0]));
+        assert!(gamepad.load_mappings(&fs, "/no_such_file.txt").is_err());
+    }
+
+    #[test]
+    fn button_and_axis_tracking() {
+        let mut state = GamepadState::default();
+        assert!(!state.is_button_pressed(Button::South));
+        state.set_button(Button::South, true);
+        let _ = state.raw_axis_values.insert(Axis::LeftStickX, -0.5);
+        state.process_axes(|_| AxisSettings::default().deadzone(0.0));
+        assert!(state.is_button_pressed(Button::South));
+        assert!(state.is_button_just_pressed(Button::South));
+        assert_eq!(state.button_value(Button::South), 1.0);
+        assert_eq!(state.axis_value(Axis::LeftStickX), -0.5);
+        assert_eq!(state.axis_value(Axis::LeftStickY), 0.0);
+
+        state.save_state();
+        assert!(state.is_button_pressed(Button::South));
+        assert!(!state.is_button_just_pressed(Button::South));
+        let _ = state.button_values.insert(Button::LeftTrigger2, 0.25);
+        assert_eq!(state.button_value(Button::LeftTrigger2), 0.25);
+        assert!(!state.is_button_pressed(Button::LeftTrigger2));
+
+        state.release_all();
+        assert!(!state.is_button_pressed(Button::South));
+        assert!(state.is_button_just_released(Button::South));
+        assert_eq!(state.axis_value(Axis::LeftStickX), 0.0);
+        state.save_state();
+        assert!(!state.is_button_just_released(Button::South));
+    }
+
+    #[test]
+    fn cleans_up_axes() {
+        let settings = AxisSettings::default()
+            .deadzone(0.2)
+            .anti_deadzone(0.1)
+            .saturation(0.8);
+        assert_eq!(settings.apply(0.15), 0.0);
+        assert_eq!(settings.apply(-0.2), 0.0);
+        assert!((settings.apply(0.5) - 0.55).abs() < 1e-6);
+        assert!((settings.apply(-0.5) + 0.55).abs() < 1e-6);
+        assert_eq!(settings.apply(0.9), 1.0);
+        let curved = settings.curve(ResponseCurve::Power { exponent: 2.0 });
+        assert!((curved.apply(0.5) - 0.325).abs() < 1e-6);
+
+        // Pushed a little way in two directions at once, which is out of
+        // a radial dead zone but inside an axial one.
+        let mut state = GamepadState::default();
+        let _ = state.raw_axis_values.insert(Axis::LeftStickX, 0.09);
+        let _ = state.raw_axis_values.insert(Axis::LeftStickY, 0.12);
+        let _ = state.raw_axis_values.insert(Axis::RightZ, 0.12);
+        let radial = AxisSettings::default().deadzone(0.1).saturation(0.2);
+        state.process_axes(|_| radial);
+        assert!((state.axis_value(Axis::LeftStickX) - 0.3).abs() < 1e-5);
+        assert!((state.axis_value(Axis::LeftStickY) - 0.4).abs() < 1e-5);
+        assert!((state.axis_value(Axis::RightZ) - 0.2).abs() < 1e-5);
+        state.process_axes(|_| radial.deadzone_shape(DeadzoneShape::Axial));
+        assert_eq!(state.axis_value(Axis::LeftStickX), 0.0);
+        assert!((state.axis_value(Axis::LeftStickY) - 0.2).abs() < 1e-5);
+
+        // Triggers reported as buttons use the settings of their axes.
+        let _ = state.raw_trigger_values.insert(Button::LeftTrigger2, 0.05);
+        let _ = state.raw_trigger_values.insert(Button::RightTrigger2, 0.6);
+        let _ = state.button_values.insert(Button::South, 0.05);
+        state.process_axes(|axis| match axis {
+            Axis::RightZ => AxisSettings::default().deadzone(0.2),
+            _ => AxisSettings::default(),
+        });
+        assert_eq!(state.button_value(Button::LeftTrigger2), 0.0);
+        assert!((state.button_value(Button::RightTrigger2) - 0.5).abs() < 1e-5);
+        assert_eq!(state.button_value(Button::South), 0.05);
+    }
+
+    #[test]
+    fn player_slots() {
+        let ids: Vec<_> = (0..6).map(test_id).collect();
+        let mut players = PlayerSlots::default();
+        for (i, &id) in ids[..5].iter().enumerate() {
+            players.connect(id, [i as u8; 16]);
+        }
+        assert_eq!(players.player(ids[0]), Some(1));
+        assert_eq!(players.player(ids[3]), Some(4));
+        assert_eq!(players.player(ids[4]), None);
+        assert_eq!(players.gamepad(2), Some(ids[1]));
+        assert_eq!(players.gamepad(0), None);
+        assert_eq!(players.gamepad(5), None);
+
+        // Player 2 keeps their gamepad through a disconnect.
+        players.disconnect(ids[1]);
+        assert_eq!(players.gamepad(2), None);
+        assert_eq!(players.player(ids[1]), Some(2));
+        players.connect(ids[5], [9; 16]);
+        assert_eq!(players.player(ids[5]), None);
+        players.connect(ids[1], [1; 16]);
+        assert_eq!(players.gamepad(2), Some(ids[1]));
+
+        // And gets it back under a new ID.
+        players.disconnect(ids[1]);
+        players.connect(ids[5], [1; 16]);
+        assert_eq!(players.gamepad(2), Some(ids[5]));
+
+        players.assign(ids[0], [0; 16], 3);
+        assert_eq!(players.gamepad(3), Some(ids[0]));
+        assert_eq!(players.gamepad(1), Some(ids[2]));
+        players.release(4);
+        players.connect(ids[4], [4; 16]);
+        assert_eq!(players.player(ids[4]), Some(4));
+    }
+
+    /// Makes up an ID, as `gilrs` only hands them out for real gamepads.
+    fn test_id(id: i64) -> GamepadId {
+        GamepadId(toml::Value::Integer(id).try_into().unwrap())
+    }
+
+    #[test]
+    fn axis_settings_overrides() {
+        let (first, second) = (test_id(0), test_id(1));
+        let mut config = AxisConfig::default();
+        config.setup.sticks = AxisSettings::default().deadzone(0.3);
+        assert_eq!(config.get(first, Axis::LeftStickX).deadzone, 0.3);
+        assert_eq!(config.get(first, Axis::LeftZ), AxisSettings::default());
+
+        let _ = config
+            .axes
+            .insert(Axis::LeftStickX, AxisSettings::default().deadzone(0.0));
+        let _ = config.gamepads.insert(
+            (second, Axis::LeftStickX),
+            AxisSettings::default().deadzone(0.5),
+        );
+        assert_eq!(config.get(first, Axis::LeftStickX).deadzone, 0.0);
+        assert_eq!(config.get(second, Axis::LeftStickX).deadzone, 0.5);
+        assert_eq!(config.get(second, Axis::LeftStickY).deadzone, 0.3);
     }
 }
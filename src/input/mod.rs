@@ -1,4 +1,7 @@
-//! Input handling modules for keyboard, mouse and gamepad.
+//! Input handling modules for keyboard, mouse, gamepad and touch, and for
+//! actions bound to any of them.
+pub mod action;
 pub mod gamepad;
 pub mod keyboard;
 pub mod mouse;
+pub mod touch;
@@ -3,12 +3,42 @@
 use crate::context::Context;
 use crate::error::GameError;
 use crate::error::GameResult;
-use std::collections::HashSet;
+use instant as time;
+use std::collections::{HashMap, HashSet};
 use winit::dpi;
 pub use winit::event::MouseButton;
 use winit::window::CursorGrabMode;
 pub use winit::window::CursorIcon;
 
+/// The thresholds used to tell clicks and drags apart.  Distances are in pixels.
+#[derive(Debug, Copy, Clone, PartialEq, smart_default::SmartDefault)]
+pub struct ClickSettings {
+    /// How long after a click the next one can come to count towards a
+    /// double or triple click.
+    #[default(time::Duration::from_millis(500))]
+    pub multi_click_interval: time::Duration,
+    /// How far from a click the next one can be to count towards a double or
+    /// triple click.
+    #[default = 4.0]
+    pub multi_click_distance: f32,
+    /// How far the cursor has to move with a button held for it to be a drag.
+    #[default = 4.0]
+    pub drag_threshold: f32,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Click {
+    position: glam::Vec2,
+    time: time::Instant,
+    count: u32,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Drag {
+    start: glam::Vec2,
+    dragging: bool,
+}
+
 /// Stores state information for the mouse input.
 // TODO: Add "differences with window cursor" notice
 #[derive(Clone, Debug)]
@@ -16,11 +46,23 @@ pub struct MouseContext {
     last_position: glam::Vec2,
     last_delta: glam::Vec2,
     delta: glam::Vec2,
+    raw_delta: glam::Vec2,
+    scroll: glam::Vec2,
     buttons_pressed: HashSet<MouseButton>,
     cursor_type: CursorIcon,
     cursor_grabbed: bool,
     cursor_hidden: bool,
+    relative_mode: bool,
     previous_buttons_pressed: HashSet<MouseButton>,
+    clicks: HashMap<MouseButton, Click>,
+    // The double, triple and further clicks made this frame, with how many
+    // clicks in a row they made.
+    multi_clicks: HashSet<(MouseButton, u32)>,
+    drags: HashMap<MouseButton, Drag>,
+    drags_started: HashSet<MouseButton>,
+    // Where drags which ended this frame started.
+    drags_ended: HashMap<MouseButton, glam::Vec2>,
+    click_settings: ClickSettings,
 }
 
 impl MouseContext {
@@ -29,11 +71,20 @@ impl MouseContext {
             last_position: glam::Vec2::ZERO,
             last_delta: glam::Vec2::ZERO,
             delta: glam::Vec2::ZERO,
+            raw_delta: glam::Vec2::ZERO,
+            scroll: glam::Vec2::ZERO,
             cursor_type: CursorIcon::Default,
             buttons_pressed: HashSet::new(),
             cursor_grabbed: false,
             cursor_hidden: false,
+            relative_mode: false,
             previous_buttons_pressed: HashSet::new(),
+            clicks: HashMap::new(),
+            multi_clicks: HashSet::new(),
+            drags: HashMap::new(),
+            drags_started: HashSet::new(),
+            drags_ended: HashMap::new(),
+            click_settings: ClickSettings::default(),
         }
     }
 
@@ -47,6 +98,12 @@ impl MouseContext {
         self.cursor_hidden
     }
 
+    /// Returns whether or not the mouse is in relative mode; see
+    /// [`set_relative_mode()`](fn.set_relative_mode.html).
+    pub fn relative_mode(&self) -> bool {
+        self.relative_mode
+    }
+
     /// Get the current position of the mouse cursor, in pixels.
     /// Complement to [`set_position()`](fn.set_position.html).
     /// Uses strictly window-only coordinates.
@@ -59,6 +116,20 @@ impl MouseContext {
         self.delta.into()
     }
 
+    /// Get the distance the mouse itself was moved during the current frame, in device units.
+    /// Unlike [`delta()`](#method.delta) this comes straight from the device, so it isn't
+    /// stopped by the edges of the screen or affected by pointer acceleration.
+    pub fn raw_delta(&self) -> mint::Point2<f32> {
+        self.raw_delta.into()
+    }
+
+    /// Get how far the mouse wheel was scrolled during the current frame, summed up from
+    /// every [`mouse_wheel_event`](../../event/trait.EventHandler.html#method.mouse_wheel_event),
+    /// in the same units as it gets.
+    pub fn scroll_delta(&self) -> mint::Point2<f32> {
+        self.scroll.into()
+    }
+
     /// Returns whether or not the given mouse button is pressed.
 
     pub fn button_pressed(&self, button: MouseButton) -> bool {
@@ -75,6 +146,64 @@ impl MouseContext {
         !self.buttons_pressed.contains(&button) && self.previous_buttons_pressed.contains(&button)
     }
 
+    /// Returns how many clicks in a row the latest press of the given mouse button
+    /// makes: `1` for a single click, `2` for a double click, and so on.  Presses count
+    /// towards the same run when each comes soon enough after, and close enough to,
+    /// the one before, as set in [`ClickSettings`](struct.ClickSettings.html).
+    /// Returns `0` if the button has never been pressed.
+    pub fn click_count(&self, button: MouseButton) -> u32 {
+        self.clicks.get(&button).map_or(0, |click| click.count)
+    }
+
+    /// Returns whether or not the given mouse button has been double-clicked this frame,
+    /// even if it has been released again since.
+    pub fn button_double_clicked(&self, button: MouseButton) -> bool {
+        self.multi_clicks.contains(&(button, 2))
+    }
+
+    /// Returns whether or not the given mouse button has been triple-clicked this frame,
+    /// even if it has been released again since.
+    pub fn button_triple_clicked(&self, button: MouseButton) -> bool {
+        self.multi_clicks.contains(&(button, 3))
+    }
+
+    /// Returns whether or not the mouse is being dragged with the given button held,
+    /// having moved further than the drag threshold since it was pressed.
+    pub fn is_dragging(&self, button: MouseButton) -> bool {
+        self.drags.get(&button).is_some_and(|drag| drag.dragging)
+    }
+
+    /// Returns whether or not a drag with the given button passed the drag threshold this frame.
+    pub fn drag_just_started(&self, button: MouseButton) -> bool {
+        self.drags_started.contains(&button)
+    }
+
+    /// Returns whether or not a drag with the given button was released this frame.
+    pub fn drag_just_ended(&self, button: MouseButton) -> bool {
+        self.drags_ended.contains_key(&button)
+    }
+
+    /// Returns where the cursor was when the given button was pressed to start a drag,
+    /// while the drag goes on and on the frame it ends.
+    pub fn drag_start(&self, button: MouseButton) -> Option<mint::Point2<f32>> {
+        self.drags
+            .get(&button)
+            .filter(|drag| drag.dragging)
+            .map(|drag| drag.start)
+            .or_else(|| self.drags_ended.get(&button).copied())
+            .map(Into::into)
+    }
+
+    /// Returns the thresholds used to tell clicks and drags apart.
+    pub fn click_settings(&self) -> &ClickSettings {
+        &self.click_settings
+    }
+
+    /// Sets the thresholds used to tell clicks and drags apart.
+    pub fn set_click_settings(&mut self, settings: ClickSettings) {
+        self.click_settings = settings;
+    }
+
     /// Updates delta and position values.
     /// The inputs are interpreted as pixel coordinates inside the window.
     ///
@@ -100,20 +229,55 @@ impl MouseContext {
         // It represents only the change between the last mouse event and the current one.
         self.set_last_delta(diff);
         self.set_last_position(glam::Vec2::new(new_x, new_y));
+
+        let threshold = self.click_settings.drag_threshold;
+        for (button, drag) in &mut self.drags {
+            if !drag.dragging && drag.start.distance(self.last_position) > threshold {
+                drag.dragging = true;
+                let _ = self.drags_started.insert(*button);
+            }
+        }
+    }
+
+    /// Adds to the raw delta value, in device units.
+    ///
+    /// This function is called internally whenever the mouse device reports motion.
+    /// It can also be used to simulate mouse input.
+    /// Calling this function alone won't trigger a
+    /// [`raw_mouse_motion_event`](../../event/trait.EventHandler.html#method.raw_mouse_motion_event) though.
+    pub fn handle_raw_motion(&mut self, dx: f32, dy: f32) {
+        self.raw_delta += glam::Vec2::new(dx, dy);
     }
 
-    /// Resets the value returned by [`mouse::delta`](fn.delta.html) to zero.
+    /// Adds to the scroll delta value.
+    ///
+    /// This function is called internally whenever the mouse wheel is scrolled.
+    /// It can also be used to simulate mouse input.
+    /// Calling this function alone won't trigger a
+    /// [`mouse_wheel_event`](../../event/trait.EventHandler.html#method.mouse_wheel_event) though.
+    pub fn handle_scroll(&mut self, x: f32, y: f32) {
+        self.scroll += glam::Vec2::new(x, y);
+    }
+
+    /// Resets the values returned by [`mouse::delta`](fn.delta.html),
+    /// [`raw_delta`](#method.raw_delta) and [`scroll_delta`](#method.scroll_delta) to zero.
     /// You shouldn't need to call this, except when you're running your own event loop.
     /// In this case call it right at the end, after `draw` and `update` have finished.
     pub fn reset_delta(&mut self) {
         self.delta = glam::Vec2::ZERO;
+        self.raw_delta = glam::Vec2::ZERO;
+        self.scroll = glam::Vec2::ZERO;
     }
 
     /// Copies the current state of the mouse buttons into the context. If you are writing your own event loop
     /// you need to call this at the end of every update in order to use the functions `is_button_just_pressed`
-    /// and `is_button_just_released`. Otherwise this is handled for you.
+    /// and `is_button_just_released`, along with the ones about double clicks and drags.
+    /// Otherwise this is handled for you.
     pub fn save_mouse_state(&mut self) {
         self.previous_buttons_pressed = self.buttons_pressed.clone();
+        self.multi_clicks.clear();
+        self.drags_started.clear();
+        self.drags_ended.clear();
     }
 
     pub(crate) fn set_last_position(&mut self, p: glam::Vec2) {
@@ -129,10 +293,51 @@ impl MouseContext {
     }
 
     pub(crate) fn set_button(&mut self, button: MouseButton, pressed: bool) {
+        self.set_button_at(button, pressed, time::Instant::now());
+    }
+
+    fn set_button_at(&mut self, button: MouseButton, pressed: bool, now: time::Instant) {
         if pressed {
-            let _ = self.buttons_pressed.insert(button);
+            if !self.buttons_pressed.insert(button) {
+                // Not a new press, so neither a click nor the start of a drag.
+                return;
+            }
+            let position = self.last_position;
+            let settings = &self.click_settings;
+            let count = match self.clicks.get(&button) {
+                Some(last)
+                    if now.duration_since(last.time) <= settings.multi_click_interval
+                        && last.position.distance(position) <= settings.multi_click_distance =>
+                {
+                    last.count + 1
+                }
+                _ => 1,
+            };
+            if count > 1 {
+                let _ = self.multi_clicks.insert((button, count));
+            }
+            let _ = self.clicks.insert(
+                button,
+                Click {
+                    position,
+                    time: now,
+                    count,
+                },
+            );
+            let _ = self.drags.insert(
+                button,
+                Drag {
+                    start: position,
+                    dragging: false,
+                },
+            );
         } else {
             let _ = self.buttons_pressed.remove(&button);
+            if let Some(drag) = self.drags.remove(&button) {
+                if drag.dragging {
+                    let _ = self.drags_ended.insert(button, drag.start);
+                }
+            }
         }
     }
 
@@ -214,7 +419,9 @@ pub fn handle_move(ctx: &mut Context, new_x: f32, new_y: f32) {
 // TODO: Move to graphics context (This isn't input)
 pub fn set_cursor_hidden(ctx: &mut Context, hidden: bool) {
     ctx.mouse.cursor_hidden = hidden;
-    ctx.gfx.window.set_cursor_visible(!hidden);
+    ctx.gfx
+        .window
+        .set_cursor_visible(!(hidden || ctx.mouse.relative_mode));
 }
 
 /// Modifies the mouse cursor type of the window.
@@ -237,6 +444,15 @@ pub fn cursor_grabbed(ctx: &Context) -> bool {
 #[allow(clippy::missing_errors_doc)]
 pub fn set_cursor_grabbed(ctx: &mut Context, grabbed: bool) -> GameResult {
     ctx.mouse.cursor_grabbed = grabbed;
+    if ctx.mouse.relative_mode {
+        // Relative mode has the cursor grabbed already, and puts it back as
+        // set here when it ends.
+        return Ok(());
+    }
+    set_cursor_grab(ctx, grabbed)
+}
+
+fn set_cursor_grab(ctx: &mut Context, grabbed: bool) -> GameResult {
     ctx.gfx
         .window
         .set_cursor_grab(if grabbed {
@@ -251,6 +467,43 @@ pub fn set_cursor_grabbed(ctx: &mut Context, grabbed: bool) -> GameResult {
         .map_err(|e| GameError::WindowError(e.to_string()))
 }
 
+/// Get whether or not the mouse is in relative mode.
+// TODO: Move to graphics context (This isn't input)
+pub fn relative_mode(ctx: &Context) -> bool {
+    ctx.mouse.relative_mode
+}
+
+/// Set whether or not the mouse is in relative mode, where the cursor is hidden
+/// and kept in place (or, where the platform can't do that, confined to the window),
+/// so that it can be moved without limit.  This is meant for controlling a camera
+/// with [`raw_delta`](struct.MouseContext.html#method.raw_delta) or
+/// [`raw_mouse_motion_event`](../../event/trait.EventHandler.html#method.raw_mouse_motion_event),
+/// as the cursor position stops changing, or changes only within the window.
+///
+/// When relative mode ends, the cursor goes back to being grabbed and hidden
+/// as set with [`set_cursor_grabbed()`](fn.set_cursor_grabbed.html) and
+/// [`set_cursor_hidden()`](fn.set_cursor_hidden.html).
+/// ### Errors
+///
+/// Will return `GameError::WindowError` if the platform can't grab the cursor.
+// TODO: Move to graphics context (This isn't input)
+pub fn set_relative_mode(ctx: &mut Context, relative: bool) -> GameResult {
+    if relative {
+        let window = &ctx.gfx.window;
+        window
+            .set_cursor_grab(CursorGrabMode::Locked)
+            .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined))
+            .map_err(|e| GameError::WindowError(e.to_string()))?;
+    } else {
+        set_cursor_grab(ctx, ctx.mouse.cursor_grabbed)?;
+    }
+    ctx.mouse.relative_mode = relative;
+    ctx.gfx
+        .window
+        .set_cursor_visible(!(relative || ctx.mouse.cursor_hidden));
+    Ok(())
+}
+
 /// Set the current position of the mouse cursor, in pixels.
 /// Uses strictly window-only coordinates.
 /// ### Errors
@@ -271,3 +524,100 @@ where
         })
         .map_err(|_| GameError::WindowError("Couldn't set mouse cursor position!".to_owned()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multi_clicks() {
+        let ms = time::Duration::from_millis;
+        let start = time::Instant::now();
+        let mut mouse = MouseContext::new();
+        assert_eq!(mouse.click_count(MouseButton::Left), 0);
+        for (i, count) in [1, 2, 3, 4].into_iter().enumerate() {
+            let now = start + ms(200 * i as u64);
+            mouse.set_button_at(MouseButton::Left, true, now);
+            assert_eq!(mouse.click_count(MouseButton::Left), count);
+            assert_eq!(mouse.button_double_clicked(MouseButton::Left), count == 2);
+            assert_eq!(mouse.button_triple_clicked(MouseButton::Left), count == 3);
+            mouse.save_mouse_state();
+            mouse.set_button_at(MouseButton::Left, false, now + ms(50));
+            mouse.save_mouse_state();
+        }
+
+        // A whole double click between two frames still counts.
+        mouse.set_button_at(MouseButton::Left, true, start + ms(2000));
+        mouse.set_button_at(MouseButton::Left, false, start + ms(2050));
+        mouse.set_button_at(MouseButton::Left, true, start + ms(2100));
+        mouse.set_button_at(MouseButton::Left, false, start + ms(2150));
+        assert!(mouse.button_double_clicked(MouseButton::Left));
+        assert!(!mouse.button_triple_clicked(MouseButton::Left));
+        mouse.save_mouse_state();
+        assert!(!mouse.button_double_clicked(MouseButton::Left));
+
+        // Too slow.
+        mouse.set_button_at(MouseButton::Left, true, start + ms(4000));
+        assert_eq!(mouse.click_count(MouseButton::Left), 1);
+        mouse.set_button_at(MouseButton::Left, false, start + ms(4000));
+        // Too far away.
+        mouse.handle_move(10.0, 0.0);
+        mouse.set_button_at(MouseButton::Left, true, start + ms(4100));
+        assert_eq!(mouse.click_count(MouseButton::Left), 1);
+        // Other buttons count separately.
+        mouse.set_button_at(MouseButton::Right, true, start + ms(4100));
+        assert_eq!(mouse.click_count(MouseButton::Right), 1);
+    }
+
+    #[test]
+    fn drags() {
+        let mut mouse = MouseContext::new();
+        mouse.handle_move(10.0, 10.0);
+        mouse.set_button(MouseButton::Left, true);
+        mouse.handle_move(12.0, 12.0);
+        assert!(!mouse.is_dragging(MouseButton::Left));
+        assert_eq!(mouse.drag_start(MouseButton::Left), None);
+
+        mouse.handle_move(20.0, 10.0);
+        assert!(mouse.is_dragging(MouseButton::Left));
+        assert!(mouse.drag_just_started(MouseButton::Left));
+        assert!(!mouse.is_dragging(MouseButton::Right));
+        mouse.save_mouse_state();
+        mouse.handle_move(30.0, 10.0);
+        assert!(!mouse.drag_just_started(MouseButton::Left));
+
+        mouse.set_button(MouseButton::Left, false);
+        assert!(!mouse.is_dragging(MouseButton::Left));
+        assert!(mouse.drag_just_ended(MouseButton::Left));
+        assert_eq!(
+            mouse.drag_start(MouseButton::Left),
+            Some(mint::Point2 { x: 10.0, y: 10.0 })
+        );
+        mouse.save_mouse_state();
+        assert!(!mouse.drag_just_ended(MouseButton::Left));
+        assert_eq!(mouse.drag_start(MouseButton::Left), None);
+    }
+
+    #[test]
+    fn scrolling() {
+        let mut mouse = MouseContext::new();
+        mouse.handle_scroll(0.0, 1.0);
+        mouse.handle_scroll(0.5, 2.0);
+        assert_eq!(mouse.scroll_delta(), mint::Point2 { x: 0.5, y: 3.0 });
+        mouse.reset_delta();
+        assert_eq!(mouse.scroll_delta(), mint::Point2 { x: 0.0, y: 0.0 });
+    }
+
+    #[test]
+    fn raw_motion() {
+        let mut mouse = MouseContext::new();
+        mouse.handle_move(10.0, 10.0);
+        mouse.handle_raw_motion(3.0, -1.0);
+        mouse.handle_raw_motion(2.0, -1.0);
+        assert_eq!(mouse.raw_delta(), mint::Point2 { x: 5.0, y: -2.0 });
+        // The raw delta is separate from the cursor's.
+        assert_eq!(mouse.delta(), mint::Point2 { x: 10.0, y: 10.0 });
+        mouse.reset_delta();
+        assert_eq!(mouse.raw_delta(), mint::Point2 { x: 0.0, y: 0.0 });
+    }
+}
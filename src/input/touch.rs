@@ -0,0 +1,542 @@
+//! Touchscreen utility functions; allow querying every finger on the
+//! screen, and recognize gestures made with them.
+//!
+//! Every touch has an ID which stays the same from when the finger goes
+//! down to when it comes back up.  The first finger down, while no others
+//! are, is the primary touch, which is the one
+//! [`EventHandler::touch_event()`](../../event/trait.EventHandler.html#method.touch_event)
+//! turns into mouse input by default.
+//!
+//! Gestures are recognized from the touches as they go, and can be either
+//! read each frame with [`TouchContext::gestures()`](struct.TouchContext.html#method.gestures)
+//! or handled in
+//! [`EventHandler::gesture_event()`](../../event/trait.EventHandler.html#method.gesture_event).
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use instant as time;
+
+/// A finger on the screen.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Touch {
+    /// The ID of the touch, unique among those on the screen at once.
+    pub id: u64,
+    /// Where the touch is now, in physical pixels.
+    pub position: mint::Point2<f32>,
+    /// Where the touch started.
+    pub start_position: mint::Point2<f32>,
+    /// When the touch started.
+    pub start_time: time::Instant,
+}
+
+/// A gesture made with one or more touches.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Gesture {
+    /// A quick touch without moving.
+    Tap {
+        /// Where the touch was.
+        position: mint::Point2<f32>,
+    },
+    /// A second tap quickly after the first, in about the same place.  The
+    /// second tap is reported as a `Tap` as well, before this.
+    DoubleTap {
+        /// Where the second tap was.
+        position: mint::Point2<f32>,
+    },
+    /// A touch held down without moving.  Reported once, while it is still
+    /// held, and the touch isn't then a tap or swipe when it ends.
+    LongPress {
+        /// Where the touch is.
+        position: mint::Point2<f32>,
+    },
+    /// A quick stroke in one direction.
+    Swipe {
+        /// Where the touch started.
+        start: mint::Point2<f32>,
+        /// Where the touch ended.
+        end: mint::Point2<f32>,
+        /// How long the swipe took.
+        duration: time::Duration,
+    },
+    /// Two touches moving closer together or further apart.  Reported
+    /// every time they move, once they've moved far enough to count.
+    Pinch {
+        /// The point halfway between the touches.
+        center: mint::Point2<f32>,
+        /// How far apart the touches are, relative to where they were when
+        /// it was last reported: above `1.0` for spreading out, and below it
+        /// for pinching in.
+        scale: f32,
+    },
+    /// Two touches turning around each other.  Reported every time they move,
+    /// once they've turned far enough to count.
+    Rotate {
+        /// The point halfway between the touches.
+        center: mint::Point2<f32>,
+        /// How far they have turned since it was last reported, in radians,
+        /// clockwise on the screen.
+        angle: f32,
+    },
+}
+
+/// The thresholds used to tell gestures apart.  Distances are in physical
+/// pixels.
+#[derive(Debug, Copy, Clone, PartialEq, smart_default::SmartDefault)]
+pub struct GestureSettings {
+    /// How far a touch can wander and still be a tap or long press.
+    #[default = 10.0]
+    pub tap_distance: f32,
+    /// How long a touch can last and still be a tap.
+    #[default(time::Duration::from_millis(300))]
+    pub tap_duration: time::Duration,
+    /// How long after a tap a second one can come to make a double tap.
+    #[default(time::Duration::from_millis(300))]
+    pub double_tap_interval: time::Duration,
+    /// How far from a tap a second one can be to make a double tap.
+    #[default = 30.0]
+    pub double_tap_distance: f32,
+    /// How long a touch has to be held to be a long press.
+    #[default(time::Duration::from_millis(500))]
+    pub long_press_duration: time::Duration,
+    /// How far a touch has to go to be a swipe.
+    #[default = 50.0]
+    pub swipe_distance: f32,
+    /// How long a touch can last and still be a swipe.
+    #[default(time::Duration::from_millis(500))]
+    pub swipe_duration: time::Duration,
+    /// How much two touches have to spread out or close in, relative to
+    /// where they started, to start pinching.
+    #[default = 0.05]
+    pub pinch_scale: f32,
+    /// How far two touches have to turn, in radians, to start rotating.
+    #[default = 0.1]
+    pub rotate_angle: f32,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct TrackedTouch {
+    touch: Touch,
+    // Whether the touch has gone too far to be a tap or long press.
+    moved: bool,
+    long_pressed: bool,
+    // Whether another touch has joined it, which makes it part of a two
+    // finger gesture rather than one of its own.
+    multi: bool,
+}
+
+/// How two touches were last reported as being placed, for pinching and
+/// rotating.
+#[derive(Debug, Copy, Clone)]
+struct TwoFingers {
+    ids: [u64; 2],
+    distance: f32,
+    angle: f32,
+    pinching: bool,
+    rotating: bool,
+}
+
+/// A structure that contains the touchscreen state.
+#[derive(Debug, Default)]
+pub struct TouchContext {
+    touches: HashMap<u64, TrackedTouch>,
+    primary: Option<u64>,
+    two_fingers: Option<TwoFingers>,
+    // The last tap, as long as it could still be the first of a double tap.
+    last_tap: Option<(mint::Point2<f32>, time::Instant)>,
+    gestures: Vec<Gesture>,
+    settings: GestureSettings,
+}
+
+fn distance(a: mint::Point2<f32>, b: mint::Point2<f32>) -> f32 {
+    glam::Vec2::from(a).distance(glam::Vec2::from(b))
+}
+
+fn midpoint(a: mint::Point2<f32>, b: mint::Point2<f32>) -> mint::Point2<f32> {
+    ((glam::Vec2::from(a) + glam::Vec2::from(b)) / 2.0).into()
+}
+
+fn angle(a: mint::Point2<f32>, b: mint::Point2<f32>) -> f32 {
+    (b.y - a.y).atan2(b.x - a.x)
+}
+
+/// Wraps an angle into `-PI..=PI`.
+fn wrap_angle(angle: f32) -> f32 {
+    let angle = angle.rem_euclid(2.0 * PI);
+    if angle > PI {
+        angle - 2.0 * PI
+    } else {
+        angle
+    }
+}
+
+impl TouchContext {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the touch with the given ID, if it's on the screen.
+    pub fn touch(&self, id: u64) -> Option<&Touch> {
+        self.touches.get(&id).map(|tracked| &tracked.touch)
+    }
+
+    /// Returns every touch on the screen, in no particular order.
+    pub fn touches(&self) -> impl Iterator<Item = &Touch> {
+        self.touches.values().map(|tracked| &tracked.touch)
+    }
+
+    /// Returns how many touches are on the screen.
+    pub fn touch_count(&self) -> usize {
+        self.touches.len()
+    }
+
+    /// Returns the primary touch, if it's still on the screen.
+    pub fn primary_touch(&self) -> Option<&Touch> {
+        self.primary.and_then(|id| self.touch(id))
+    }
+
+    /// Checks if a touch is, or was when it ended, the primary touch.
+    pub fn is_primary(&self, id: u64) -> bool {
+        self.primary == Some(id)
+    }
+
+    /// Returns the gestures recognized this frame, oldest first.
+    pub fn gestures(&self) -> &[Gesture] {
+        &self.gestures
+    }
+
+    /// Returns the thresholds used to recognize gestures.
+    pub fn gesture_settings(&self) -> &GestureSettings {
+        &self.settings
+    }
+
+    /// Sets the thresholds used to recognize gestures.
+    pub fn set_gesture_settings(&mut self, settings: GestureSettings) {
+        self.settings = settings;
+    }
+
+    /// Forgets the gestures recognized this frame. If you are writing your own event loop
+    /// you need to call this at the end of every update in order to use the function `gestures`.
+    /// Otherwise this is handled for you.
+    pub fn clear_gestures(&mut self) {
+        self.gestures.clear();
+    }
+
+    /// Recognizes gestures which come from touches being held, rather than
+    /// moving.  If you are writing your own event loop you need to call this
+    /// every update, before handling gestures.  Otherwise this is handled for you.
+    pub fn update(&mut self) {
+        self.update_at(time::Instant::now());
+    }
+
+    pub(crate) fn handle_touch(
+        &mut self,
+        id: u64,
+        phase: winit::event::TouchPhase,
+        x: f32,
+        y: f32,
+    ) {
+        self.handle_touch_at(id, phase, mint::Point2 { x, y }, time::Instant::now());
+    }
+
+    fn update_at(&mut self, now: time::Instant) {
+        for tracked in self.touches.values_mut() {
+            if !tracked.moved
+                && !tracked.long_pressed
+                && !tracked.multi
+                && now.duration_since(tracked.touch.start_time) >= self.settings.long_press_duration
+            {
+                tracked.long_pressed = true;
+                self.gestures.push(Gesture::LongPress {
+                    position: tracked.touch.position,
+                });
+            }
+        }
+    }
+
+    fn handle_touch_at(
+        &mut self,
+        id: u64,
+        phase: winit::event::TouchPhase,
+        position: mint::Point2<f32>,
+        now: time::Instant,
+    ) {
+        use winit::event::TouchPhase;
+
+        match phase {
+            TouchPhase::Started => self.start(id, position, now),
+            TouchPhase::Moved => self.move_to(id, position),
+            TouchPhase::Ended => self.end(id, position, now),
+            TouchPhase::Cancelled => {
+                let _ = self.touches.remove(&id);
+                self.two_fingers = None;
+            }
+        }
+    }
+
+    fn start(&mut self, id: u64, position: mint::Point2<f32>, now: time::Instant) {
+        if self.touches.is_empty() {
+            self.primary = Some(id);
+        }
+        let _ = self.touches.insert(
+            id,
+            TrackedTouch {
+                touch: Touch {
+                    id,
+                    position,
+                    start_position: position,
+                    start_time: now,
+                },
+                moved: false,
+                long_pressed: false,
+                multi: false,
+            },
+        );
+        if self.touches.len() > 1 {
+            for tracked in self.touches.values_mut() {
+                tracked.multi = true;
+            }
+        }
+        self.two_fingers = self
+            .placement()
+            .map(|(ids, distance, angle, _)| TwoFingers {
+                ids,
+                distance,
+                angle,
+                pinching: false,
+                rotating: false,
+            });
+    }
+
+    fn move_to(&mut self, id: u64, position: mint::Point2<f32>) {
+        let Some(tracked) = self.touches.get_mut(&id) else {
+            return;
+        };
+        tracked.touch.position = position;
+        if distance(tracked.touch.start_position, position) > self.settings.tap_distance {
+            tracked.moved = true;
+        }
+
+        let (Some((ids, distance, angle, center)), Some(two)) =
+            (self.placement(), self.two_fingers.as_mut())
+        else {
+            return;
+        };
+        if two.ids != ids {
+            return;
+        }
+        if !two.pinching && (distance / two.distance - 1.0).abs() > self.settings.pinch_scale {
+            two.pinching = true;
+        }
+        if two.pinching && distance != two.distance && two.distance > 0.0 {
+            self.gestures.push(Gesture::Pinch {
+                center,
+                scale: distance / two.distance,
+            });
+            two.distance = distance;
+        }
+        let turned = wrap_angle(angle - two.angle);
+        if !two.rotating && turned.abs() > self.settings.rotate_angle {
+            two.rotating = true;
+        }
+        if two.rotating && turned != 0.0 {
+            self.gestures.push(Gesture::Rotate {
+                center,
+                angle: turned,
+            });
+            two.angle = angle;
+        }
+    }
+
+    fn end(&mut self, id: u64, position: mint::Point2<f32>, now: time::Instant) {
+        let Some(mut tracked) = self.touches.remove(&id) else {
+            return;
+        };
+        tracked.touch.position = position;
+        if self.touches.len() < 2 {
+            self.two_fingers = None;
+        }
+        if tracked.multi || tracked.long_pressed {
+            return;
+        }
+
+        let touch = tracked.touch;
+        let duration = now.duration_since(touch.start_time);
+        let travelled = distance(touch.start_position, position);
+        if travelled <= self.settings.tap_distance && duration <= self.settings.tap_duration {
+            self.gestures.push(Gesture::Tap { position });
+            let double = self.last_tap.is_some_and(|(last, time)| {
+                now.duration_since(time) <= self.settings.double_tap_interval
+                    && distance(last, position) <= self.settings.double_tap_distance
+            });
+            if double {
+                self.gestures.push(Gesture::DoubleTap { position });
+                self.last_tap = None;
+            } else {
+                self.last_tap = Some((position, now));
+            }
+        } else if travelled >= self.settings.swipe_distance
+            && duration <= self.settings.swipe_duration
+        {
+            self.gestures.push(Gesture::Swipe {
+                start: touch.start_position,
+                end: position,
+                duration,
+            });
+        }
+    }
+
+    /// Returns the IDs of the two touches on the screen, in order, along with
+    /// how far apart they are, the angle between them and their midpoint, if
+    /// there are exactly two.
+    fn placement(&self) -> Option<([u64; 2], f32, f32, mint::Point2<f32>)> {
+        if self.touches.len() != 2 {
+            return None;
+        }
+        let mut touches: Vec<_> = self.touches.values().map(|t| t.touch).collect();
+        touches.sort_by_key(|touch| touch.id);
+        let (a, b) = (touches[0].position, touches[1].position);
+        Some((
+            [touches[0].id, touches[1].id],
+            distance(a, b),
+            angle(a, b),
+            midpoint(a, b),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::event::TouchPhase;
+
+    fn at(x: f32, y: f32) -> mint::Point2<f32> {
+        mint::Point2 { x, y }
+    }
+
+    #[test]
+    fn tracks_touches() {
+        let start = time::Instant::now();
+        let mut touch = TouchContext::new();
+        touch.handle_touch_at(7, TouchPhase::Started, at(1.0, 2.0), start);
+        touch.handle_touch_at(3, TouchPhase::Started, at(500.0, 2.0), start);
+        touch.handle_touch_at(3, TouchPhase::Moved, at(501.0, 2.0), start);
+        assert_eq!(touch.touch_count(), 2);
+        assert_eq!(touch.touch(3).unwrap().position, at(501.0, 2.0));
+        assert_eq!(touch.touch(3).unwrap().start_position, at(500.0, 2.0));
+        assert_eq!(touch.primary_touch().unwrap().id, 7);
+
+        // The primary touch stays the same until every finger is lifted.
+        touch.handle_touch_at(7, TouchPhase::Ended, at(1.0, 2.0), start);
+        assert!(touch.is_primary(7));
+        assert!(touch.primary_touch().is_none());
+        touch.handle_touch_at(8, TouchPhase::Started, at(1.0, 2.0), start);
+        assert!(!touch.is_primary(8));
+        touch.handle_touch_at(3, TouchPhase::Cancelled, at(501.0, 2.0), start);
+        touch.handle_touch_at(8, TouchPhase::Ended, at(1.0, 2.0), start);
+        touch.handle_touch_at(9, TouchPhase::Started, at(1.0, 2.0), start);
+        assert!(touch.is_primary(9));
+        assert!(touch.touch(3).is_none());
+        // None of that made a gesture, as there was always another finger down.
+        assert!(touch.gestures().is_empty());
+    }
+
+    #[test]
+    fn taps_presses_and_swipes() {
+        let ms = time::Duration::from_millis;
+        let start = time::Instant::now();
+        let mut touch = TouchContext::new();
+        touch.handle_touch_at(0, TouchPhase::Started, at(10.0, 10.0), start);
+        touch.handle_touch_at(0, TouchPhase::Ended, at(12.0, 10.0), start + ms(100));
+        touch.handle_touch_at(1, TouchPhase::Started, at(20.0, 10.0), start + ms(200));
+        touch.handle_touch_at(1, TouchPhase::Ended, at(20.0, 10.0), start + ms(250));
+        assert_eq!(
+            touch.gestures(),
+            [
+                Gesture::Tap {
+                    position: at(12.0, 10.0)
+                },
+                Gesture::Tap {
+                    position: at(20.0, 10.0)
+                },
+                Gesture::DoubleTap {
+                    position: at(20.0, 10.0)
+                }
+            ]
+        );
+        touch.clear_gestures();
+
+        // Too slow for a tap.
+        touch.handle_touch_at(2, TouchPhase::Started, at(10.0, 10.0), start + ms(1000));
+        touch.update_at(start + ms(1400));
+        assert!(touch.gestures().is_empty());
+        touch.update_at(start + ms(1500));
+        touch.update_at(start + ms(1600));
+        touch.handle_touch_at(2, TouchPhase::Ended, at(10.0, 10.0), start + ms(1700));
+        assert_eq!(
+            touch.gestures(),
+            [Gesture::LongPress {
+                position: at(10.0, 10.0)
+            }]
+        );
+        touch.clear_gestures();
+
+        touch.handle_touch_at(3, TouchPhase::Started, at(10.0, 10.0), start + ms(2000));
+        touch.handle_touch_at(3, TouchPhase::Moved, at(40.0, 10.0), start + ms(2100));
+        touch.handle_touch_at(3, TouchPhase::Ended, at(100.0, 10.0), start + ms(2200));
+        // Moved too far to be a long press, and too short to be a swipe.
+        touch.handle_touch_at(4, TouchPhase::Started, at(10.0, 10.0), start + ms(3000));
+        touch.handle_touch_at(4, TouchPhase::Moved, at(40.0, 10.0), start + ms(3100));
+        touch.update_at(start + ms(3600));
+        touch.handle_touch_at(4, TouchPhase::Ended, at(40.0, 10.0), start + ms(3700));
+        assert_eq!(
+            touch.gestures(),
+            [Gesture::Swipe {
+                start: at(10.0, 10.0),
+                end: at(100.0, 10.0),
+                duration: ms(200),
+            }]
+        );
+    }
+
+    #[test]
+    fn pinches_and_rotations() {
+        let start = time::Instant::now();
+        let mut touch = TouchContext::new();
+        touch.handle_touch_at(0, TouchPhase::Started, at(-10.0, 0.0), start);
+        touch.handle_touch_at(1, TouchPhase::Started, at(10.0, 0.0), start);
+        // Too small a change to count.
+        touch.handle_touch_at(1, TouchPhase::Moved, at(10.5, 0.0), start);
+        assert!(touch.gestures().is_empty());
+
+        touch.handle_touch_at(0, TouchPhase::Moved, at(-20.0, 0.0), start);
+        touch.handle_touch_at(1, TouchPhase::Moved, at(20.0, 0.0), start);
+        let scales: Vec<f32> = touch
+            .gestures()
+            .iter()
+            .map(|gesture| match gesture {
+                Gesture::Pinch { scale, .. } => *scale,
+                other => panic!("{other:?}"),
+            })
+            .collect();
+        assert!((scales.iter().product::<f32>() - 2.0).abs() < 1e-5);
+        touch.clear_gestures();
+
+        // A quarter turn clockwise, around the middle.
+        touch.handle_touch_at(0, TouchPhase::Moved, at(0.0, -20.0), start);
+        touch.handle_touch_at(1, TouchPhase::Moved, at(0.0, 20.0), start);
+        let turned: f32 = touch
+            .gestures()
+            .iter()
+            .filter_map(|gesture| match gesture {
+                Gesture::Rotate { angle, .. } => Some(*angle),
+                _ => None,
+            })
+            .sum();
+        assert!((turned - PI / 2.0).abs() < 1e-5);
+        assert!(matches!(
+            touch.gestures().last(),
+            Some(Gesture::Rotate { center, .. }) if *center == at(0.0, 0.0)
+        ));
+    }
+}